@@ -1,6 +1,65 @@
 use crate::command::{CommandError, Data};
 use anyhow::Context;
-use riven::consts::RegionalRoute;
+use riven::consts::PlatformRoute;
+use the_collector_types::{account_route, Puuid};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Region {
+    #[name = "NA"]
+    Na,
+    #[name = "EUW"]
+    Euw,
+    #[name = "EUNE"]
+    Eune,
+    #[name = "OCE"]
+    Oce,
+    #[name = "KR"]
+    Kr,
+    #[name = "JP"]
+    Jp,
+    #[name = "BR"]
+    Br,
+    #[name = "LAN"]
+    Lan,
+    #[name = "LAS"]
+    Las,
+    #[name = "TR"]
+    Tr,
+    #[name = "RU"]
+    Ru,
+    #[name = "ME"]
+    Me,
+    #[name = "SEA"]
+    Sea,
+    #[name = "TW"]
+    Tw,
+    #[name = "VN"]
+    Vn,
+}
+
+impl Region {
+    /// Platform of the region, as routed to by the Riot API.
+    pub fn platform(&self) -> PlatformRoute {
+        match self {
+            Self::Na => PlatformRoute::NA1,
+            Self::Euw => PlatformRoute::EUW1,
+            Self::Eune => PlatformRoute::EUN1,
+            Self::Oce => PlatformRoute::OC1,
+            Self::Kr => PlatformRoute::KR,
+            Self::Jp => PlatformRoute::JP1,
+            Self::Br => PlatformRoute::BR1,
+            Self::Lan => PlatformRoute::LA1,
+            Self::Las => PlatformRoute::LA2,
+            Self::Tr => PlatformRoute::TR1,
+            Self::Ru => PlatformRoute::RU,
+            Self::Me => PlatformRoute::ME1,
+            Self::Sea => PlatformRoute::SG2,
+            Self::Tw => PlatformRoute::TW2,
+            Self::Vn => PlatformRoute::VN2,
+        }
+    }
+}
 
 /// Subscribes the guild to the provided summoner
 #[poise::command(slash_command, guild_only)]
@@ -8,16 +67,24 @@ pub async fn follow(
     ctx: poise::Context<'_, Data, CommandError>,
     #[description = "Summoner Name"] name: String,
     #[description = "Summoner Tag"] tag: String,
+    #[description = "Region the summoner plays on (default NA, or their region if already known)"]
+    region: Option<Region>,
 ) -> Result<(), CommandError> {
     let guild_id = ctx.guild_id().context("Trying to get guild ID")?;
     let db_handler = &ctx.data().db_handler;
     let riot_api = &ctx.data().riot_api;
 
     // Always query the API to guarantee we're using the PUUID that matches
-    // with the summoner with that name and tag at this point in time
+    // with the summoner with that name and tag at this point in time. This single
+    // lookup is made directly rather than through the collector's scheduler, which
+    // runs in another process, as someone is waiting on the reply
     let Some(account) = riot_api
         .account_v1()
-        .get_by_riot_id(RegionalRoute::AMERICAS, &name, &tag)
+        .get_by_riot_id(
+            account_route(region.unwrap_or(Region::Na).platform()),
+            &name,
+            &tag,
+        )
         .await?
     else {
         let message = format!("No summoner exists with name **{name}#{tag}**.");
@@ -27,22 +94,42 @@ pub async fn follow(
     let name = account.game_name.as_ref().unwrap_or(&name);
     let tag = account.tag_line.as_ref().unwrap_or(&tag);
 
+    // Summoners that are already known keep their region unless another is given, and
+    // are moved to it otherwise, which applies to every guild following them
+    let puuid = Puuid::from(&account);
+    let known_platform = db_handler
+        .get_summoner(&puuid)
+        .await?
+        .and_then(|summoner| summoner.platform());
+    let platform = match (region, known_platform) {
+        (Some(region), _) => region.platform(),
+        (None, Some(known_platform)) => known_platform,
+        (None, None) => Region::Na.platform(),
+    };
+    db_handler.insert_summoner(&account, platform).await?;
+    let moved = db_handler.update_summoner_region(&puuid, platform).await? > 0;
+    if moved {
+        warn!("Guild {guild_id} moved {puuid:?} from {known_platform:?} to {platform}");
+    }
+
     // Do not proceed if the guild already follows them
     let following = db_handler.get_guild_follows(guild_id.into()).await?;
-    if following
-        .iter()
-        .any(|following| following.puuid == account.puuid)
-    {
-        let message = format!("Already following **{name}#{tag}**.");
+    if following.iter().any(|following| following.puuid == puuid) {
+        let message = if moved {
+            format!("Already following **{name}#{tag}**, who is now on {platform}.")
+        } else {
+            format!("Already following **{name}#{tag}**.")
+        };
         ctx.reply(message).await?;
         return Ok(());
     }
 
-    // Insert information into database
-    db_handler.insert_summoner(&account).await?;
     db_handler
-        .insert_guild_following(guild_id.into(), &account.puuid)
+        .insert_guild_following(guild_id.into(), &puuid)
         .await?;
+    // The collector backfills the summoner's history if configured to, telling this
+    // guild about its progress
+    db_handler.insert_backfill(&puuid, guild_id.into()).await?;
 
     let message = format!("Followed **{name}#{tag}**.");
     ctx.reply(message).await?;
@@ -1,27 +1,137 @@
+use crate::command::queues::Queue;
 use crate::command::{CommandError, Data};
-use crate::ddragon::GameVersion;
+use crate::ddragon::{DataDragonVersion, GameVersion};
 use anyhow::Context;
-use tokio_stream::StreamExt;
+use chrono::{DateTime, NaiveDate, Utc};
+use riven::consts::Champion;
+use std::collections::HashMap;
+use the_collector_db::leaderboard::{
+    LeaderboardMetric, LeaderboardQuery, PositionKdaWeights, TimeWindow,
+};
 
 const DEFAULT_LEADERBOARD_SIZE: usize = 10;
 
+/// Format of dates accepted by the `since` and `until` options.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum Period {
+    #[name = "All time"]
+    AllTime,
+    #[name = "This week"]
+    Week,
+    #[name = "This month"]
+    Month,
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum Role {
+    Top,
+    Jungle,
+    Mid,
+    Bot,
+    Support,
+}
+
+impl Role {
+    /// Position as given by the Riot API.
+    pub fn position(&self) -> &'static str {
+        match self {
+            Self::Top => "TOP",
+            Self::Jungle => "JUNGLE",
+            Self::Mid => "MIDDLE",
+            Self::Bot => "BOTTOM",
+            Self::Support => "UTILITY",
+        }
+    }
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum Metric {
+    Deaths,
+    #[name = "Weighted KDA"]
+    WeightedKda,
+    #[name = "Time spent dead"]
+    TimeDead,
+    #[name = "Deaths per minute"]
+    DeathsPerMinute,
+}
+
+impl Metric {
+    /// Get the leaderboard metric, weighing KDA with the match evaluator's weights.
+    fn leaderboard_metric(&self, kda_weights: &PositionKdaWeights) -> LeaderboardMetric {
+        match self {
+            Self::Deaths => LeaderboardMetric::Deaths,
+            Self::WeightedKda => LeaderboardMetric::WeightedKda(kda_weights.clone()),
+            Self::TimeDead => LeaderboardMetric::TimeDead,
+            Self::DeathsPerMinute => LeaderboardMetric::DeathsPerMinute,
+        }
+    }
+}
+
 /// Displays a leaderboard of the top ints
 #[poise::command(slash_command, guild_only)]
+#[allow(clippy::too_many_arguments)]
 pub async fn leaderboard(
     ctx: poise::Context<'_, Data, CommandError>,
     #[description = "Leaderboard size to view"]
     #[max = 20]
     count: Option<usize>,
+    #[description = "Only include matches from this period"] period: Option<Period>,
+    #[description = "Only include matches from this date (YYYY-MM-DD)"] since: Option<String>,
+    #[description = "Only include matches before this date (YYYY-MM-DD)"] until: Option<String>,
+    #[description = "Only include matches from this queue"] queue: Option<Queue>,
+    #[description = "Only include matches played in this role"] role: Option<Role>,
+    #[description = "Only include matches played as this champion"] champion: Option<String>,
+    #[description = "What to rank matches by"] metric: Option<Metric>,
 ) -> Result<(), CommandError> {
     let guild_id = ctx.guild_id().context("Trying to get guild ID")?;
+
+    let window = match (period, since, until) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => {
+            ctx.reply("Use either a period or a date range, not both.")
+                .await?;
+            return Ok(());
+        }
+        (Some(Period::AllTime) | None, None, None) => TimeWindow::AllTime,
+        (Some(Period::Week), None, None) => TimeWindow::ThisWeek,
+        (Some(Period::Month), None, None) => TimeWindow::ThisMonth,
+        (None, since, until) => {
+            let (Ok(start), Ok(end)) = (
+                parse_date(since.as_deref(), DateTime::UNIX_EPOCH),
+                parse_date(until.as_deref(), Utc::now()),
+            ) else {
+                ctx.reply("Dates should be given as YYYY-MM-DD.").await?;
+                return Ok(());
+            };
+            TimeWindow::Range { start, end }
+        }
+    };
+
+    let mut query = LeaderboardQuery::new(guild_id)
+        .size(count.unwrap_or(DEFAULT_LEADERBOARD_SIZE))
+        .window(window);
+    if let Some(queue) = queue {
+        query = query.queues([queue.queue_id()]);
+    }
+    if let Some(role) = role {
+        query = query.position(role.position());
+    }
+    if let Some(champion) = champion {
+        let Ok(champion) = champion.parse::<Champion>() else {
+            ctx.reply(format!("No champion named {champion} found."))
+                .await?;
+            return Ok(());
+        };
+        query = query.champion(champion.into());
+    }
+    if let Some(metric) = metric {
+        query = query.metric(metric.leaderboard_metric(&ctx.data().kda_weights));
+    }
     ctx.defer().await?;
 
     // Get leaderboard data from the database
-    let leaderboard_data = ctx
-        .data()
-        .db_handler
-        .get_leaderboard(guild_id.into(), count.unwrap_or(DEFAULT_LEADERBOARD_SIZE))
-        .await?;
+    let leaderboard_data = ctx.data().db_handler.get_leaderboard(&query).await?;
 
     if leaderboard_data.is_empty() {
         ctx.reply("No leaderboard matches yet.").await?;
@@ -30,50 +140,52 @@ pub async fn leaderboard(
 
     let leaderboard_data_len = leaderboard_data.len();
     // Format the leaderboard message
-    // TODO: Examine better ways to handle this
     let mut message = String::from("**INT LEADERBOARD**\n**-------------------------**\n");
-    let lines = tokio_stream::iter(leaderboard_data).then(|summoner_match| async move {
-        // TODO: Get summoner and match data in same query
-        let summoner = ctx
-            .data()
-            .db_handler
-            .get_summoner(&summoner_match.puuid)
-            .await?
-            .context("No summoner found with matching PUUID")?;
-        let match_info = ctx
-            .data()
-            .db_handler
-            .get_match(&summoner_match.match_id)
-            .await?
-            .context("No match found with matching ID")?;
-
-        let version = GameVersion(match_info.game_version.clone())
-            .to_data_dragon_version()
-            .await?;
-        let summoner_name = format!("{}#{}", summoner.game_name, summoner.tag);
+    // Matches on the same patch share a Data Dragon version, so only look each up once
+    let mut versions: HashMap<&str, DataDragonVersion> = HashMap::new();
+    for (index, entry) in leaderboard_data.iter().enumerate() {
+        let version = match versions.get(entry.game_version.as_str()) {
+            Some(version) => version,
+            None => {
+                let version = GameVersion(entry.game_version.clone())
+                    .to_data_dragon_version()
+                    .await?;
+                versions.entry(&entry.game_version).or_insert(version)
+            }
+        };
         let champion_name = ctx
             .data()
             .data_dragon
             .lock()
             .await
-            .get_champion_name(&version, summoner_match.champion_id as u16)
+            .get_champion_name(version, entry.champion_id as u16)
             .await?
             .context("No champion with ID found")?;
-        Ok::<String, anyhow::Error>(format!(
-            "{}/{}/{} - {} ({})",
-            summoner_match.kills,
-            summoner_match.deaths,
-            summoner_match.assists,
-            summoner_name,
+
+        // Show the ranked value when it isn't visible from the KDA alone
+        let detail = match &query.metric {
+            LeaderboardMetric::TimeDead => format!(
+                " - {}:{:02} dead",
+                entry.time_dead / 60,
+                entry.time_dead % 60
+            ),
+            LeaderboardMetric::DeathsPerMinute => format!(
+                " - {:.2} deaths/min",
+                entry.deaths as f64 * 60.0 / entry.duration as f64
+            ),
+            _ => String::new(),
+        };
+        message += &format!(
+            "**{})** {}/{}/{} - {}#{} ({}){}\n",
+            index + 1,
+            entry.kills,
+            entry.deaths,
+            entry.assists,
+            entry.game_name,
+            entry.tag,
             champion_name,
-        ))
-    });
-    tokio::pin!(lines);
-
-    let mut index = 1;
-    while let Some(line) = lines.next().await {
-        message += &format!("**{})** {}\n", index, line?);
-        index += 1;
+            detail,
+        );
     }
 
     if let Some(provided_count) = count {
@@ -88,3 +200,17 @@ pub async fn leaderboard(
     ctx.reply(message).await?;
     Ok(())
 }
+
+/// Parse an optional date given as an option to the start of that day (UTC), falling
+/// back to the default if not given.
+pub fn parse_date(
+    date: Option<&str>,
+    default: DateTime<Utc>,
+) -> Result<DateTime<Utc>, chrono::ParseError> {
+    match date {
+        Some(date) => Ok(NaiveDate::parse_from_str(date, DATE_FORMAT)?
+            .and_time(Default::default())
+            .and_utc()),
+        None => Ok(default),
+    }
+}
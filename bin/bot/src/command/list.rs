@@ -1,6 +1,5 @@
 use crate::command::{CommandError, Data};
 use anyhow::Context;
-use tokio_stream::StreamExt;
 
 /// Display a list of the summoners that the guild is subscribed to
 #[poise::command(slash_command, guild_only, ephemeral)]
@@ -8,36 +7,26 @@ pub async fn list(ctx: poise::Context<'_, Data, CommandError>) -> Result<(), Com
     let guild_id = ctx.guild_id().context("Trying to get guild ID")?;
 
     // Get raw data from DB
-    let followed_data = ctx
+    let summoners = ctx
         .data()
         .db_handler
-        .get_guild_follows(guild_id.into())
+        .get_followed_summoners(guild_id.into())
         .await?;
 
-    if followed_data.is_empty() {
+    if summoners.is_empty() {
         ctx.reply("No followed summoners.").await?;
         return Ok(());
     }
 
     // Format the message
-    // TODO: Examine better ways to handle this
     let mut message = String::from("**FOLLOWED SUMMONERS**\n**-------------------------**\n");
-    let lines = tokio_stream::iter(followed_data).then(|guild_following| async move {
-        let summoner = ctx
-            .data()
-            .db_handler
-            .get_summoner(&guild_following.puuid)
-            .await?
-            .context("Failed to get summoner matching PUUID from database")?;
-        let summoner_name = format!("{}#{}", summoner.game_name, summoner.tag);
-        Ok::<String, anyhow::Error>(summoner_name)
-    });
-    tokio::pin!(lines);
-
-    let mut index = 1;
-    while let Some(line) = lines.next().await {
-        message += &format!("**{})** {}\n", index, line?);
-        index += 1;
+    for (index, summoner) in summoners.iter().enumerate() {
+        message += &format!(
+            "**{})** {}#{}\n",
+            index + 1,
+            summoner.game_name,
+            summoner.tag
+        );
     }
 
     // Send the message
@@ -1,6 +1,7 @@
 use crate::ddragon::DataDragon;
 use riven::RiotApi;
 use std::sync::Arc;
+use the_collector_db::leaderboard::PositionKdaWeights;
 use the_collector_db::DbHandler;
 use tokio::sync::Mutex;
 
@@ -9,6 +10,7 @@ mod follow;
 mod here;
 mod leaderboard;
 mod list;
+mod queues;
 mod stats;
 mod unfollow;
 mod unhere;
@@ -18,6 +20,7 @@ pub use follow::follow;
 pub use here::here;
 pub use leaderboard::leaderboard;
 pub use list::list;
+pub use queues::queues;
 pub use stats::stats;
 pub use unfollow::unfollow;
 pub use unhere::unhere;
@@ -28,4 +31,6 @@ pub struct Data {
     pub db_handler: Arc<DbHandler>,
     pub riot_api: RiotApi,
     pub data_dragon: Mutex<DataDragon>,
+    /// Weights of the weighted KDA leaderboard, as configured for the match evaluator.
+    pub kda_weights: PositionKdaWeights,
 }
@@ -0,0 +1,174 @@
+use crate::command::{CommandError, Data};
+use anyhow::Context;
+use poise::ChoiceParameter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Queue {
+    #[name = "Ranked Solo/Duo"]
+    RankedSolo,
+    #[name = "Ranked Flex"]
+    RankedFlex,
+    Draft,
+    #[name = "ARAM"]
+    Aram,
+    Swiftplay,
+    Quickplay,
+    Arena,
+}
+
+impl Queue {
+    const ALL: [Self; 7] = [
+        Self::RankedSolo,
+        Self::RankedFlex,
+        Self::Draft,
+        Self::Aram,
+        Self::Swiftplay,
+        Self::Quickplay,
+        Self::Arena,
+    ];
+
+    pub fn queue_id(&self) -> u16 {
+        match self {
+            Self::RankedSolo => 420,
+            Self::RankedFlex => 440,
+            Self::Draft => 400,
+            Self::Aram => 450,
+            Self::Swiftplay => 480,
+            Self::Quickplay => 490,
+            Self::Arena => 1700,
+        }
+    }
+
+    pub fn from_id(queue_id: u16) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|queue| queue.queue_id() == queue_id)
+    }
+}
+
+/// Manage which queues are counted by the guild
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("show", "add", "remove", "reset"),
+    subcommand_required
+)]
+pub async fn queues(_: poise::Context<'_, Data, CommandError>) -> Result<(), CommandError> {
+    Ok(())
+}
+
+/// List the queues counted by the guild
+#[poise::command(slash_command, guild_only)]
+async fn show(ctx: poise::Context<'_, Data, CommandError>) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().context("Trying to get guild ID")?;
+    let queue_ids = ctx
+        .data()
+        .db_handler
+        .get_guild_queues(guild_id.into())
+        .await?;
+
+    let message = format!("Counting matches from {}.", format_queues(&queue_ids));
+    ctx.reply(message).await?;
+    Ok(())
+}
+
+/// Count matches from a queue
+#[poise::command(slash_command, guild_only)]
+async fn add(
+    ctx: poise::Context<'_, Data, CommandError>,
+    #[description = "Queue to count"] queue: Queue,
+) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().context("Trying to get guild ID")?;
+    let db_handler = &ctx.data().db_handler;
+
+    let mut queue_ids = db_handler.get_guild_queues(guild_id.into()).await?;
+    if queue_ids.contains(&queue.queue_id()) {
+        let message = format!("Already counting **{}**.", queue.name());
+        ctx.reply(message).await?;
+        return Ok(());
+    }
+    queue_ids.push(queue.queue_id());
+    db_handler
+        .set_guild_queues(guild_id.into(), &queue_ids)
+        .await?;
+
+    let message = format!("Now counting **{}**.", queue.name());
+    ctx.reply(message).await?;
+    Ok(())
+}
+
+/// Stop counting matches from a queue
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: poise::Context<'_, Data, CommandError>,
+    #[description = "Queue to stop counting"] queue: Queue,
+) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().context("Trying to get guild ID")?;
+    let db_handler = &ctx.data().db_handler;
+
+    let mut queue_ids = db_handler.get_guild_queues(guild_id.into()).await?;
+    if !queue_ids.contains(&queue.queue_id()) {
+        let message = format!("Not counting **{}**.", queue.name());
+        ctx.reply(message).await?;
+        return Ok(());
+    }
+    // Setting no queues would restore the defaults instead
+    if queue_ids.len() == 1 {
+        ctx.reply("At least one queue must be counted.").await?;
+        return Ok(());
+    }
+    queue_ids.retain(|queue_id| *queue_id != queue.queue_id());
+    db_handler
+        .set_guild_queues(guild_id.into(), &queue_ids)
+        .await?;
+
+    let message = format!("No longer counting **{}**.", queue.name());
+    ctx.reply(message).await?;
+    Ok(())
+}
+
+/// Count matches from the default queues
+#[poise::command(slash_command, guild_only)]
+async fn reset(ctx: poise::Context<'_, Data, CommandError>) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().context("Trying to get guild ID")?;
+    let db_handler = &ctx.data().db_handler;
+
+    db_handler.set_guild_queues(guild_id.into(), &[]).await?;
+    let queue_ids = db_handler.get_guild_queues(guild_id.into()).await?;
+
+    let message = format!("Counting matches from {}.", format_queues(&queue_ids));
+    ctx.reply(message).await?;
+    Ok(())
+}
+
+fn format_queues(queue_ids: &[u16]) -> String {
+    queue_ids
+        .iter()
+        .map(|queue_id| match Queue::from_id(*queue_id) {
+            Some(queue) => format!("**{}**", queue.name()),
+            None => format!("**queue {queue_id}**"),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_id() {
+        for queue in Queue::ALL {
+            assert_eq!(Queue::from_id(queue.queue_id()), Some(queue));
+        }
+        assert_eq!(Queue::from_id(0), None);
+    }
+
+    #[test]
+    fn test_format_queues() {
+        assert_eq!(
+            format_queues(&[420, 450, 9999]),
+            "**Ranked Solo/Duo**, **ARAM**, **queue 9999**"
+        );
+    }
+}
@@ -1,7 +1,15 @@
+use crate::command::leaderboard::{parse_date, Role};
 use crate::command::{CommandError, Data};
+use crate::message::format_lp_delta;
 use anyhow::Context;
+use chrono::DateTime;
 use indoc::formatdoc;
-use the_collector_db::model::SummonerAggregateStats;
+use riven::consts::Champion;
+use the_collector_db::model::{SummonerAggregateStats, SummonerGroupStats};
+use the_collector_db::stats::{StatsGrouping, StatsQuery};
+
+/// Number of champions listed by the breakdown.
+const BREAKDOWN_SIZE: usize = 5;
 
 /// Display statistics of the provided summoner
 #[poise::command(slash_command, guild_only)]
@@ -9,15 +17,48 @@ pub async fn stats(
     ctx: poise::Context<'_, Data, CommandError>,
     #[description = "Summoner Name"] name: String,
     #[description = "Summoner Tag"] tag: String,
+    #[description = "Only include matches played as this champion"] champion: Option<String>,
+    #[description = "Only include matches played in this role"] role: Option<Role>,
+    #[description = "Only include matches from this date (YYYY-MM-DD)"] since: Option<String>,
+    #[description = "List the champions with the most deaths per game"] breakdown: Option<bool>,
 ) -> Result<(), CommandError> {
+    let guild_id = ctx.guild_id().context("Trying to get guild ID")?;
     let db_handler = &ctx.data().db_handler;
 
-    let stats_data = db_handler
-        .get_summoner_stats(&name, &tag)
-        .await?
-        .context("No data for **{name}#{tag}** found.")?;
+    let queue_ids = db_handler.get_guild_queues(guild_id.into()).await?;
+    let mut query = StatsQuery::new(name, tag, queue_ids);
+    if let Some(champion) = champion {
+        let Ok(champion) = champion.parse::<Champion>() else {
+            ctx.reply(format!("No champion named {champion} found."))
+                .await?;
+            return Ok(());
+        };
+        query = query.champion(champion.into());
+    }
+    if let Some(role) = role {
+        query = query.position(role.position());
+    }
+    if let Some(since) = since {
+        let Ok(since) = parse_date(Some(&since), DateTime::UNIX_EPOCH) else {
+            ctx.reply("Dates should be given as YYYY-MM-DD.").await?;
+            return Ok(());
+        };
+        query = query.since(since);
+    }
+
+    let Some(stats_data) = db_handler.get_summoner_stats(&query).await? else {
+        let message = format!("No data for **{}#{}** found.", query.name, query.tag);
+        ctx.reply(message).await?;
+        return Ok(());
+    };
 
-    let message = create_message(&stats_data);
+    let mut message = create_message(&stats_data);
+    if breakdown.unwrap_or(false) {
+        let champions = db_handler
+            .get_summoner_grouped_stats(&query, StatsGrouping::Champion)
+            .await?;
+        message += &create_breakdown(&champions);
+    }
     ctx.reply(message).await?;
     Ok(())
 }
@@ -30,7 +71,8 @@ fn create_message(data: &SummonerAggregateStats) -> String {
         **{game_name}#{tag_line} Stats**
  
         **Total Matches:** {matches}
-        **Total Playtime:** {playtime} minutes
+        **Win Rate:** {win_rate:.2}%
+        {lp_change}**Total Playtime:** {playtime} minutes
         **Total Time Spent Dead:** {total_dead:.2} minutes
 
         **Total Kills:** {kills}
@@ -43,6 +85,11 @@ fn create_message(data: &SummonerAggregateStats) -> String {
         game_name = data.game_name,
         tag_line = data.tag,
         matches = data.num_matches,
+        win_rate = data.wins as f64 / data.num_matches as f64 * 100.0,
+        lp_change = match data.lp_delta {
+            Some(lp_delta) => format!("**LP Change:** {}\n", format_lp_delta(lp_delta)),
+            None => String::new(),
+        },
         playtime = total_minutes,
         total_dead = total_time_dead,
         kills = data.kills,
@@ -53,6 +100,32 @@ fn create_message(data: &SummonerAggregateStats) -> String {
     }
 }
 
+/// List the champions with the most deaths per game, given groups of matches by
+/// champion in that order.
+fn create_breakdown(champions: &[SummonerGroupStats]) -> String {
+    let mut message = String::from("\n**Most Deaths per Game**\n");
+    for (index, group) in champions.iter().take(BREAKDOWN_SIZE).enumerate() {
+        let champion = group
+            .group_key
+            .as_deref()
+            .and_then(|champion_id| champion_id.parse::<i16>().ok())
+            .map(Champion::from);
+        let champion_name = match champion.and_then(|champion| champion.name()) {
+            Some(name) => name.to_string(),
+            None => format!("Champion {}", group.group_key.as_deref().unwrap_or("?")),
+        };
+        message += &format!(
+            "**{})** {} - {:.2} deaths/game over {} matches ({:.0}% won)\n",
+            index + 1,
+            champion_name,
+            group.deaths as f64 / group.num_matches as f64,
+            group.num_matches,
+            group.wins as f64 / group.num_matches as f64 * 100.0,
+        );
+    }
+    message
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,11 +137,13 @@ mod tests {
             game_name: String::from("riot"),
             tag: String::from("games"),
             num_matches: 200,
+            wins: 90,
             kills: 900,
             deaths: 560,
             assists: 1300,
             total_duration: 15000,
             total_time_dead: 5000,
+            lp_delta: None,
         };
 
         let message = create_message(&input_data);
@@ -76,6 +151,7 @@ mod tests {
         assert_eq!(lines.next().unwrap(), "**riot#games Stats**");
         assert_eq!(lines.next().unwrap(), "");
         assert_eq!(lines.next().unwrap(), "**Total Matches:** 200");
+        assert_eq!(lines.next().unwrap(), "**Win Rate:** 45.00%");
         assert_eq!(lines.next().unwrap(), "**Total Playtime:** 250 minutes");
         assert_eq!(
             lines.next().unwrap(),
@@ -93,4 +169,64 @@ mod tests {
         );
         assert!(lines.next().is_none());
     }
+
+    #[test]
+    fn test_create_message_lp_change() {
+        let input_data = SummonerAggregateStats {
+            game_name: String::from("riot"),
+            tag: String::from("games"),
+            num_matches: 2,
+            wins: 0,
+            kills: 3,
+            deaths: 20,
+            assists: 4,
+            total_duration: 3600,
+            total_time_dead: 600,
+            lp_delta: Some(-37),
+        };
+
+        let message = create_message(&input_data);
+        let mut lines = message.lines().skip(3);
+        assert_eq!(lines.next().unwrap(), "**Win Rate:** 0.00%");
+        assert_eq!(lines.next().unwrap(), "**LP Change:** -37 LP");
+        assert_eq!(lines.next().unwrap(), "**Total Playtime:** 60 minutes");
+    }
+
+    #[test]
+    fn test_create_breakdown() {
+        let group = |champion_id: &str, num_matches, wins, deaths| SummonerGroupStats {
+            group_key: Some(champion_id.to_string()),
+            num_matches,
+            wins,
+            kills: 0,
+            deaths,
+            assists: 0,
+            total_duration: 0,
+            total_time_dead: 0,
+            lp_delta: None,
+        };
+        let input_data = [
+            group("222", 4, 1, 40),
+            group("51", 2, 2, 9),
+            group("9999", 1, 0, 1),
+        ];
+
+        let message = create_breakdown(&input_data);
+        let mut lines = message.lines();
+        assert_eq!(lines.next().unwrap(), "");
+        assert_eq!(lines.next().unwrap(), "**Most Deaths per Game**");
+        assert_eq!(
+            lines.next().unwrap(),
+            "**1)** Jinx - 10.00 deaths/game over 4 matches (25% won)"
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            "**2)** Caitlyn - 4.50 deaths/game over 2 matches (100% won)"
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            "**3)** Champion 9999 - 1.00 deaths/game over 1 matches (0% won)"
+        );
+        assert!(lines.next().is_none());
+    }
 }
@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 use the_collector_evaluation::evaluator::MatchEvaluator;
 use tokio::fs::read_to_string;
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
 pub struct Config {
     pub database_url: String,
     pub discord_token: String,
@@ -11,6 +11,22 @@ pub struct Config {
     pub message_templates_path: PathBuf,
     // TODO: Consider making this also a path
     pub match_stats_evaluator: MatchEvaluator,
+    /// How long to wait on shutdown for notifications being sent to finish.
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            database_url: Default::default(),
+            discord_token: Default::default(),
+            rgapi_key: Default::default(),
+            message_templates_path: Default::default(),
+            match_stats_evaluator: Default::default(),
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+        }
+    }
 }
 
 impl Config {
@@ -31,6 +47,10 @@ impl Config {
             .ok()
             .map(PathBuf::from)
             .unwrap_or(config.message_templates_path);
+        config.shutdown_timeout_secs = std::env::var("SHUTDOWN_TIMEOUT_SECS")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .unwrap_or(config.shutdown_timeout_secs);
 
         Ok(config)
     }
@@ -40,3 +60,7 @@ impl Config {
         Ok(toml::from_str(&contents)?)
     }
 }
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
@@ -0,0 +1,83 @@
+use anyhow::Context as _;
+use poise::serenity_prelude::Http;
+use std::sync::Arc;
+use the_collector_db::DbHandler;
+use the_collector_ipc::{sub::IpcSubscriber, BackfillProgress};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+
+/// Tells guilds about the progress of backfilling the history of summoners they followed.
+#[derive(Debug)]
+pub struct BackfillHandler {
+    pub db_handler: Arc<DbHandler>,
+    pub subscriber: IpcSubscriber<BackfillProgress>,
+    pub http: Arc<Http>,
+    pub cancel: CancellationToken,
+}
+
+impl BackfillHandler {
+    /// Handle backfill progress from the collector until shutdown is requested,
+    /// finishing the one being handled.
+    pub async fn start(&self) {
+        loop {
+            let progress = tokio::select! {
+                _ = self.cancel.cancelled() => break,
+                progress = self.subscriber.recv() => progress,
+            };
+            let result = match progress {
+                Ok(progress) => self.run(progress).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                error!("Error handling backfill progress: {e:?}");
+            }
+        }
+        self.subscriber.close();
+        info!("Backfill Handler stopped");
+    }
+
+    async fn run(&self, progress: BackfillProgress) -> anyhow::Result<()> {
+        debug!("Got backfill progress: {progress:?}");
+
+        let Some(channel_id) = self
+            .db_handler
+            .get_guilds()
+            .await?
+            .into_iter()
+            .find(|guild| guild.id == progress.guild_id)
+            .and_then(|guild| guild.channel_id)
+        else {
+            debug!(
+                "Skipping {:?} because no channel ID set yet",
+                progress.guild_id
+            );
+            return Ok(());
+        };
+        let summoner = self
+            .db_handler
+            .get_summoner(&progress.puuid)
+            .await?
+            .context("No summoner with PUUID found in database")?;
+
+        let name = format!("**{}#{}**", summoner.game_name, summoner.tag);
+        let message = if progress.done {
+            format!(
+                "Finished looking through the history of {name}: found {} past matches to collect.",
+                progress.matches
+            )
+        } else {
+            format!(
+                "Found {} past matches of {name} to collect so far...",
+                progress.matches
+            )
+        };
+        let channel = self
+            .http
+            .get_channel(channel_id.into())
+            .await?
+            .guild()
+            .context("Found non-guild channel ID in database")?;
+        channel.say(&self.http, message).await?;
+        Ok(())
+    }
+}
@@ -52,12 +52,9 @@ impl EventHandler for BotHandler {
         _messages: Option<Vec<Message>>,
     ) {
         match self.db_handler.delete_channel(channel.id.into()).await {
-            Ok(result) => {
-                if result.rows_affected() >= 1 {
-                    info!(
-                        "Deleted {} channel IDs from database.",
-                        result.rows_affected()
-                    );
+            Ok(rows_affected) => {
+                if rows_affected >= 1 {
+                    info!("Deleted {rows_affected} channel IDs from database.");
                 }
             }
             Err(e) => error!(
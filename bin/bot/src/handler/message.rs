@@ -1,12 +1,27 @@
 use crate::message::MessageBuilder;
 use anyhow::Context as _;
+use chrono::Utc;
 use poise::serenity_prelude::Http;
 use std::sync::Arc;
+use std::time::Duration;
+use the_collector_db::model::NotificationStatus;
 use the_collector_db::DbHandler;
-use the_collector_evaluation::label::IntLevel;
 use the_collector_evaluation::evaluator::MatchEvaluator;
+use the_collector_evaluation::label::IntLevel;
 use the_collector_ipc::{sub::IpcSubscriber, SummonerMatchQuery};
-use tracing::{debug, error};
+use the_collector_types::{ChannelId, GuildId, MatchId, Puuid};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info};
+
+/// Number of times sending a notification is attempted before giving up on it.
+const MAX_SEND_ATTEMPTS: i64 = 5;
+
+/// How often failed notifications are retried.
+const RETRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// How long a notification can stay pending before it is considered to have been left
+/// by a crash between recording and sending it, and is retried like a failed one.
+const STALE_PENDING_AGE: Duration = Duration::from_secs(10 * 60);
 
 #[derive(Debug)]
 pub struct MessageHandler {
@@ -16,19 +31,31 @@ pub struct MessageHandler {
     pub evaluator: MatchEvaluator,
     pub message_builder: MessageBuilder,
     pub http: Arc<Http>,
+    pub cancel: CancellationToken,
 }
 
 impl MessageHandler {
+    /// Handle summoner matches from the collector until shutdown is requested, finishing
+    /// the one being handled.
     pub async fn start(&self) {
         loop {
-            if let Err(e) = self.run().await {
+            let summoner_match_query = tokio::select! {
+                _ = self.cancel.cancelled() => break,
+                query = self.subscriber.recv() => query,
+            };
+            let result = match summoner_match_query {
+                Ok(summoner_match_query) => self.run(summoner_match_query).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
                 error!("Error handling summoner match: {e:?}");
             }
         }
+        self.subscriber.close();
+        info!("Summoner Match Handler stopped");
     }
 
-    async fn run(&self) -> anyhow::Result<()> {
-        let summoner_match_query = self.subscriber.recv().await?;
+    async fn run(&self, summoner_match_query: SummonerMatchQuery) -> anyhow::Result<()> {
         debug!("Got summoner match query: {summoner_match_query:?}");
 
         let summoner_match = self
@@ -56,32 +83,168 @@ impl MessageHandler {
             .get_summoner(&summoner_match.puuid)
             .await?
             .context("No summoner with PUUID found in database")?;
-        let message =
-            self.message_builder
-                .build_message(&summoner_match, &summoner, evaluation.level);
+        let lp_delta = self
+            .db_handler
+            .get_rank_snapshot(&summoner_match.puuid, &summoner_match.match_id)
+            .await?
+            .and_then(|snapshot| snapshot.lp_delta);
+        let message = self.message_builder.build_message(
+            &summoner_match,
+            &summoner,
+            evaluation.level,
+            lp_delta,
+        );
         let followers = self
             .db_handler
             .get_following_guilds(&summoner_match.puuid)
             .await?;
 
         debug!("Sending a message to {} guilds", followers.len());
+        let level = format!("{:?}", evaluation.level);
         for follower in followers {
             let Some(channel_id) = follower.channel_id else {
                 debug!("Skipping {:?} because no channel ID set yet", follower.id);
                 continue;
             };
 
-            let channel = self
-                .http
-                .get_channel((channel_id as u64).into())
+            let guild_id = follower.id;
+            let queue_ids = self.db_handler.get_guild_queues(guild_id).await?;
+            if !queue_ids.contains(&(match_data.queue_id as u16)) {
+                debug!("Skipping {guild_id:?} because it does not count the match's queue");
+                continue;
+            }
+
+            // Record the notification before sending, so that each guild is only
+            // notified once even if the same summoner match is received again
+            let inserted = self
+                .db_handler
+                .insert_notification(
+                    &summoner_match.puuid,
+                    &summoner_match.match_id,
+                    guild_id,
+                    &level,
+                    &message,
+                )
+                .await?;
+            if inserted == 0 {
+                debug!("Skipping {guild_id:?} because it has already been notified");
+                continue;
+            }
+
+            self.send_notification(
+                &summoner_match.puuid,
+                &summoner_match.match_id,
+                guild_id,
+                channel_id,
+                &message,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Periodically retry sending notifications that previously failed to send, or that
+    /// were left pending, until shutdown is requested.
+    pub async fn start_retrying(&self) {
+        loop {
+            tokio::select! {
+                _ = self.cancel.cancelled() => break,
+                _ = tokio::time::sleep(RETRY_INTERVAL) => {}
+            }
+            match self.retry_failed_notifications().await {
+                Ok(0) => {}
+                Ok(count) => info!("Retried {count} failed or stale notifications"),
+                Err(e) => error!("Error retrying failed notifications: {e:?}"),
+            }
+        }
+    }
+
+    /// Retry sending each failed notification that has not yet used up its attempts,
+    /// to the current channel of its guild. Notifications pending for longer than
+    /// [`STALE_PENDING_AGE`] are retried too, as the bot stopped before sending them.
+    /// Returns the number of notifications retried.
+    pub async fn retry_failed_notifications(&self) -> anyhow::Result<usize> {
+        let mut notifications = self
+            .db_handler
+            .get_notifications(NotificationStatus::Failed)
+            .await?;
+        let stale_before = Utc::now().naive_utc() - STALE_PENDING_AGE;
+        notifications.extend(
+            self.db_handler
+                .get_notifications(NotificationStatus::Pending)
                 .await?
-                .guild()
-                .context("Found non-guild channel ID in database")?;
-            if let Err(e) = channel.say(&self.http, &message).await {
-                error!("Failed sending message: {e:?}");
+                .into_iter()
+                .filter(|notification| notification.update_time < stale_before),
+        );
+        let guilds = self.db_handler.get_guilds().await?;
+
+        let mut count = 0;
+        for notification in notifications {
+            if notification.attempts >= MAX_SEND_ATTEMPTS {
+                continue;
             }
+            let Some(channel_id) = guilds
+                .iter()
+                .find(|guild| guild.id == notification.guild_id)
+                .and_then(|guild| guild.channel_id)
+            else {
+                continue;
+            };
+
+            // A notification that cannot be recorded does not hold up the rest
+            if let Err(e) = self
+                .send_notification(
+                    &notification.puuid,
+                    &notification.match_id,
+                    notification.guild_id,
+                    channel_id,
+                    &notification.message,
+                )
+                .await
+            {
+                error!(
+                    "Error retrying notification of {:?} for {:?}: {e:?}",
+                    notification.match_id, notification.puuid
+                );
+                continue;
+            }
+            count += 1;
         }
+        Ok(count)
+    }
 
+    /// Send a recorded notification to a channel, and record whether it was sent.
+    async fn send_notification(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        message: &str,
+    ) -> anyhow::Result<()> {
+        let (status, message_id) = match self.send_message(channel_id, message).await {
+            Ok(message_id) => (NotificationStatus::Sent, Some(message_id)),
+            Err(e) => {
+                error!("Failed sending message: {e:?}");
+                (NotificationStatus::Failed, None)
+            }
+        };
+        self.db_handler
+            .update_notification(puuid, match_id, guild_id, status, message_id)
+            .await?;
         Ok(())
     }
+
+    /// Send a message to a guild channel, returning the ID of the sent message.
+    async fn send_message(&self, channel_id: ChannelId, message: &str) -> anyhow::Result<u64> {
+        let channel = self
+            .http
+            .get_channel(channel_id.into())
+            .await?
+            .guild()
+            .context("Found non-guild channel ID in database")?;
+        let sent = channel.say(&self.http, message).await?;
+        Ok(sent.id.get())
+    }
 }
@@ -1,2 +1,3 @@
+pub mod backfill;
 pub mod bot;
 pub mod message;
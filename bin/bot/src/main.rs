@@ -2,6 +2,7 @@ use anyhow::Context as _;
 use command::Data;
 use config::Config;
 use ddragon::DataDragon;
+use handler::backfill::BackfillHandler;
 use handler::bot::BotHandler;
 use handler::message::MessageHandler;
 use message::MessageBuilder;
@@ -9,10 +10,14 @@ use poise::serenity_prelude::{Client, GatewayIntents};
 use poise::{Framework, FrameworkOptions};
 use riven::RiotApi;
 use std::sync::Arc;
-use the_collector_db::{DbHandler, SqlitePoolOptions};
-use the_collector_ipc::{sub::IpcSubscriber, IPC_SUMMONER_MATCH_PATH};
+use std::time::Duration;
+use the_collector_db::DbHandler;
+use the_collector_ipc::{sub::IpcSubscriber, IPC_BACKFILL_PATH, IPC_SUMMONER_MATCH_PATH};
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::Mutex;
-use tracing::{error, info};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{error, info, warn};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 mod command;
@@ -30,18 +35,23 @@ async fn main() -> anyhow::Result<()> {
     let config = Config::load(std::env::args().nth(1)).await?;
 
     info!("Setting up DB client");
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(&config.database_url)
+    let db_handler = Arc::new(
+        DbHandler::connect(&config.database_url)
+            .await
+            .context("Failed to connect to database")?,
+    );
+    info!("Running DB migrations");
+    db_handler
+        .migrate()
         .await
-        .context("Failed to connect to database")?;
-    let db_handler = Arc::new(DbHandler::new(pool));
+        .context("Failed to run database migrations")?;
 
     // Setup Riot API
     info!("Setting up Riot API client");
     let riot_api = RiotApi::new(config.rgapi_key);
 
     let db_handler_clone = db_handler.clone();
+    let kda_weights = config.match_stats_evaluator.leaderboard_weights();
     let framework = Framework::builder()
         .options(FrameworkOptions {
             commands: vec![
@@ -50,6 +60,7 @@ async fn main() -> anyhow::Result<()> {
                 command::here(),
                 command::leaderboard(),
                 command::list(),
+                command::queues(),
                 command::stats(),
                 command::unfollow(),
                 command::unhere(),
@@ -63,6 +74,7 @@ async fn main() -> anyhow::Result<()> {
                     db_handler: db_handler_clone,
                     data_dragon: Mutex::new(DataDragon::new()),
                     riot_api,
+                    kda_weights,
                 })
             })
         })
@@ -77,22 +89,77 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("Failed to create client")?;
 
-    let summoner_match_handler = MessageHandler {
+    // Handlers are tracked, so that shutdown can wait for them to finish
+    let cancel = CancellationToken::new();
+    tokio::task::spawn(cancel_on_shutdown_signal(cancel.clone()));
+    let tasks = TaskTracker::new();
+
+    let summoner_match_handler = Arc::new(MessageHandler {
         db_handler: db_handler.clone(),
         subscriber: IpcSubscriber::new(IPC_SUMMONER_MATCH_PATH)?,
         evaluator: config.match_stats_evaluator,
         message_builder: MessageBuilder::new(config.message_templates_path).await?,
         http: client.http.clone(),
-    };
+        cancel: cancel.clone(),
+    });
     info!("Starting Summoner Match Handler");
-    tokio::task::spawn(async move { summoner_match_handler.start().await });
+    tasks.spawn({
+        let summoner_match_handler = summoner_match_handler.clone();
+        async move { summoner_match_handler.start().await }
+    });
+    tasks.spawn(async move { summoner_match_handler.start_retrying().await });
+
+    let backfill_handler = BackfillHandler {
+        db_handler: db_handler.clone(),
+        subscriber: IpcSubscriber::new(IPC_BACKFILL_PATH)?,
+        http: client.http.clone(),
+        cancel: cancel.clone(),
+    };
+    info!("Starting Backfill Handler");
+    tasks.spawn(async move { backfill_handler.start().await });
+
+    // Disconnecting the shards makes the client return
+    tokio::task::spawn({
+        let cancel = cancel.clone();
+        let shard_manager = client.shard_manager.clone();
+        async move {
+            cancel.cancelled().await;
+            shard_manager.shutdown_all().await;
+        }
+    });
 
     info!("Starting client");
     client.start().await.context("Client exited its loop")?;
 
+    info!(
+        "Shutting down, waiting up to {}s for handlers to finish",
+        config.shutdown_timeout_secs
+    );
+    cancel.cancel();
+    tasks.close();
+    let deadline = Duration::from_secs(config.shutdown_timeout_secs);
+    match tokio::time::timeout(deadline, tasks.wait()).await {
+        Ok(()) => info!("Shut down cleanly"),
+        Err(_) => warn!(
+            "Shutdown deadline passed with {} tasks still running",
+            tasks.len()
+        ),
+    }
+
     Ok(())
 }
 
+/// Request shutdown once the process is asked to stop, by SIGTERM (e.g. from systemd)
+/// or Ctrl+C.
+async fn cancel_on_shutdown_signal(cancel: CancellationToken) {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = terminate.recv() => info!("Received SIGTERM"),
+        _ = tokio::signal::ctrl_c() => info!("Received Ctrl+C"),
+    }
+    cancel.cancel();
+}
+
 fn load_env() {
     match dotenvy::dotenv() {
         Ok(path) => info!("Overriding config with values from {path:?}"),
@@ -16,11 +16,15 @@ impl MessageBuilder {
     }
 
     // TODO: Add more here
+    /// Fill in a random template of the level. Templates may use `%s` for the summoner's
+    /// name (`%S` in upper case), `%d` for deaths, `%k` for kills and `%l` for the LP
+    /// gained or lost in a ranked match (e.g. `-18 LP`), which is empty if not known.
     pub fn build_message(
         &self,
         summoner_match: &model::SummonerMatch,
         summoner: &model::Summoner,
         level: &IntLevel,
+        lp_delta: Option<i64>,
     ) -> String {
         let templates = self
             .templates
@@ -36,5 +40,11 @@ impl MessageBuilder {
             .replace("%d", &summoner_match.deaths.to_string())
             .replace("%d", &summoner_match.deaths.to_string())
             .replace("%k", &summoner_match.kills.to_string())
+            .replace("%l", &lp_delta.map(format_lp_delta).unwrap_or_default())
     }
 }
+
+/// Format a change in LP with its sign, e.g. `+21 LP` or `-18 LP`.
+pub fn format_lp_delta(lp_delta: i64) -> String {
+    format!("{lp_delta:+} LP")
+}
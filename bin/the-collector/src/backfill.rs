@@ -0,0 +1,355 @@
+//! Backfilling the match history of summoners when they are first followed.
+//!
+//! Polling only finds matches played after a summoner was added, so the bot requests a
+//! backfill for each newly followed summoner. Their match IDs from before then are paged
+//! through, newest first, and queued as jobs like any other match. Matches from before
+//! a summoner was followed are stored without notifying anyone.
+
+use crate::riot_api::match_data::MatchDataRequester;
+use crate::riot_api::queue::Handling;
+use crate::riot_api::retry::{FailureKind, RetryPolicy};
+use crate::riot_api::scheduler::{Priority, Scheduler};
+use crate::riot_api::Publish;
+use std::sync::Arc;
+use std::time::Duration;
+use the_collector_db::model::{Backfill, BackfillStatus, JobKind};
+use the_collector_db::DbHandler;
+use the_collector_ipc::{r#pub::IpcPublisher, BackfillProgress};
+use the_collector_types::MatchId;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, error, info, warn};
+
+// Max value that Riot API accepts for getting match IDs
+const PAGE_SIZE: u32 = 100;
+
+/// How long to wait for the bot to take a progress report, which is dropped otherwise
+/// so that backfilling is not held up while the bot is down.
+pub const REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How far back the match history of a newly followed summoner is backfilled. Both
+/// limits apply if set, and backfilling is disabled if neither is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BackfillPolicy {
+    /// Maximum number of matches to backfill.
+    pub max_matches: Option<u32>,
+    /// Maximum age of matches to backfill, from when the summoner was followed.
+    pub max_age: Option<Duration>,
+}
+
+impl BackfillPolicy {
+    pub fn is_enabled(&self) -> bool {
+        self.max_matches.is_some() || self.max_age.is_some()
+    }
+
+    /// Number of match IDs to request in the page starting at `start`, or [`None`] once
+    /// the maximum number of matches has been reached.
+    fn page_count(&self, start: u32) -> Option<u32> {
+        let remaining = match self.max_matches {
+            Some(max_matches) => max_matches.saturating_sub(start),
+            None => PAGE_SIZE,
+        };
+        (remaining > 0).then_some(remaining.min(PAGE_SIZE))
+    }
+}
+
+/// Runs the backfills requested by the bot, each in its own task.
+#[derive(Debug)]
+pub struct Backfiller {
+    scheduler: Arc<Scheduler>,
+    db_conn: Arc<DbHandler>,
+    retry_policy: RetryPolicy,
+    policy: BackfillPolicy,
+    match_requester: Arc<MatchDataRequester>,
+    publisher: IpcPublisher<BackfillProgress>,
+    cancel: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl Backfiller {
+    pub fn new(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        policy: BackfillPolicy,
+        match_requester: Arc<MatchDataRequester>,
+        publisher: IpcPublisher<BackfillProgress>,
+        cancel: CancellationToken,
+    ) -> Self {
+        Self {
+            scheduler,
+            db_conn,
+            retry_policy,
+            policy,
+            match_requester,
+            publisher,
+            cancel,
+            tasks: TaskTracker::new(),
+        }
+    }
+
+    /// Start the backfills left in progress by a previous run, which resume from the
+    /// last page they found.
+    pub async fn resume(self: &Arc<Self>) -> anyhow::Result<()> {
+        let backfills = self
+            .db_conn
+            .get_backfills(BackfillStatus::InProgress)
+            .await?;
+        info!("Resuming {} backfills", backfills.len());
+        for backfill in backfills {
+            self.spawn(backfill);
+        }
+        Ok(())
+    }
+
+    /// Start the backfills requested since the last call.
+    pub async fn start_pending(self: &Arc<Self>) -> anyhow::Result<()> {
+        for backfill in self.db_conn.get_backfills(BackfillStatus::Pending).await? {
+            info!("Starting backfill of {:?}", backfill.puuid);
+            self.db_conn
+                .update_backfill(&backfill.puuid, BackfillStatus::InProgress, 0)
+                .await?;
+            self.spawn(backfill);
+        }
+        Ok(())
+    }
+
+    /// Mark the backfills requested since the last call as skipped, for when backfilling
+    /// is disabled, so that they are not left pending.
+    pub async fn skip_pending(db_conn: &DbHandler) -> anyhow::Result<()> {
+        for backfill in db_conn.get_backfills(BackfillStatus::Pending).await? {
+            debug!("Skipping backfill of {:?}", backfill.puuid);
+            db_conn
+                .update_backfill(&backfill.puuid, BackfillStatus::Skipped, 0)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for the running backfills to stop after shutdown has been requested, which
+    /// they do after their current page.
+    pub async fn wait(&self) {
+        self.tasks.close();
+        self.tasks.wait().await;
+    }
+
+    fn spawn(self: &Arc<Self>, backfill: Backfill) {
+        let backfiller = self.clone();
+        self.tasks.spawn(async move {
+            if let Err(e) = backfiller.run(backfill).await {
+                error!("Failed to record backfill: {e:?}");
+            }
+        });
+    }
+
+    /// Run a backfill, recording whether it finished along with the number of matches
+    /// found. Backfills stopped by shutdown are left in progress, to be resumed.
+    async fn run(&self, backfill: Backfill) -> anyhow::Result<()> {
+        let mut matches = u32::try_from(backfill.matches)?;
+        let status = match self.page(&backfill, &mut matches).await {
+            Ok(false) => {
+                info!(
+                    "Pausing backfill of {:?} until the next run",
+                    backfill.puuid
+                );
+                BackfillStatus::InProgress
+            }
+            Ok(true) => {
+                info!("Backfilled {matches} matches of {:?}", backfill.puuid);
+                self.report(&backfill, matches, true).await;
+                BackfillStatus::Done
+            }
+            Err(e) => {
+                error!("Error backfilling {:?}: {e:?}", backfill.puuid);
+                BackfillStatus::Failed
+            }
+        };
+        self.db_conn
+            .update_backfill(&backfill.puuid, status, matches.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Page through the match IDs of a summoner from before they were followed, newest
+    /// first, until the policy's limits or the start of their history are reached.
+    /// `matches` is the number found so far, from which paging starts. Returns whether
+    /// the backfill finished, rather than being stopped by shutdown.
+    async fn page(&self, backfill: &Backfill, matches: &mut u32) -> anyhow::Result<bool> {
+        let Some(summoner) = self.db_conn.get_summoner(&backfill.puuid).await? else {
+            anyhow::bail!("Summoner is no longer in the database");
+        };
+        let Some(platform) = summoner.platform() else {
+            anyhow::bail!("Unknown region {:?}", summoner.region);
+        };
+        let end_time = summoner.create_time.and_utc().timestamp();
+        let start_time = self
+            .policy
+            .max_age
+            .map(|max_age| end_time - max_age.as_secs() as i64);
+
+        while let Some(count) = self.policy.page_count(*matches) {
+            if self.cancel.is_cancelled() {
+                return Ok(false);
+            }
+            let start = *matches;
+            let result = self
+                .retry_policy
+                .run(&mut Handling::unqueued(self.cancel.clone()), || {
+                    let puuid = backfill.puuid.clone();
+                    self.scheduler
+                        .call(Priority::Backfill, move |riot_api| async move {
+                            Ok(riot_api
+                                .match_v5()
+                                .get_match_ids_by_puuid(
+                                    platform.to_regional(),
+                                    puuid.as_str(),
+                                    Some(count as i32),
+                                    Some(end_time),
+                                    None,
+                                    start_time,
+                                    Some(start as i32),
+                                    None,
+                                )
+                                .await?)
+                        })
+                })
+                .await;
+            let match_ids: Vec<MatchId> = match result {
+                Ok(match_ids) => match_ids.into_iter().map(MatchId::from).collect(),
+                // Left to resume from the current page on the next run
+                Err(exhausted) if exhausted.kind == FailureKind::Cancelled => return Ok(false),
+                Err(exhausted) => return Err(exhausted.error),
+            };
+            debug!(
+                "Backfill of {:?} found {} matches",
+                backfill.puuid,
+                match_ids.len()
+            );
+
+            let found = match_ids.len() as u32;
+            *matches += found;
+            self.queue_matches(match_ids).await?;
+            if found < count {
+                break;
+            }
+            self.db_conn
+                .update_backfill(
+                    &backfill.puuid,
+                    BackfillStatus::InProgress,
+                    (*matches).into(),
+                )
+                .await?;
+            self.report(backfill, *matches, false).await;
+        }
+        Ok(true)
+    }
+
+    /// Queue a job for each match not yet in the database, as for polled matches.
+    async fn queue_matches(&self, match_ids: Vec<MatchId>) -> anyhow::Result<()> {
+        let db_matches: Vec<MatchId> = self
+            .db_conn
+            .get_matches(&match_ids)
+            .await?
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        let mut queued = Vec::new();
+        for match_id in match_ids {
+            if db_matches.contains(&match_id) {
+                continue;
+            }
+            if self
+                .db_conn
+                .insert_job(JobKind::MatchData, &match_id)
+                .await?
+                > 0
+            {
+                queued.push(match_id);
+            }
+        }
+        self.match_requester.push(queued, Priority::Backfill).await;
+        Ok(())
+    }
+
+    /// Tell the bot about the progress of a backfill, for the guild that requested it.
+    /// The publisher should have a send timeout, such as [`REPORT_TIMEOUT`].
+    async fn report(&self, backfill: &Backfill, matches: u32, done: bool) {
+        let Some(guild_id) = backfill.guild_id else {
+            return;
+        };
+        let progress = BackfillProgress {
+            puuid: backfill.puuid.clone(),
+            guild_id,
+            matches,
+            done,
+        };
+        if let Err(e) = self.publisher.publish(progress).await {
+            warn!("Failed to report backfill progress: {e:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pages_stop_at_max_matches() {
+        let policy = BackfillPolicy {
+            max_matches: Some(250),
+            max_age: None,
+        };
+        assert!(policy.is_enabled());
+        let pages: Vec<Option<u32>> = [0, 100, 200, 250]
+            .into_iter()
+            .map(|start| policy.page_count(start))
+            .collect();
+        assert_eq!(pages, [Some(100), Some(100), Some(50), None]);
+
+        // Only limited by age, pages continue until the history runs out
+        let policy = BackfillPolicy {
+            max_matches: None,
+            max_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+        };
+        assert_eq!(policy.page_count(1000), Some(100));
+        assert!(!BackfillPolicy::default().is_enabled());
+    }
+
+    #[tokio::test]
+    async fn test_disabled_backfills_are_skipped() {
+        use riven::consts::PlatformRoute;
+        use riven::models::account_v1::Account;
+        use the_collector_db::sqlite::SqliteStorage;
+        use the_collector_db::SqlitePoolOptions;
+        use the_collector_types::{GuildId, Puuid};
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let db_conn = DbHandler::new(SqliteStorage::new(pool));
+        db_conn.migrate().await.unwrap();
+        let account = Account {
+            puuid: "puuid-0".into(),
+            game_name: Some("Player0".into()),
+            tag_line: Some("NA1".into()),
+        };
+        db_conn
+            .insert_summoner(&account, PlatformRoute::NA1)
+            .await
+            .unwrap();
+        db_conn.insert_guild(GuildId::new(1234)).await.unwrap();
+        let puuid = Puuid::from(&account);
+        db_conn
+            .insert_backfill(&puuid, GuildId::new(1234))
+            .await
+            .unwrap();
+
+        Backfiller::skip_pending(&db_conn).await.unwrap();
+        let pending = db_conn.get_backfills(BackfillStatus::Pending).await;
+        assert!(pending.unwrap().is_empty());
+        let skipped = db_conn.get_backfills(BackfillStatus::Skipped).await;
+        assert_eq!(skipped.unwrap()[0].puuid, puuid);
+    }
+}
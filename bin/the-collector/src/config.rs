@@ -1,12 +1,95 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::Path;
+use the_collector_evaluation::role::Role;
+use the_collector_evaluation::weight::Weights;
 use tokio::fs::read_to_string;
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
 pub struct Config {
     pub database_url: String,
     pub rgapi_key: String,
     pub iteration_secs: u64,
+    /// How often to refresh the Riot IDs of known summoners, to pick up renames.
+    #[serde(default = "default_account_refresh_secs")]
+    pub account_refresh_secs: u64,
+    /// Whether to also fetch match timelines, which are used for death events.
+    #[serde(default)]
+    pub collect_timelines: bool,
+    /// Delete matches that started more than this many days ago, unless flagged or on a
+    /// leaderboard. Matches are kept forever if unset.
+    #[serde(default)]
+    pub match_retention_days: Option<u64>,
+    /// Number of entries of each guild's all time, monthly and weekly leaderboards whose
+    /// matches are kept, for each metric.
+    #[serde(default = "default_retained_leaderboard_size")]
+    pub retained_leaderboard_size: usize,
+    /// KDA weights of each role, as configured for the bot's match evaluator, so that the
+    /// matches of weighted KDA leaderboards are kept as well.
+    #[serde(default)]
+    pub kda_weights: HashMap<Role, Weights>,
+    /// Whether to delete summoners that no guild follows.
+    #[serde(default)]
+    pub prune_unfollowed_summoners: bool,
+    /// How often to delete data that is no longer retained.
+    #[serde(default = "default_prune_interval_secs")]
+    pub prune_interval_secs: u64,
+    /// Whether to only log what would be deleted, rather than deleting it.
+    #[serde(default)]
+    pub prune_dry_run: bool,
+    /// Maximum number of Riot API calls let through by the scheduler at once, one of
+    /// which is kept free of backfill calls. At least 2.
+    #[serde(default = "default_max_in_flight_requests")]
+    pub max_in_flight_requests: usize,
+    /// Maximum number of attempts at a Riot API request before it is recorded as a dead
+    /// letter.
+    #[serde(default = "default_max_request_attempts")]
+    pub max_request_attempts: u32,
+    /// Number of items each requester queues and handles at once, and that each channel
+    /// between requesters and handlers holds, before producers wait.
+    #[serde(default = "default_queue_capacity")]
+    pub queue_capacity: usize,
+    /// Longest time between polls of a summoner for new matches. Summoners are polled
+    /// every `iteration_secs` while active, backing off up to this while inactive.
+    #[serde(default = "default_max_poll_interval_secs")]
+    pub max_poll_interval_secs: u64,
+    /// Backfill up to this many matches of each newly followed summoner from before they
+    /// were followed.
+    #[serde(default)]
+    pub backfill_max_matches: Option<u32>,
+    /// Backfill matches of each newly followed summoner from up to this many days before
+    /// they were followed. Nothing is backfilled if neither this nor
+    /// `backfill_max_matches` is set.
+    #[serde(default)]
+    pub backfill_max_days: Option<u64>,
+    /// How long to wait on shutdown for requests in flight to finish and be stored.
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            database_url: Default::default(),
+            rgapi_key: Default::default(),
+            iteration_secs: Default::default(),
+            account_refresh_secs: default_account_refresh_secs(),
+            collect_timelines: Default::default(),
+            match_retention_days: Default::default(),
+            retained_leaderboard_size: default_retained_leaderboard_size(),
+            kda_weights: Default::default(),
+            prune_unfollowed_summoners: Default::default(),
+            prune_interval_secs: default_prune_interval_secs(),
+            prune_dry_run: Default::default(),
+            max_in_flight_requests: default_max_in_flight_requests(),
+            max_request_attempts: default_max_request_attempts(),
+            queue_capacity: default_queue_capacity(),
+            max_poll_interval_secs: default_max_poll_interval_secs(),
+            backfill_max_matches: Default::default(),
+            backfill_max_days: Default::default(),
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+        }
+    }
 }
 
 impl Config {
@@ -24,6 +107,62 @@ impl Config {
             .map(|f| f.parse().expect("Should be numeric"))
             .ok()
             .unwrap_or(config.iteration_secs);
+        config.account_refresh_secs = std::env::var("ACCOUNT_REFRESH_SECS")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .unwrap_or(config.account_refresh_secs);
+        config.collect_timelines = std::env::var("COLLECT_TIMELINES")
+            .map(|f| f.parse().expect("Should be a boolean"))
+            .ok()
+            .unwrap_or(config.collect_timelines);
+        config.match_retention_days = std::env::var("MATCH_RETENTION_DAYS")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .or(config.match_retention_days);
+        config.retained_leaderboard_size = std::env::var("RETAINED_LEADERBOARD_SIZE")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .unwrap_or(config.retained_leaderboard_size);
+        config.prune_unfollowed_summoners = std::env::var("PRUNE_UNFOLLOWED_SUMMONERS")
+            .map(|f| f.parse().expect("Should be a boolean"))
+            .ok()
+            .unwrap_or(config.prune_unfollowed_summoners);
+        config.prune_interval_secs = std::env::var("PRUNE_INTERVAL_SECS")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .unwrap_or(config.prune_interval_secs);
+        config.prune_dry_run = std::env::var("PRUNE_DRY_RUN")
+            .map(|f| f.parse().expect("Should be a boolean"))
+            .ok()
+            .unwrap_or(config.prune_dry_run);
+        config.max_in_flight_requests = std::env::var("MAX_IN_FLIGHT_REQUESTS")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .unwrap_or(config.max_in_flight_requests);
+        config.max_request_attempts = std::env::var("MAX_REQUEST_ATTEMPTS")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .unwrap_or(config.max_request_attempts);
+        config.queue_capacity = std::env::var("QUEUE_CAPACITY")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .unwrap_or(config.queue_capacity);
+        config.max_poll_interval_secs = std::env::var("MAX_POLL_INTERVAL_SECS")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .unwrap_or(config.max_poll_interval_secs);
+        config.backfill_max_matches = std::env::var("BACKFILL_MAX_MATCHES")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .or(config.backfill_max_matches);
+        config.backfill_max_days = std::env::var("BACKFILL_MAX_DAYS")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .or(config.backfill_max_days);
+        config.shutdown_timeout_secs = std::env::var("SHUTDOWN_TIMEOUT_SECS")
+            .map(|f| f.parse().expect("Should be numeric"))
+            .ok()
+            .unwrap_or(config.shutdown_timeout_secs);
 
         Ok(config)
     }
@@ -33,3 +172,39 @@ impl Config {
         Ok(toml::from_str(&contents)?)
     }
 }
+
+fn default_account_refresh_secs() -> u64 {
+    // Daily
+    24 * 60 * 60
+}
+
+fn default_retained_leaderboard_size() -> usize {
+    // The largest leaderboard the bot shows
+    20
+}
+
+fn default_prune_interval_secs() -> u64 {
+    // Daily
+    24 * 60 * 60
+}
+
+fn default_max_in_flight_requests() -> usize {
+    4
+}
+
+fn default_max_request_attempts() -> u32 {
+    5
+}
+
+fn default_queue_capacity() -> usize {
+    64
+}
+
+fn default_max_poll_interval_secs() -> u64 {
+    // Every 6 hours
+    6 * 60 * 60
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
@@ -0,0 +1,78 @@
+//! The `dead-letters` subcommand, for inspecting the Riot API requests that exhausted
+//! their retries, and requeueing them once whatever caused them to fail is resolved.
+//!
+//! Requeued dead letters are picked up by the running collector on its next iteration.
+
+use the_collector_db::model::DeadLetterKind;
+use the_collector_db::DbHandler;
+use tracing::info;
+
+const USAGE: &str = "Usage: the-collector dead-letters [requeue \
+    [account|match_ids|match_data|timeline|league]]";
+
+#[derive(Debug)]
+pub enum DeadLetterCommand {
+    /// Log every dead letter.
+    List,
+    /// Requeue the dead letters of a kind, or of every kind if [`None`].
+    Requeue { kind: Option<DeadLetterKind> },
+}
+
+impl DeadLetterCommand {
+    /// Parse the command line arguments (excluding the program name), returning [`None`]
+    /// if they are not the `dead-letters` subcommand.
+    pub fn parse(args: &[String]) -> anyhow::Result<Option<Self>> {
+        if args.first().map(String::as_str) != Some("dead-letters") {
+            return Ok(None);
+        }
+        let command = match args.get(1).map(String::as_str) {
+            None => Self::List,
+            Some("requeue") => Self::Requeue {
+                kind: args.get(2).map(|kind| parse_kind(kind)).transpose()?,
+            },
+            Some(_) => anyhow::bail!(USAGE),
+        };
+        anyhow::ensure!(args.len() <= 3, USAGE);
+        Ok(Some(command))
+    }
+
+    pub async fn run(&self, db_handler: &DbHandler) -> anyhow::Result<()> {
+        match self {
+            Self::List => {
+                let dead_letters = db_handler.get_dead_letters().await?;
+                for dead_letter in &dead_letters {
+                    info!(
+                        "{:?} {:?}: {} attempts, last at {}{}: {}",
+                        dead_letter.kind,
+                        dead_letter.key,
+                        dead_letter.attempts,
+                        dead_letter.update_time,
+                        if dead_letter.requeued {
+                            " (requeued)"
+                        } else {
+                            ""
+                        },
+                        dead_letter.error
+                    );
+                }
+                info!("Found {} dead letters", dead_letters.len());
+            }
+            Self::Requeue { kind } => {
+                let count = db_handler.requeue_dead_letters(*kind).await?;
+                info!("Requeued {count} dead letters");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_kind(kind: &str) -> anyhow::Result<DeadLetterKind> {
+    Ok(match kind {
+        "account" => DeadLetterKind::Account,
+        "match_ids" => DeadLetterKind::MatchIds,
+        "match_data" => DeadLetterKind::MatchData,
+        "timeline" => DeadLetterKind::Timeline,
+        "league" => DeadLetterKind::League,
+        _ => anyhow::bail!(USAGE),
+    })
+}
@@ -0,0 +1,62 @@
+//! The `export` and `import` subcommands, for moving data in and out of the database as
+//! a portable dump.
+
+use anyhow::Context;
+use the_collector_db::dump::{Dump, DumpFormat};
+use the_collector_db::DbHandler;
+use tracing::info;
+
+const USAGE: &str = "Usage: the-collector <export|import> <directory> [jsonl|csv]";
+
+#[derive(Debug)]
+pub enum DumpCommand {
+    /// Write every table to the directory.
+    Export { dir: String, format: DumpFormat },
+    /// Load the tables written by an export, if they are valid.
+    Import { dir: String, format: DumpFormat },
+}
+
+impl DumpCommand {
+    /// Parse the command line arguments (excluding the program name), returning [`None`]
+    /// if they are not a dump subcommand.
+    pub fn parse(args: &[String]) -> anyhow::Result<Option<Self>> {
+        let Some(subcommand) = args.first() else {
+            return Ok(None);
+        };
+        if subcommand != "export" && subcommand != "import" {
+            return Ok(None);
+        }
+
+        let dir = args.get(1).context(USAGE)?.clone();
+        let format = match args.get(2) {
+            Some(format) => format.parse().context(USAGE)?,
+            None => DumpFormat::JsonLines,
+        };
+        anyhow::ensure!(args.len() <= 3, USAGE);
+
+        Ok(Some(match subcommand.as_str() {
+            "export" => Self::Export { dir, format },
+            _ => Self::Import { dir, format },
+        }))
+    }
+
+    pub async fn run(&self, db_handler: &DbHandler) -> anyhow::Result<()> {
+        match self {
+            Self::Export { dir, format } => {
+                let dump = db_handler.export_dump().await?;
+                dump.write(dir, *format)?;
+                info!(
+                    "Exported {} summoners and {} matches to {dir:?}",
+                    dump.summoners.len(),
+                    dump.matches.len()
+                );
+            }
+            Self::Import { dir, format } => {
+                let dump = Dump::read(dir, *format)?;
+                let count = db_handler.import_dump(&dump).await?;
+                info!("Imported {count} rows from {dir:?}");
+            }
+        }
+        Ok(())
+    }
+}
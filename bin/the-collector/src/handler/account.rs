@@ -1,17 +1,17 @@
-use riven::models::account_v1::Account;
+use crate::riot_api::account::SummonerAccount;
 use std::sync::Arc;
 use the_collector_db::DbHandler;
-use tokio::sync::mpsc::UnboundedReceiver;
-use tracing::{debug, error};
+use tokio::sync::mpsc::Receiver;
+use tracing::{debug, error, info};
 
 #[derive(Debug)]
 pub struct AccountHandler {
     db_conn: Arc<DbHandler>,
-    rx_channel: UnboundedReceiver<Account>,
+    rx_channel: Receiver<SummonerAccount>,
 }
 
 impl AccountHandler {
-    pub fn new(db_conn: Arc<DbHandler>, rx_channel: UnboundedReceiver<Account>) -> Self {
+    pub fn new(db_conn: Arc<DbHandler>, rx_channel: Receiver<SummonerAccount>) -> Self {
         Self {
             db_conn,
             rx_channel,
@@ -19,20 +19,22 @@ impl AccountHandler {
     }
 
     /// Iterate on trying to receive data from [`Self::rx_channel`], and handle
-    /// by inserting the data into the DB.
+    /// by inserting the data into the DB, or updating the name of an existing summoner.
     #[tracing::instrument]
     pub async fn start(mut self) {
-        loop {
-            let data = self
-                .rx_channel
-                .recv()
-                .await
-                .expect("Receiving channel closed unexpectedly");
-            debug!("Received Account data: {data:?}");
+        while let Some(SummonerAccount { account, platform }) = self.rx_channel.recv().await {
+            debug!("Received Account data: {account:?}");
 
-            if let Err(e) = self.db_conn.insert_summoner(&data).await {
-                error!("Failed to insert summoner to database: {e:?}");
+            match self.db_conn.insert_summoner(&account, platform).await {
+                Ok(0) => match self.db_conn.update_summoner_name(&account).await {
+                    Ok(0) => {}
+                    Ok(_) => info!("Updated name of summoner: {:?}", account.puuid),
+                    Err(e) => error!("Failed to update summoner name in database: {e:?}"),
+                },
+                Ok(_) => {}
+                Err(e) => error!("Failed to insert summoner to database: {e:?}"),
             }
         }
+        info!("Account Handler stopped, as its channel closed");
     }
 }
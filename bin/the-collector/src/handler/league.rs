@@ -0,0 +1,103 @@
+use crate::riot_api::league::LeagueEntries;
+use riven::models::league_v4::LeagueEntry;
+use std::sync::Arc;
+use the_collector_db::model::JobStatus;
+use the_collector_db::rank;
+use the_collector_db::DbHandler;
+use the_collector_ipc::{r#pub::IpcPublisher, SummonerMatchQuery};
+use the_collector_types::{MatchId, Puuid};
+use tokio::sync::mpsc::Receiver;
+use tracing::{debug, error, info};
+
+/// Handler recording the rank of summoners after their ranked matches, which then
+/// tells the bot about the summoner match, so that the LP delta is known by the time
+/// a message is built for it.
+#[derive(Debug)]
+pub struct LeagueHandler {
+    db_conn: Arc<DbHandler>,
+    rx_channel: Receiver<LeagueEntries>,
+    publisher: IpcPublisher<SummonerMatchQuery>,
+}
+
+impl LeagueHandler {
+    pub fn new(
+        db_conn: Arc<DbHandler>,
+        rx_channel: Receiver<LeagueEntries>,
+        publisher: IpcPublisher<SummonerMatchQuery>,
+    ) -> Self {
+        Self {
+            db_conn,
+            rx_channel,
+            publisher,
+        }
+    }
+
+    /// Iterate on trying to receive data from [`Self::rx_channel`], and handle by
+    /// inserting a rank snapshot for the match's queue into the DB, then sending the
+    /// summoner match to the bot and marking the league job as done.
+    #[tracing::instrument]
+    pub async fn start(mut self) {
+        while let Some(data) = self.rx_channel.recv().await {
+            match self.run(data).await {
+                Ok(count) => info!("Inserted {count} rank snapshots"),
+                Err(e) => error!("League Handler error: {e:?}"),
+            }
+        }
+        info!("League Handler stopped, as its channel closed");
+    }
+
+    async fn run(&self, data: LeagueEntries) -> anyhow::Result<u64> {
+        let LeagueEntries { query, entries } = data;
+        debug!("Received league entries for {query:?}");
+
+        // The bot is told about the match even if recording the rank fails
+        let count = match self
+            .record_rank(&query.puuid, &query.match_id, &entries)
+            .await
+        {
+            Ok(count) => count,
+            Err(e) => {
+                error!("Failed to record rank for {query:?}: {e:?}");
+                0
+            }
+        };
+
+        let message = SummonerMatchQuery {
+            puuid: query.puuid.clone(),
+            match_id: query.match_id.clone(),
+        };
+        debug!("Sending match query: {message:?}");
+        // The league job is only done once the bot has been told about the match
+        let status = match self.publisher.publish(message).await {
+            Ok(_) => JobStatus::Done,
+            Err(e) => {
+                error!("Failed to send match query for {query:?}: {e:?}");
+                JobStatus::Failed
+            }
+        };
+        self.db_conn
+            .update_league_job(&query.puuid, &query.match_id, status)
+            .await?;
+        Ok(count)
+    }
+
+    /// Record the entry of the queue that the match was played in, if the summoner
+    /// is ranked in it.
+    async fn record_rank(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        entries: &[LeagueEntry],
+    ) -> anyhow::Result<u64> {
+        let Some(match_data) = self.db_conn.get_match(match_id).await? else {
+            return Ok(0);
+        };
+        let Some(queue_type) = rank::queue_type(match_data.queue_id as u16) else {
+            return Ok(0);
+        };
+        let Some(entry) = entries.iter().find(|entry| entry.queue_type == queue_type) else {
+            return Ok(0);
+        };
+        Ok(self.db_conn.record_rank(puuid, match_id, entry).await?)
+    }
+}
@@ -1,82 +1,150 @@
-use riven::models::match_v5::Match;
+use crate::riot_api::league::{LeagueQuery, LeagueRequester};
+use crate::riot_api::match_data::MatchPayload;
+use crate::riot_api::timeline::TimelineRequester;
+use crate::riot_api::Publish;
 use std::sync::Arc;
-use the_collector_db::DbHandler;
+use the_collector_db::model::{JobKind, JobStatus};
+use the_collector_db::{rank, DbHandler};
 use the_collector_ipc::{r#pub::IpcPublisher, SummonerMatchQuery};
-use tokio::sync::mpsc::UnboundedReceiver;
+use the_collector_types::{MatchId, Puuid};
+use tokio::sync::mpsc::Receiver;
 use tracing::{debug, error, info};
 
 #[derive(Debug)]
 pub struct MatchDataHandler {
     db_conn: Arc<DbHandler>,
-    rx_channel: UnboundedReceiver<Match>,
+    rx_channel: Receiver<MatchPayload>,
     publisher: IpcPublisher<SummonerMatchQuery>,
+    /// Only set if timelines are being collected.
+    timeline_requester: Option<Arc<TimelineRequester>>,
+    /// Fetches the rank of summoners after ranked matches, leaving the bot to be told
+    /// about the match once the rank has been recorded.
+    league_requester: Arc<LeagueRequester>,
 }
 
 impl MatchDataHandler {
     pub fn new(
         db_conn: Arc<DbHandler>,
-        rx_channel: UnboundedReceiver<Match>,
+        rx_channel: Receiver<MatchPayload>,
         publisher: IpcPublisher<SummonerMatchQuery>,
+        timeline_requester: Option<Arc<TimelineRequester>>,
+        league_requester: Arc<LeagueRequester>,
     ) -> Self {
         Self {
             db_conn,
             rx_channel,
             publisher,
+            timeline_requester,
+            league_requester,
         }
     }
 
     /// Iterate on trying to receive data from [`Self::rx_channel`], and then
-    // 1. Insert general data into DB
-    // 2. Insert followed data into DB
-    // 3. Send match ID to
+    // 1. Insert general and followed data into DB
+    // 2. Send match ID to
     #[tracing::instrument]
     pub async fn start(mut self) {
-        loop {
-            match self.run().await {
+        while let Some(data) = self.rx_channel.recv().await {
+            match self.run(data).await {
                 Ok(count) => info!("Inserted {count} summoner matches"),
                 Err(e) => error!("Match Data Handler error: {e:?}"),
             }
         }
+        info!("Match Data Handler stopped, as its channel closed");
     }
 
-    async fn run(&mut self) -> anyhow::Result<u8> {
-        // Receive data
-        let data = self
-            .rx_channel
-            .recv()
-            .await
-            .expect("Receiving channel closed unexpectedly");
-        debug!("Received Match data: {:?}", data.metadata.match_id);
+    async fn run(&self, payload: MatchPayload) -> anyhow::Result<u8> {
+        let data = &payload.data;
+        let match_id = MatchId::from(data);
+        debug!("Received Match data: {match_id:?}");
 
-        // Insert general match data into DB
-        // TODO: Batch inserts and/or DB jobs queue
-        if let Err(e) = self.db_conn.insert_match(&data).await {
-            // TODO: Retry
-            anyhow::bail!("Failed to insert match into database: {e:?}");
+        // Archive the full payload first, so that the match can be reprocessed even
+        // if ingesting it fails
+        if let Err(e) = self.db_conn.archive_match(&match_id, &payload.raw).await {
+            error!("Failed to archive match: {e:?}");
         }
 
-        // Insert followed info into DB
+        // Find which participants are tracked, so that only their stats are stored
+        let mut tracked_summoners = Vec::new();
+        for puuid in data.metadata.participants.iter().cloned().map(Puuid::from) {
+            if let Some(summoner) = self.db_conn.get_summoner(&puuid).await? {
+                tracked_summoners.push(summoner);
+            }
+        }
+        let tracked_puuids: Vec<Puuid> = tracked_summoners
+            .iter()
+            .map(|summoner| summoner.puuid.clone())
+            .collect();
+
+        // Insert match and followed data into DB
+        if let Err(e) = self.db_conn.ingest_match(data, &tracked_puuids).await {
+            self.db_conn
+                .update_job(JobKind::MatchData, &match_id, JobStatus::Failed)
+                .await?;
+            anyhow::bail!("Failed to ingest match into database: {e:?}");
+        }
+        self.db_conn
+            .update_job(JobKind::MatchData, &match_id, JobStatus::Done)
+            .await?;
+
+        // Timelines are only of interest for matches with tracked participants
+        if let Some(timeline_requester) = &self.timeline_requester {
+            if !tracked_puuids.is_empty()
+                && self
+                    .db_conn
+                    .insert_job(JobKind::Timeline, &match_id)
+                    .await?
+                    > 0
+            {
+                timeline_requester
+                    .push(vec![match_id.clone()], payload.priority)
+                    .await;
+            }
+        }
+
+        let is_ranked = rank::queue_type(u16::from(data.info.queue_id)).is_some();
         let mut count = 0;
-        for puuid in &data.metadata.participants {
-            if self.db_conn.get_summoner(puuid).await?.is_none() {
+        for summoner in tracked_summoners {
+            // Matches from before a summoner was followed, such as those backfilled,
+            // are only stored
+            count += 1;
+            if data.info.game_start_timestamp < summoner.create_time.and_utc().timestamp_millis() {
+                debug!(
+                    "Not notifying of match before {:?} was followed",
+                    summoner.puuid
+                );
                 continue;
             }
 
-            if let Err(e) = self.db_conn.insert_summoner_match(puuid, &data).await {
-                error!("Failed to insert summoner match data into database: {e:?}");
+            let puuid = summoner.puuid;
+            // Ranks can only be fetched as they are now, so are only recorded after a
+            // summoner's latest match. The league job keeps the notification from being
+            // lost if the collector restarts before then
+            if is_ranked && self.is_latest_match(&puuid, &match_id).await? {
+                if self.db_conn.insert_league_job(&puuid, &match_id).await? > 0 {
+                    let query = LeagueQuery {
+                        puuid,
+                        match_id: match_id.clone(),
+                    };
+                    debug!("Requesting rank: {query:?}");
+                    self.league_requester.push(query, payload.priority).await;
+                }
                 continue;
             }
 
-            // TODO: Avoid cloning?
             let message = SummonerMatchQuery {
-                puuid: puuid.clone(),
-                match_id: data.metadata.match_id.clone(),
+                puuid,
+                match_id: match_id.clone(),
             };
             debug!("Sending match query: {message:?}");
             self.publisher.publish(message).await?;
-            count += 1;
         }
 
         Ok(count)
     }
+
+    async fn is_latest_match(&self, puuid: &Puuid, match_id: &MatchId) -> anyhow::Result<bool> {
+        let latest_match = self.db_conn.get_summoner_latest_match(puuid).await?;
+        Ok(latest_match.is_some_and(|latest_match| &latest_match.id == match_id))
+    }
 }
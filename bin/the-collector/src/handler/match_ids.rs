@@ -1,34 +1,26 @@
 use crate::riot_api::match_data::MatchDataRequester;
+use crate::riot_api::match_ids::MatchIds;
 use crate::riot_api::Publish;
-use circular_queue::CircularQueue;
 use std::sync::Arc;
+use the_collector_db::model::JobKind;
 use the_collector_db::DbHandler;
-use tokio::sync::mpsc::UnboundedReceiver;
-use tracing::{debug, error};
-
-const CACHE_SIZE: usize = 100;
+use the_collector_types::MatchId;
+use tokio::sync::mpsc::Receiver;
+use tracing::{debug, error, info};
 
 #[derive(Debug)]
 pub struct MatchIdsHandler<P: Publish> {
     db_conn: Arc<DbHandler>,
-    rx_channel: UnboundedReceiver<Vec<String>>,
+    rx_channel: Receiver<MatchIds>,
     output: Arc<P>,
-    // TODO: Consider removing the cache
-    cache: CircularQueue<String>,
 }
 
 impl<P: Publish> MatchIdsHandler<P> {
-    pub fn new(
-        db_conn: Arc<DbHandler>,
-        rx_channel: UnboundedReceiver<Vec<String>>,
-        output: Arc<P>,
-    ) -> Self {
-        let cache = CircularQueue::with_capacity(CACHE_SIZE);
+    pub fn new(db_conn: Arc<DbHandler>, rx_channel: Receiver<MatchIds>, output: Arc<P>) -> Self {
         Self {
             db_conn,
             rx_channel,
             output,
-            cache,
         }
     }
 }
@@ -38,12 +30,11 @@ impl MatchIdsHandler<MatchDataRequester> {
     /// data to [`Self::output`].
     #[tracing::instrument]
     pub async fn start(mut self) {
-        loop {
-            let mut data = self
-                .rx_channel
-                .recv()
-                .await
-                .expect("Receiving channel closed unexpectedly");
+        while let Some(MatchIds {
+            match_ids: data,
+            priority,
+        }) = self.rx_channel.recv().await
+        {
             debug!("Received Matches data: {data:?}");
 
             let Ok(matches) = self.db_conn.get_matches(&data).await else {
@@ -55,17 +46,23 @@ impl MatchIdsHandler<MatchDataRequester> {
                 matches.len(),
                 data.len()
             );
-            let db_matches: Vec<String> = matches.into_iter().map(|m| m.id).collect();
-
-            // Remove games that are already in the cache, or are in the database
-            data.retain(|match_id| !self.cache.iter().any(|cache_id| cache_id == match_id));
-            data.retain(|match_id| !db_matches.contains(match_id));
+            let db_matches: Vec<MatchId> = matches.into_iter().map(|m| m.id).collect();
 
-            // Add match IDs to cache and push out
-            for match_id in &data {
-                self.cache.push(match_id.clone());
+            // Queue a job for each match not in the database, skipping those that
+            // already have one pending or in flight
+            let mut queued = Vec::new();
+            for match_id in data {
+                if db_matches.contains(&match_id) {
+                    continue;
+                }
+                match self.db_conn.insert_job(JobKind::MatchData, &match_id).await {
+                    Ok(0) => {}
+                    Ok(_) => queued.push(match_id),
+                    Err(e) => error!("Error queueing job for {match_id:?}: {e:?}"),
+                }
             }
-            self.output.push(data).await;
+            self.output.push(queued, priority).await;
         }
+        info!("Match IDs Handler stopped, as its channel closed");
     }
 }
@@ -1,3 +1,5 @@
 pub mod account;
+pub mod league;
 pub mod match_data;
 pub mod match_ids;
+pub mod timeline;
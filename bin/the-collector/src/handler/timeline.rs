@@ -0,0 +1,57 @@
+use riven::models::match_v5::Timeline;
+use std::sync::Arc;
+use the_collector_db::model::{JobKind, JobStatus};
+use the_collector_db::DbHandler;
+use the_collector_types::{MatchId, Puuid};
+use tokio::sync::mpsc::Receiver;
+use tracing::{debug, error, info};
+
+#[derive(Debug)]
+pub struct TimelineHandler {
+    db_conn: Arc<DbHandler>,
+    rx_channel: Receiver<Timeline>,
+}
+
+impl TimelineHandler {
+    pub fn new(db_conn: Arc<DbHandler>, rx_channel: Receiver<Timeline>) -> Self {
+        Self {
+            db_conn,
+            rx_channel,
+        }
+    }
+
+    /// Iterate on trying to receive data from [`Self::rx_channel`], and handle
+    /// by inserting the death events of tracked participants into the DB.
+    #[tracing::instrument]
+    pub async fn start(mut self) {
+        while let Some(data) = self.rx_channel.recv().await {
+            match self.run(data).await {
+                Ok(count) => info!("Inserted {count} death events"),
+                Err(e) => error!("Timeline Handler error: {e:?}"),
+            }
+        }
+        info!("Timeline Handler stopped, as its channel closed");
+    }
+
+    async fn run(&self, data: Timeline) -> anyhow::Result<u64> {
+        let match_id = MatchId::from(data.metadata.match_id.as_str());
+        debug!("Received Timeline data: {match_id:?}");
+
+        let mut tracked_puuids = Vec::new();
+        for puuid in data.metadata.participants.iter().cloned().map(Puuid::from) {
+            if self.db_conn.get_summoner(&puuid).await?.is_some() {
+                tracked_puuids.push(puuid);
+            }
+        }
+
+        let result = self.db_conn.ingest_timeline(&data, &tracked_puuids).await;
+        let status = match result {
+            Ok(_) => JobStatus::Done,
+            Err(_) => JobStatus::Failed,
+        };
+        self.db_conn
+            .update_job(JobKind::Timeline, &match_id, status)
+            .await?;
+        Ok(result?)
+    }
+}
@@ -0,0 +1,490 @@
+//! The `import-legacy` subcommand, for importing the history of the original int-bot.
+//!
+//! Legacy match and stat records are mapped directly onto match and summoner match rows.
+//! Matches are only fetched again through the requesters to fill in columns the legacy
+//! database lacks, and accounts are fetched to resolve the summoners, as when collecting.
+
+use crate::riot_api::account::{AccountQuery, AccountRequester, SummonerAccount, UsernameAndTag};
+use crate::riot_api::match_data::{MatchDataRequester, MatchPayload};
+use crate::riot_api::retry::RetryPolicy;
+use crate::riot_api::scheduler::{Priority, Scheduler};
+use crate::riot_api::Publish;
+use anyhow::Context;
+use riven::consts::PlatformRoute;
+use riven::models::match_v5::Match;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use the_collector_db::dump::Dump;
+use the_collector_db::legacy::{
+    self, LegacyFollowing, LegacyMatch, LegacyQueries, LegacySummoner, LegacySummonerMatch,
+};
+use the_collector_db::DbHandler;
+use the_collector_types::{MatchId, Puuid};
+use tokio::fs::read_to_string;
+use tokio::sync::mpsc::{channel, Receiver};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+const USAGE: &str = "Usage: the-collector import-legacy <config.toml>";
+
+/// How long a single attempt at a request may take, including waiting for the scheduler
+/// and riven's own retries.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+/// Capacity of the requester queues and channels used by the import.
+const QUEUE_CAPACITY: usize = 64;
+
+/// Where to read the legacy database from and how to map it.
+#[derive(Debug, Deserialize)]
+struct LegacyConfig {
+    /// URL of the legacy SQLite database.
+    database_url: String,
+    /// Platform of the legacy summoners, which is also prefixed to match IDs stored
+    /// without one (e.g. `NA1`).
+    #[serde(default = "default_platform")]
+    platform: String,
+    /// Tag given to summoners selected with a name but no tag, to make Riot IDs.
+    #[serde(default)]
+    tag: Option<String>,
+    /// Where to write the report of the legacy records that could not be imported.
+    #[serde(default = "default_report_path")]
+    report_path: PathBuf,
+    /// Queries selecting the legacy records, which depend on the legacy schema.
+    queries: LegacyQueries,
+}
+
+fn default_platform() -> String {
+    "NA1".into()
+}
+
+fn default_report_path() -> PathBuf {
+    "legacy-import-report.json".into()
+}
+
+/// Legacy records that could not be mapped onto the current schema, written as the
+/// report at the end of the import.
+#[derive(Debug, Default, Serialize)]
+struct Unmapped {
+    /// Summoners whose account was not found.
+    summoners: Vec<LegacySummoner>,
+    /// Followings of summoners that were not mapped.
+    followings: Vec<LegacyFollowing>,
+    /// Matches with missing columns that could not be fetched to fill them in.
+    matches: Vec<LegacyMatch>,
+    /// Stats of summoners or in matches that were not mapped, or with missing columns
+    /// that could not be filled in.
+    summoner_matches: Vec<LegacySummonerMatch>,
+}
+
+#[derive(Debug)]
+pub struct LegacyImportCommand {
+    config_path: PathBuf,
+}
+
+impl LegacyImportCommand {
+    /// Parse the command line arguments (excluding the program name), returning [`None`]
+    /// if they are not the `import-legacy` subcommand.
+    pub fn parse(args: &[String]) -> anyhow::Result<Option<Self>> {
+        if args.first().map(String::as_str) != Some("import-legacy") {
+            return Ok(None);
+        }
+        anyhow::ensure!(args.len() == 2, USAGE);
+        Ok(Some(Self {
+            config_path: PathBuf::from(&args[1]),
+        }))
+    }
+
+    pub async fn run(
+        &self,
+        db_handler: Arc<DbHandler>,
+        scheduler: Arc<Scheduler>,
+        retry_policy: RetryPolicy,
+    ) -> anyhow::Result<()> {
+        let config = load_config(&self.config_path).await?;
+        let platform: PlatformRoute = config
+            .platform
+            .parse()
+            .with_context(|| format!("Unknown platform {:?}", config.platform))?;
+        let mut data = legacy::read(&config.database_url, &config.queries).await?;
+        info!(
+            "Read {} summoners, {} followings, {} matches and {} summoner matches from the \
+            legacy database",
+            data.summoners.len(),
+            data.followings.len(),
+            data.matches.len(),
+            data.summoner_matches.len()
+        );
+        let mut unmapped = Unmapped::default();
+
+        // Followed summoners and those with stats are included, in case they are missing
+        // from the summoners
+        let mut summoners: Vec<LegacySummoner> = Vec::new();
+        for summoner in data
+            .summoners
+            .iter_mut()
+            .chain(data.followings.iter_mut().map(|row| &mut row.summoner))
+            .chain(
+                data.summoner_matches
+                    .iter_mut()
+                    .map(|row| &mut row.summoner),
+            )
+        {
+            if summoner.game_name.is_some() && summoner.tag.is_none() {
+                summoner.tag.clone_from(&config.tag);
+            }
+            if !summoners.contains(summoner) {
+                summoners.push(summoner.clone());
+            }
+        }
+        let puuids = import_summoners(
+            db_handler.clone(),
+            scheduler.clone(),
+            retry_policy,
+            summoners,
+            platform,
+            &mut unmapped,
+        )
+        .await?;
+
+        let mut following_count = 0;
+        for following in data.followings {
+            let Some(puuid) = puuids.get(&following.summoner) else {
+                unmapped.followings.push(following);
+                continue;
+            };
+            let guild_id = following.guild_id;
+            db_handler.insert_guild(guild_id).await?;
+            let follows = db_handler.get_guild_follows(guild_id).await?;
+            if !follows.iter().any(|follow| &follow.puuid == puuid) {
+                db_handler.insert_guild_following(guild_id, puuid).await?;
+                following_count += 1;
+            }
+        }
+
+        let (match_count, summoner_match_count) = import_matches(
+            db_handler.clone(),
+            scheduler,
+            retry_policy,
+            data.matches,
+            data.summoner_matches,
+            &puuids,
+            &config.platform,
+            &mut unmapped,
+        )
+        .await?;
+
+        info!(
+            "Imported {} summoners, {following_count} followings, {match_count} matches and \
+            {summoner_match_count} summoner matches",
+            puuids.values().collect::<HashSet<_>>().len()
+        );
+        let report = serde_json::to_string_pretty(&unmapped)?;
+        tokio::fs::write(&config.report_path, report)
+            .await
+            .with_context(|| format!("Writing {:?}", config.report_path))?;
+        let unmapped_count = unmapped.summoners.len()
+            + unmapped.followings.len()
+            + unmapped.matches.len()
+            + unmapped.summoner_matches.len();
+        if unmapped_count > 0 {
+            warn!(
+                "Could not map {unmapped_count} legacy records, which are listed in {:?}",
+                config.report_path
+            );
+        }
+        Ok(())
+    }
+}
+
+async fn load_config(path: &Path) -> anyhow::Result<LegacyConfig> {
+    let contents = read_to_string(path)
+        .await
+        .with_context(|| format!("Reading {path:?}"))?;
+    Ok(toml::from_str(&contents)?)
+}
+
+/// Fetch the account of each legacy summoner and insert it as a summoner on the platform,
+/// returning the PUUIDs of the summoners that were found.
+async fn import_summoners(
+    db_handler: Arc<DbHandler>,
+    scheduler: Arc<Scheduler>,
+    retry_policy: RetryPolicy,
+    summoners: Vec<LegacySummoner>,
+    platform: PlatformRoute,
+    unmapped: &mut Unmapped,
+) -> anyhow::Result<HashMap<LegacySummoner, Puuid>> {
+    let (account_tx, mut account_rx) = channel::<SummonerAccount>(QUEUE_CAPACITY);
+    let account_requester = Arc::new(AccountRequester::new(
+        scheduler,
+        db_handler.clone(),
+        retry_policy,
+        QUEUE_CAPACITY,
+        CancellationToken::new(),
+    ));
+    tokio::task::spawn({
+        let account_requester = account_requester.clone();
+        async move {
+            account_requester.start(account_tx).await;
+        }
+    });
+
+    let queries: Vec<AccountQuery> = summoners
+        .iter()
+        .filter_map(|summoner| match summoner {
+            LegacySummoner {
+                puuid: Some(puuid), ..
+            } => Some(AccountQuery::Puuid(puuid.clone(), platform)),
+            LegacySummoner {
+                game_name: Some(game_name),
+                tag: Some(tag),
+                ..
+            } => Some(AccountQuery::RiotId(
+                UsernameAndTag(game_name.clone(), tag.clone()),
+                platform,
+            )),
+            _ => None,
+        })
+        .collect();
+    let requested = queries.len();
+    // Pushed in their own task, as pushing waits until responses are received
+    tokio::task::spawn(async move {
+        for query in queries {
+            account_requester.push(query, Priority::Backfill).await;
+        }
+    });
+    let accounts: Vec<_> = receive(&mut account_rx, requested, response_timeout(retry_policy))
+        .await
+        .into_iter()
+        .map(|data| data.account)
+        .collect();
+
+    let mut puuids = HashMap::new();
+    for summoner in summoners {
+        let account = accounts.iter().find(|account| match &summoner {
+            LegacySummoner {
+                puuid: Some(puuid), ..
+            } => puuid.as_str() == account.puuid,
+            LegacySummoner {
+                game_name: Some(game_name),
+                tag: Some(tag),
+                ..
+            } => {
+                account.game_name.as_ref().map(|name| name.to_lowercase())
+                    == Some(game_name.to_lowercase())
+                    && account.tag_line.as_ref().map(|tag| tag.to_lowercase())
+                        == Some(tag.to_lowercase())
+            }
+            _ => false,
+        });
+        match account {
+            Some(account) => {
+                db_handler.insert_summoner(account, platform).await?;
+                puuids.insert(summoner, Puuid::from(account));
+            }
+            None => unmapped.summoners.push(summoner),
+        }
+    }
+    Ok(puuids)
+}
+
+/// Map the legacy matches and summoner stats onto rows, fetching the matches with missing
+/// columns to fill them in, and insert them. Matches that were already collected are
+/// skipped along with their stats. Unlike when collecting, the bot is not told about
+/// these matches, so that no notifications are sent for them.
+///
+/// Returns the number of matches and summoner matches inserted.
+#[allow(clippy::too_many_arguments)]
+async fn import_matches(
+    db_handler: Arc<DbHandler>,
+    scheduler: Arc<Scheduler>,
+    retry_policy: RetryPolicy,
+    matches: Vec<LegacyMatch>,
+    summoner_matches: Vec<LegacySummonerMatch>,
+    puuids: &HashMap<LegacySummoner, Puuid>,
+    platform: &str,
+    unmapped: &mut Unmapped,
+) -> anyhow::Result<(usize, usize)> {
+    let mut legacy_matches: HashMap<MatchId, LegacyMatch> = HashMap::new();
+    for legacy_match in matches {
+        legacy_matches
+            .entry(qualify_match_id(&legacy_match.match_id, platform))
+            .or_insert(legacy_match);
+    }
+    // Matches only stored along with stats are mapped from fetched data alone
+    for row in &summoner_matches {
+        legacy_matches
+            .entry(qualify_match_id(&row.match_id, platform))
+            .or_insert_with(|| LegacyMatch {
+                match_id: row.match_id.clone(),
+                ..Default::default()
+            });
+    }
+    let match_ids: Vec<MatchId> = legacy_matches.keys().cloned().collect();
+    let existing: HashSet<MatchId> = db_handler
+        .get_matches(&match_ids)
+        .await?
+        .into_iter()
+        .map(|data| data.id)
+        .collect();
+    legacy_matches.retain(|match_id, _| !existing.contains(match_id));
+
+    let mut incomplete: HashSet<MatchId> = legacy_matches
+        .iter()
+        .filter(|(_, legacy_match)| !legacy_match.is_complete())
+        .map(|(match_id, _)| match_id.clone())
+        .collect();
+    for row in summoner_matches.iter().filter(|row| !row.is_complete()) {
+        let match_id = qualify_match_id(&row.match_id, platform);
+        if legacy_matches.contains_key(&match_id) {
+            incomplete.insert(match_id);
+        }
+    }
+    info!(
+        "Importing {} legacy matches ({} already collected), fetching {} to fill in missing \
+        columns",
+        legacy_matches.len(),
+        existing.len(),
+        incomplete.len()
+    );
+    let fetched = fetch_matches(db_handler.clone(), scheduler, retry_policy, incomplete).await?;
+
+    let mut dump = Dump::default();
+    for (match_id, legacy_match) in legacy_matches {
+        match legacy_match.to_row(&match_id, fetched.get(&match_id)) {
+            Some(row) => dump.matches.push(row),
+            None => unmapped.matches.push(legacy_match),
+        }
+    }
+    let imported: HashSet<MatchId> = dump.matches.iter().map(|row| row.id.clone()).collect();
+    for row in summoner_matches {
+        let match_id = qualify_match_id(&row.match_id, platform);
+        if existing.contains(&match_id) {
+            continue;
+        }
+        let summoner_match = puuids
+            .get(&row.summoner)
+            .filter(|_| imported.contains(&match_id))
+            .and_then(|puuid| row.to_row(puuid, &match_id, fetched.get(&match_id)));
+        match summoner_match {
+            Some(summoner_match) => dump.summoner_matches.push(summoner_match),
+            None => unmapped.summoner_matches.push(row),
+        }
+    }
+    db_handler.insert_dump(&dump).await?;
+    Ok((dump.matches.len(), dump.summoner_matches.len()))
+}
+
+/// Fetch and archive each match, returning the data of those that were found.
+async fn fetch_matches(
+    db_handler: Arc<DbHandler>,
+    scheduler: Arc<Scheduler>,
+    retry_policy: RetryPolicy,
+    mut remaining: HashSet<MatchId>,
+) -> anyhow::Result<HashMap<MatchId, Match>> {
+    let mut fetched = HashMap::new();
+    if remaining.is_empty() {
+        return Ok(fetched);
+    }
+
+    let (match_tx, mut match_rx) = channel::<MatchPayload>(QUEUE_CAPACITY);
+    let match_requester = Arc::new(MatchDataRequester::new(
+        scheduler,
+        db_handler.clone(),
+        retry_policy,
+        QUEUE_CAPACITY,
+        CancellationToken::new(),
+    ));
+    tokio::task::spawn({
+        let match_requester = match_requester.clone();
+        async move {
+            match_requester.start(match_tx).await;
+        }
+    });
+    tokio::task::spawn({
+        let match_ids = remaining.iter().cloned().collect();
+        async move {
+            match_requester.push(match_ids, Priority::Backfill).await;
+        }
+    });
+
+    let timeout = response_timeout(retry_policy);
+    while !remaining.is_empty() {
+        let Ok(Some(payload)) = tokio::time::timeout(timeout, match_rx.recv()).await else {
+            break;
+        };
+        let match_id = MatchId::from(&payload.data);
+        remaining.remove(&match_id);
+        db_handler.archive_match(&match_id, &payload.raw).await?;
+        fetched.insert(match_id, payload.data);
+    }
+    Ok(fetched)
+}
+
+/// How long to wait for the next response from a requester before considering the
+/// remaining requests failed, as requesters only log failures. This is long enough for
+/// a request to use up every attempt allowed by the retry policy.
+fn response_timeout(retry_policy: RetryPolicy) -> Duration {
+    retry_policy.max_total_delay() + ATTEMPT_TIMEOUT * retry_policy.max_attempts
+}
+
+/// Receive up to the expected number of responses, stopping early if none arrive
+/// within `timeout`.
+async fn receive<T>(rx: &mut Receiver<T>, expected: usize, timeout: Duration) -> Vec<T> {
+    let mut received = Vec::new();
+    while received.len() < expected {
+        match tokio::time::timeout(timeout, rx.recv()).await {
+            Ok(Some(item)) => received.push(item),
+            _ => break,
+        }
+    }
+    received
+}
+
+/// Prefix the platform to a match ID stored as the bare game ID, as match-v5 expects.
+fn qualify_match_id(match_id: &str, platform: &str) -> MatchId {
+    if match_id.chars().all(|c| c.is_ascii_digit()) {
+        format!("{platform}_{match_id}").into()
+    } else {
+        match_id.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queries_are_required() {
+        let config = toml::from_str::<LegacyConfig>(
+            r#"
+            database_url = "sqlite://int-bot.db"
+            tag = "EUW"
+            "#,
+        );
+        assert!(config.is_err());
+
+        let config: LegacyConfig = toml::from_str(
+            r#"
+            database_url = "sqlite://int-bot.db"
+
+            [queries]
+            summoners = "SELECT puuid FROM summoners"
+            followings = "SELECT guild AS guild_id, puuid FROM follows"
+            matches = "SELECT match_id FROM matches"
+            summoner_matches = "SELECT match_id, puuid, deaths FROM stats"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.queries.matches, "SELECT match_id FROM matches");
+        assert_eq!(config.tag, None);
+    }
+
+    #[test]
+    fn test_response_timeout_outlasts_retries() {
+        let retry_policy = RetryPolicy::default();
+        assert!(response_timeout(retry_policy) > retry_policy.max_total_delay());
+    }
+}
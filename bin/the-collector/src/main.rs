@@ -1,27 +1,52 @@
-use chrono::TimeDelta;
+use backfill::{BackfillPolicy, Backfiller, REPORT_TIMEOUT};
+use chrono::{TimeDelta, Utc};
 use config::Config;
-use handler::{account::AccountHandler, match_data::MatchDataHandler, match_ids::MatchIdsHandler};
+use dead_letter::DeadLetterCommand;
+use dump::DumpCommand;
+use handler::{
+    account::AccountHandler, league::LeagueHandler, match_data::MatchDataHandler,
+    match_ids::MatchIdsHandler, timeline::TimelineHandler,
+};
+use legacy::LegacyImportCommand;
+use polling::{Activity, PollSchedule, PollingPolicy};
 use riot_api::{
-    account::AccountRequester,
-    match_data::MatchDataRequester,
-    match_ids::{GetMatchIdsQuery, MatchIdsRequester},
+    account::{AccountQuery, AccountRequester, SummonerAccount},
+    league::{LeagueEntries, LeagueQuery, LeagueRequester},
+    match_data::{MatchDataRequester, MatchPayload},
+    match_ids::{GetMatchIdsQuery, MatchIds, MatchIdsRequester},
+    retry::{DeadLetterKey, RetryPolicy},
+    scheduler::{Budgets, Priority, Scheduler},
+    spectator::is_in_game,
+    timeline::TimelineRequester,
     Publish,
 };
-use riven::{
-    models::{account_v1::Account, match_v5::Match},
-    RiotApi,
-};
+use riven::{models::match_v5::Timeline, RiotApi};
 use std::sync::Arc;
-use the_collector_db::{DbHandler, SqlitePoolOptions};
-use the_collector_ipc::{r#pub::IpcPublisher, IPC_SUMMONER_MATCH_PATH};
-use tokio::sync::mpsc::unbounded_channel;
-use tracing::{debug, error, info};
+use std::time::{Duration, Instant};
+use the_collector_db::leaderboard::LeaderboardMetric;
+use the_collector_db::model::{DeadLetterKind, JobKind, JobStatus};
+use the_collector_db::retention::RetentionPolicy;
+use the_collector_db::DbHandler;
+use the_collector_evaluation::weight::position_kda_weights;
+use the_collector_ipc::{r#pub::IpcPublisher, IPC_BACKFILL_PATH, IPC_SUMMONER_MATCH_PATH};
+use the_collector_types::MatchId;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::channel;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{fmt, EnvFilter};
 
+mod backfill;
 mod config;
+mod dead_letter;
+mod dump;
 mod handler;
+mod legacy;
+mod polling;
 mod riot_api;
 
 #[tokio::main]
@@ -29,101 +54,554 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     load_env();
     setup_tracing_subscriber();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = DumpCommand::parse(&args)? {
+        let config = Config::load(None::<&str>).await?;
+        let db_handler = DbHandler::connect(&config.database_url).await?;
+        db_handler.migrate().await?;
+        command.run(&db_handler).await?;
+        return Ok(());
+    }
+    if let Some(command) = DeadLetterCommand::parse(&args)? {
+        let config = Config::load(None::<&str>).await?;
+        let db_handler = DbHandler::connect(&config.database_url).await?;
+        db_handler.migrate().await?;
+        command.run(&db_handler).await?;
+        return Ok(());
+    }
+    if let Some(command) = LegacyImportCommand::parse(&args)? {
+        let config = Config::load(None::<&str>).await?;
+        let db_handler = Arc::new(DbHandler::connect(&config.database_url).await?);
+        db_handler.migrate().await?;
+        let riot_api = Arc::new(RiotApi::new(config.rgapi_key));
+        let scheduler = start_scheduler(riot_api, config.max_in_flight_requests);
+        let retry_policy = RetryPolicy {
+            max_attempts: config.max_request_attempts,
+            ..Default::default()
+        };
+        command.run(db_handler, scheduler, retry_policy).await?;
+        return Ok(());
+    }
+
     info!("Loading configuration");
-    let config = Config::load(std::env::args().nth(1)).await?;
+    let config = Config::load(args.first()).await?;
 
     // Setup Riot API
     info!("Setting up Riot API client");
     let riot_api = Arc::new(RiotApi::new(config.rgapi_key));
+    let scheduler = start_scheduler(riot_api, config.max_in_flight_requests);
 
     // Setup DB Client
     info!("Setting up DB client");
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(&config.database_url)
-        .await?;
-    let db_handler = Arc::new(DbHandler::new(pool));
+    let db_handler = Arc::new(DbHandler::connect(&config.database_url).await?);
+    info!("Running DB migrations");
+    db_handler.migrate().await?;
 
     // Setup Riot API channels
     info!("Setting up channels");
-    let (account_tx, account_rx) = unbounded_channel::<Account>();
-    let (match_tx, match_rx) = unbounded_channel::<Match>();
-    let (matches_tx, matches_rx) = unbounded_channel::<Vec<String>>();
+    let (account_tx, account_rx) = channel::<SummonerAccount>(config.queue_capacity);
+    let (match_tx, match_rx) = channel::<MatchPayload>(config.queue_capacity);
+    let (matches_tx, matches_rx) = channel::<MatchIds>(config.queue_capacity);
+    let (timeline_tx, timeline_rx) = channel::<Timeline>(config.queue_capacity);
+    let (league_tx, league_rx) = channel::<LeagueEntries>(config.queue_capacity);
+
+    // Requesters and handlers are tracked, so that shutdown can wait for them to drain
+    let cancel = CancellationToken::new();
+    tokio::task::spawn(cancel_on_shutdown_signal(cancel.clone()));
+    let tasks = TaskTracker::new();
 
     // Start API Queues
     info!("Starting Requester tasks");
-    let account_requester = Arc::new(AccountRequester::new(riot_api.clone()));
-    tokio::task::spawn({
+    let retry_policy = RetryPolicy {
+        max_attempts: config.max_request_attempts,
+        ..Default::default()
+    };
+    let account_requester = Arc::new(AccountRequester::new(
+        scheduler.clone(),
+        db_handler.clone(),
+        retry_policy,
+        config.queue_capacity,
+        cancel.clone(),
+    ));
+    tasks.spawn({
         let account_requester = account_requester.clone();
         async move {
             account_requester.start(account_tx).await;
         }
     });
-    let match_requester = Arc::new(MatchDataRequester::new(riot_api.clone()));
-    tokio::task::spawn({
+    let match_requester = Arc::new(MatchDataRequester::new(
+        scheduler.clone(),
+        db_handler.clone(),
+        retry_policy,
+        config.queue_capacity,
+        cancel.clone(),
+    ));
+    tasks.spawn({
         let match_requester = match_requester.clone();
         async move {
             match_requester.start(match_tx).await;
         }
     });
-    let matches_requester = Arc::new(MatchIdsRequester::new(riot_api.clone()));
-    tokio::task::spawn({
+    let matches_requester = Arc::new(MatchIdsRequester::new(
+        scheduler.clone(),
+        db_handler.clone(),
+        retry_policy,
+        config.queue_capacity,
+        cancel.clone(),
+    ));
+    tasks.spawn({
         let matches_requester = matches_requester.clone();
         async move {
             matches_requester.start(matches_tx).await;
         }
     });
 
+    let league_requester = Arc::new(LeagueRequester::new(
+        scheduler.clone(),
+        db_handler.clone(),
+        retry_policy,
+        config.queue_capacity,
+        cancel.clone(),
+    ));
+    tasks.spawn({
+        let league_requester = league_requester.clone();
+        async move {
+            league_requester.start(league_tx).await;
+        }
+    });
+
+    let timeline_requester = if config.collect_timelines {
+        let timeline_requester = Arc::new(TimelineRequester::new(
+            scheduler.clone(),
+            db_handler.clone(),
+            retry_policy,
+            config.queue_capacity,
+            cancel.clone(),
+        ));
+        tasks.spawn({
+            let timeline_requester = timeline_requester.clone();
+            async move {
+                timeline_requester.start(timeline_tx).await;
+            }
+        });
+        Some(timeline_requester)
+    } else {
+        // Nothing sends timelines, so the handler stops straight away
+        drop(timeline_tx);
+        None
+    };
+
     info!("Starting Handler tasks");
     let account_handler = AccountHandler::new(db_handler.clone(), account_rx);
-    tokio::task::spawn(account_handler.start());
+    tasks.spawn(account_handler.start());
 
     let match_data_handler = MatchDataHandler::new(
         db_handler.clone(),
         match_rx,
         IpcPublisher::new(IPC_SUMMONER_MATCH_PATH)?,
+        timeline_requester.clone(),
+        league_requester.clone(),
     );
-    tokio::task::spawn(match_data_handler.start());
+    tasks.spawn(match_data_handler.start());
 
-    let match_ids_handler = MatchIdsHandler::new(db_handler.clone(), matches_rx, match_requester);
-    tokio::task::spawn(match_ids_handler.start());
+    let league_handler = LeagueHandler::new(
+        db_handler.clone(),
+        league_rx,
+        IpcPublisher::new(IPC_SUMMONER_MATCH_PATH)?,
+    );
+    tasks.spawn(league_handler.start());
+
+    let timeline_handler = TimelineHandler::new(db_handler.clone(), timeline_rx);
+    tasks.spawn(timeline_handler.start());
+
+    let match_ids_handler =
+        MatchIdsHandler::new(db_handler.clone(), matches_rx, match_requester.clone());
+    tasks.spawn(match_ids_handler.start());
+
+    // Resumed once the handlers are running, as pushing waits while queues are full
+    resume_jobs(
+        &db_handler,
+        &match_requester,
+        timeline_requester.as_deref(),
+        &league_requester,
+    )
+    .await?;
+
+    let backfill_policy = BackfillPolicy {
+        max_matches: config.backfill_max_matches,
+        max_age: config
+            .backfill_max_days
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+    };
+    let backfiller = if backfill_policy.is_enabled() {
+        let backfiller = Arc::new(Backfiller::new(
+            scheduler.clone(),
+            db_handler.clone(),
+            retry_policy,
+            backfill_policy,
+            match_requester.clone(),
+            IpcPublisher::new(IPC_BACKFILL_PATH)?.with_send_timeout(REPORT_TIMEOUT)?,
+            cancel.clone(),
+        ));
+        backfiller.resume().await?;
+        Some(backfiller)
+    } else {
+        None
+    };
+
+    let mut retention_policy = RetentionPolicy {
+        match_retention_days: config.match_retention_days,
+        prune_unfollowed_summoners: config.prune_unfollowed_summoners,
+        leaderboard_size: config.retained_leaderboard_size,
+        ..Default::default()
+    };
+    if !config.kda_weights.is_empty() {
+        retention_policy
+            .leaderboard_metrics
+            .push(LeaderboardMetric::WeightedKda(position_kda_weights(
+                &config.kda_weights,
+            )));
+    }
+    if retention_policy.match_retention_days.is_some()
+        || retention_policy.prune_unfollowed_summoners
+    {
+        info!("Starting pruning task");
+        tasks.spawn(prune_periodically(
+            db_handler.clone(),
+            retention_policy,
+            Duration::from_secs(config.prune_interval_secs),
+            config.prune_dry_run,
+            cancel.clone(),
+        ));
+    }
 
     info!("Starting main loop");
+    let account_refresh_interval = Duration::from_secs(config.account_refresh_secs);
+    let mut last_account_refresh: Option<Instant> = None;
+    let mut poll_schedule = PollSchedule::new(PollingPolicy {
+        min_interval: Duration::from_secs(config.iteration_secs),
+        max_interval: Duration::from_secs(config.max_poll_interval_secs),
+    });
     loop {
         debug!("Sleeping {}s...", config.iteration_secs);
-        tokio::time::sleep(std::time::Duration::from_secs(config.iteration_secs)).await;
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(Duration::from_secs(config.iteration_secs)) => {}
+        }
 
         // Start with looping summoners, because that's what we're using to query
         // the API. If we started with guilds or followings, we might end up sending
         // duplicate requests (or have to implement logic to avoid duplicates)
         let summoners = db_handler.get_summoners().await?;
+        let done_jobs = db_handler.delete_jobs(JobStatus::Done).await?;
+        debug!("Deleted {done_jobs} done jobs");
+        requeue_dead_letters(
+            &db_handler,
+            &account_requester,
+            &matches_requester,
+            &match_requester,
+            timeline_requester.as_deref(),
+            &league_requester,
+        )
+        .await?;
+        match &backfiller {
+            Some(backfiller) => backfiller.start_pending().await?,
+            None => Backfiller::skip_pending(&db_handler).await?,
+        }
+        info!(
+            "Riot API queue depths: {:?}",
+            scheduler.queue_depths().await
+        );
+
+        // Periodically re-fetch accounts, so that renamed summoners are picked up
+        if last_account_refresh.is_none_or(|time| time.elapsed() >= account_refresh_interval) {
+            info!("Refreshing {} summoner accounts", summoners.len());
+            for summoner in &summoners {
+                let Some(platform) = summoner.platform() else {
+                    continue;
+                };
+                account_requester
+                    .push(
+                        AccountQuery::Puuid(summoner.puuid.clone(), platform),
+                        Priority::Backfill,
+                    )
+                    .await;
+            }
+            last_account_refresh = Some(Instant::now());
+        }
+
+        // Only summoners due to be polled are checked for a live game, and their match
+        // IDs are only fetched once they are out of it
+        poll_schedule.retain(&summoners.iter().map(|summoner| &summoner.puuid).collect());
+        let now = Instant::now();
+        let mut live_checks = JoinSet::new();
         for summoner in summoners {
+            let Some(platform) = summoner.platform() else {
+                warn!(
+                    "Skipping {:?} with unknown region {:?}",
+                    summoner.puuid, summoner.region
+                );
+                continue;
+            };
+            if !poll_schedule.is_due(&summoner.puuid, now) {
+                continue;
+            }
+            let scheduler = scheduler.clone();
+            live_checks.spawn(async move {
+                let in_game = is_in_game(&scheduler, platform, summoner.puuid.clone())
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!(
+                            "Could not check if {:?} is in a game: {e:?}",
+                            summoner.puuid
+                        );
+                        false
+                    });
+                (summoner, platform, in_game)
+            });
+        }
+
+        while let Some(live_check) = live_checks.join_next().await {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let (summoner, platform, in_game) = live_check?;
+
             // If we have a latest match to use, use that to determine when to query from.
             // Otherwise, use the time of the summoner being added to the database. This
             // avoids the workaround used for a long time in which the last match prior to
             // the summoner being added to the database had to be added and processed first.
-            let start_time = match db_handler
+            // Summoners without a match yet have likely just been followed, so their
+            // first matches are fetched ahead of regular polling.
+            let latest_match = db_handler
                 .get_summoner_latest_match(&summoner.puuid)
-                .await?
-            {
+                .await?;
+            let priority = match latest_match {
+                Some(_) => Priority::Polling,
+                None => Priority::Interactive,
+            };
+            let last_active = match &latest_match {
                 Some(latest_match) => latest_match
                     .start_time
                     .checked_add_signed(TimeDelta::seconds(latest_match.duration))
                     .expect("Time fits"),
                 None => summoner.create_time,
+            };
+            let activity = Activity {
+                in_game,
+                latest_match: latest_match.map(|latest_match| latest_match.id),
+                inactive_for: (Utc::now().naive_utc() - last_active)
+                    .to_std()
+                    .unwrap_or_default(),
+            };
+            if !poll_schedule.record(&summoner.puuid, Instant::now(), activity) {
+                debug!("Waiting for {:?} to finish their game", summoner.puuid);
+                continue;
             }
-            .and_utc()
-            .timestamp();
 
             let query = GetMatchIdsQuery {
                 puuid: summoner.puuid,
-                start_time: Some(start_time),
+                platform,
+                start_time: Some(last_active.and_utc().timestamp()),
                 count: None,
             };
             debug!("GetMatchIdsQuery: {query:?}");
-            matches_requester.push(query).await;
+            matches_requester.push(query, priority).await;
+        }
+    }
+
+    // Requesters stop taking new requests and finish those in flight, after which
+    // handlers drain their channels. Jobs cut off by the deadline are resumed next run.
+    info!(
+        "Shutting down, waiting up to {}s for requests in flight",
+        config.shutdown_timeout_secs
+    );
+    tasks.close();
+    let drained = async {
+        tasks.wait().await;
+        if let Some(backfiller) = &backfiller {
+            backfiller.wait().await;
+        }
+    };
+    match tokio::time::timeout(Duration::from_secs(config.shutdown_timeout_secs), drained).await {
+        Ok(()) => info!("Shut down cleanly"),
+        Err(_) => warn!(
+            "Shutdown deadline passed with {} tasks still running",
+            tasks.len()
+        ),
+    }
+    Ok(())
+}
+
+/// Push the jobs left pending or in flight by a previous run to their requesters, so
+/// that matches found before a restart are still fetched, and guilds are still notified
+/// of ranked matches. Timeline jobs are left as they are if timelines are not being
+/// collected.
+async fn resume_jobs(
+    db_handler: &DbHandler,
+    match_requester: &MatchDataRequester,
+    timeline_requester: Option<&TimelineRequester>,
+    league_requester: &LeagueRequester,
+) -> anyhow::Result<()> {
+    for status in [JobStatus::Pending, JobStatus::InFlight] {
+        let jobs = db_handler.get_jobs(status).await?;
+        info!("Resuming {} {status:?} jobs", jobs.len());
+        let (match_ids, timeline_ids): (Vec<_>, Vec<_>) = jobs
+            .into_iter()
+            .partition(|job| job.kind == JobKind::MatchData);
+        match_requester
+            .push(
+                match_ids.into_iter().map(|job| job.match_id).collect(),
+                Priority::Polling,
+            )
+            .await;
+        if let Some(timeline_requester) = timeline_requester {
+            timeline_requester
+                .push(
+                    timeline_ids.into_iter().map(|job| job.match_id).collect(),
+                    Priority::Polling,
+                )
+                .await;
+        }
+
+        let league_jobs = db_handler.get_league_jobs(status).await?;
+        info!("Resuming {} {status:?} league jobs", league_jobs.len());
+        for job in league_jobs {
+            let query = LeagueQuery {
+                puuid: job.puuid,
+                match_id: job.match_id,
+            };
+            league_requester.push(query, Priority::Polling).await;
+        }
+    }
+    Ok(())
+}
+
+/// Push the requests of dead letters that operators have requeued back to their
+/// requesters. Match data, timeline and league jobs are queued again first, and dead letters
+/// that cannot be read back are dropped.
+async fn requeue_dead_letters(
+    db_handler: &DbHandler,
+    account_requester: &AccountRequester,
+    matches_requester: &MatchIdsRequester,
+    match_requester: &MatchDataRequester,
+    timeline_requester: Option<&TimelineRequester>,
+    league_requester: &LeagueRequester,
+) -> anyhow::Result<()> {
+    let dead_letters = db_handler.take_requeued_dead_letters().await?;
+    if !dead_letters.is_empty() {
+        info!("Requeueing {} dead letters", dead_letters.len());
+    }
+    for dead_letter in dead_letters {
+        let key = dead_letter.key.as_str();
+        let requeued = match dead_letter.kind {
+            DeadLetterKind::Account => match AccountQuery::from_key(key) {
+                Some(query) => {
+                    account_requester.push(query, Priority::Backfill).await;
+                    true
+                }
+                None => false,
+            },
+            DeadLetterKind::MatchIds => match GetMatchIdsQuery::from_key(key) {
+                Some(query) => {
+                    matches_requester.push(query, Priority::Polling).await;
+                    true
+                }
+                None => false,
+            },
+            DeadLetterKind::MatchData => {
+                let match_id = MatchId::from(key);
+                if db_handler.insert_job(JobKind::MatchData, &match_id).await? > 0 {
+                    match_requester
+                        .push(vec![match_id], Priority::Polling)
+                        .await;
+                }
+                true
+            }
+            DeadLetterKind::Timeline => match timeline_requester {
+                Some(timeline_requester) => {
+                    let match_id = MatchId::from(key);
+                    if db_handler.insert_job(JobKind::Timeline, &match_id).await? > 0 {
+                        timeline_requester
+                            .push(vec![match_id], Priority::Polling)
+                            .await;
+                    }
+                    true
+                }
+                None => false,
+            },
+            DeadLetterKind::League => match LeagueQuery::from_key(key) {
+                Some(query) => {
+                    if db_handler
+                        .insert_league_job(&query.puuid, &query.match_id)
+                        .await?
+                        > 0
+                    {
+                        league_requester.push(query, Priority::Polling).await;
+                    }
+                    true
+                }
+                None => false,
+            },
+        };
+        if !requeued {
+            warn!("Could not requeue dead letter: {dead_letter:?}");
+        }
+    }
+    Ok(())
+}
+
+/// Create the scheduler for all Riot API calls, letting up to `max_in_flight` calls run
+/// at once, and start it in its own task.
+fn start_scheduler(riot_api: Arc<RiotApi>, max_in_flight: usize) -> Arc<Scheduler> {
+    let scheduler = Arc::new(Scheduler::new(riot_api, max_in_flight, Budgets::default()));
+    tokio::task::spawn({
+        let scheduler = scheduler.clone();
+        async move {
+            scheduler.start().await;
         }
+    });
+    scheduler
+}
+
+/// Delete data that the retention policy no longer keeps at every interval, starting
+/// immediately. On a dry run, only log what would be deleted.
+async fn prune_periodically(
+    db_handler: Arc<DbHandler>,
+    policy: RetentionPolicy,
+    interval: Duration,
+    dry_run: bool,
+    cancel: CancellationToken,
+) {
+    loop {
+        match db_handler.prune(&policy, dry_run).await {
+            Ok(report) => {
+                let verb = if dry_run { "Would prune" } else { "Pruned" };
+                info!(
+                    "{verb} {} matches and {} summoners",
+                    report.match_ids.len(),
+                    report.puuids.len()
+                );
+                debug!("Prune report: {report:?}");
+            }
+            Err(e) => error!("Error pruning data: {e:?}"),
+        }
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+/// Request shutdown once the process is asked to stop, by SIGTERM (e.g. from systemd)
+/// or Ctrl+C.
+async fn cancel_on_shutdown_signal(cancel: CancellationToken) {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = terminate.recv() => info!("Received SIGTERM"),
+        _ = tokio::signal::ctrl_c() => info!("Received Ctrl+C"),
     }
+    cancel.cancel();
 }
 
 fn load_env() {
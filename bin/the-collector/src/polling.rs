@@ -0,0 +1,215 @@
+//! Deciding when to poll each summoner for new matches, based on how active they are.
+//!
+//! Summoners in a game are not polled until it ends, at which point they are polled
+//! straight away. Otherwise, every poll that finds no new match doubles the time until
+//! the next, up to a maximum, so that inactive accounts are rarely polled.
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use the_collector_types::{MatchId, Puuid};
+
+/// Bounds on the time between polls of a summoner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollingPolicy {
+    /// Time between polls of active summoners, and between live game checks of those
+    /// in a game.
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+}
+
+impl PollingPolicy {
+    /// Time until the next poll of a summoner, after a number of polls in a row that
+    /// found no new match.
+    fn interval(&self, idle_polls: u32) -> Duration {
+        self.min_interval
+            .saturating_mul(2u32.saturating_pow(idle_polls))
+            .min(self.max_interval)
+    }
+
+    /// Number of idle polls that a summoner inactive for a time is treated as having
+    /// had, so that inactive summoners are not all polled often after a restart.
+    fn idle_polls_for(&self, inactive_for: Duration) -> u32 {
+        let mut idle_polls = 0;
+        while idle_polls < u32::BITS && self.interval(idle_polls + 1) <= inactive_for {
+            if self.interval(idle_polls + 1) == self.interval(idle_polls) {
+                break;
+            }
+            idle_polls += 1;
+        }
+        idle_polls
+    }
+}
+
+/// What was found when a summoner became due to be polled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Activity {
+    /// Whether the summoner is in a game right now.
+    pub in_game: bool,
+    /// The summoner's latest collected match, if any.
+    pub latest_match: Option<MatchId>,
+    /// Time since the summoner's latest collected match ended.
+    pub inactive_for: Duration,
+}
+
+#[derive(Debug)]
+struct SummonerPolls {
+    next_poll: Instant,
+    idle_polls: u32,
+    in_game: bool,
+    latest_match: Option<MatchId>,
+}
+
+/// When each summoner is next due to be polled.
+#[derive(Debug)]
+pub struct PollSchedule {
+    policy: PollingPolicy,
+    summoners: HashMap<Puuid, SummonerPolls>,
+}
+
+impl PollSchedule {
+    pub fn new(policy: PollingPolicy) -> Self {
+        Self {
+            policy,
+            summoners: HashMap::new(),
+        }
+    }
+
+    /// Whether a summoner is due to be polled, which summoners not yet seen always are.
+    pub fn is_due(&self, puuid: &Puuid, now: Instant) -> bool {
+        self.summoners
+            .get(puuid)
+            .is_none_or(|polls| polls.next_poll <= now)
+    }
+
+    /// Record the activity of a summoner that was due, scheduling their next poll.
+    /// Returns whether their match IDs should be fetched now, which they should not be
+    /// while they are in a game.
+    pub fn record(&mut self, puuid: &Puuid, now: Instant, activity: Activity) -> bool {
+        let policy = self.policy;
+        let (polls, first_poll) = match self.summoners.entry(puuid.clone()) {
+            Entry::Occupied(entry) => (entry.into_mut(), false),
+            Entry::Vacant(entry) => {
+                let polls = entry.insert(SummonerPolls {
+                    next_poll: now,
+                    idle_polls: policy.idle_polls_for(activity.inactive_for),
+                    in_game: false,
+                    latest_match: activity.latest_match.clone(),
+                });
+                (polls, true)
+            }
+        };
+
+        if activity.in_game {
+            polls.in_game = true;
+            polls.next_poll = now + self.policy.min_interval;
+            return false;
+        }
+
+        if polls.in_game || polls.latest_match != activity.latest_match {
+            polls.idle_polls = 0;
+        } else if !first_poll {
+            polls.idle_polls = polls.idle_polls.saturating_add(1);
+        }
+        polls.in_game = false;
+        polls.latest_match = activity.latest_match;
+        polls.next_poll = now + self.policy.interval(polls.idle_polls);
+        true
+    }
+
+    /// Forget summoners that are no longer collected.
+    pub fn retain(&mut self, puuids: &HashSet<&Puuid>) {
+        self.summoners.retain(|puuid, _| puuids.contains(puuid));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn schedule() -> PollSchedule {
+        PollSchedule::new(PollingPolicy {
+            min_interval: MINUTE,
+            max_interval: 8 * MINUTE,
+        })
+    }
+
+    fn idle(latest_match: &str) -> Activity {
+        Activity {
+            in_game: false,
+            latest_match: Some(MatchId::from(latest_match)),
+            inactive_for: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_idle_summoners_back_off() {
+        let mut schedule = schedule();
+        let puuid = Puuid::from("puuid-0");
+        let start = Instant::now();
+
+        let mut now = start;
+        let mut intervals = Vec::new();
+        for _ in 0..6 {
+            assert!(schedule.is_due(&puuid, now));
+            assert!(schedule.record(&puuid, now, idle("NA1_1")));
+            let next_poll = schedule.summoners[&puuid].next_poll;
+            intervals.push((next_poll - now).as_secs() / 60);
+            assert!(!schedule.is_due(&puuid, next_poll - Duration::from_secs(1)));
+            now = next_poll;
+        }
+        assert_eq!(intervals, [1, 2, 4, 8, 8, 8]);
+
+        // A new match resets the backoff
+        assert!(schedule.record(&puuid, now, idle("NA1_2")));
+        assert_eq!(schedule.summoners[&puuid].next_poll - now, MINUTE);
+    }
+
+    #[test]
+    fn test_summoners_are_polled_after_games() {
+        let mut schedule = schedule();
+        let puuid = Puuid::from("puuid-0");
+        let mut now = Instant::now();
+        for _ in 0..4 {
+            schedule.record(&puuid, now, idle("NA1_1"));
+            now = schedule.summoners[&puuid].next_poll;
+        }
+
+        // Not polled during the game, but checked again soon
+        let in_game = Activity {
+            in_game: true,
+            ..idle("NA1_1")
+        };
+        assert!(!schedule.record(&puuid, now, in_game.clone()));
+        assert!(!schedule.record(&puuid, now + MINUTE, in_game));
+        assert!(schedule.is_due(&puuid, now + 2 * MINUTE));
+
+        // Polled once the game ends, and soon after in case the match is not out yet
+        assert!(schedule.record(&puuid, now + 2 * MINUTE, idle("NA1_1")));
+        assert_eq!(schedule.summoners[&puuid].next_poll, now + 3 * MINUTE);
+    }
+
+    #[test]
+    fn test_inactive_summoners_start_backed_off() {
+        let mut schedule = schedule();
+        let now = Instant::now();
+        let active = Puuid::from("puuid-0");
+        let inactive = Puuid::from("puuid-1");
+        schedule.record(&active, now, idle("NA1_1"));
+        schedule.record(
+            &inactive,
+            now,
+            Activity {
+                inactive_for: Duration::from_secs(180 * 24 * 60 * 60),
+                ..idle("NA1_1")
+            },
+        );
+        assert_eq!(schedule.summoners[&active].next_poll, now + MINUTE);
+        assert_eq!(schedule.summoners[&inactive].next_poll, now + 8 * MINUTE);
+
+        schedule.retain(&HashSet::from([&active]));
+        assert!(schedule.is_due(&inactive, now));
+    }
+}
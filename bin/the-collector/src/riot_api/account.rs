@@ -1,20 +1,77 @@
+use super::queue::{Handling, RequestQueue};
+use super::retry::{
+    platform_key, record_dead_letter, split_platform_key, DeadLetterKey, RetryPolicy,
+};
+use super::scheduler::{Priority, Scheduler};
 use super::Publish;
-use riven::consts::RegionalRoute::AMERICAS;
-use riven::{models::account_v1::Account, RiotApi};
-use std::collections::VecDeque;
+use riven::consts::PlatformRoute;
+use riven::{models::account_v1::Account, RiotApi, RiotApiError};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tracing::debug;
+use the_collector_db::model::DeadLetterKind;
+use the_collector_db::DbHandler;
+use the_collector_types::{account_route, Puuid};
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UsernameAndTag(pub String, pub String);
 
-/// Handler for retrieving [`Account`] information from Riot API, given a
-/// [`UsernameAndTag`]. [`Publish::start`] should be called within
+/// Query for the [`Account`] of a summoner on a platform, either by its Riot ID or by
+/// its PUUID. Querying by PUUID is used to pick up changes to the Riot ID of known
+/// accounts.
+#[derive(Debug, Clone)]
+pub enum AccountQuery {
+    RiotId(UsernameAndTag, PlatformRoute),
+    Puuid(Puuid, PlatformRoute),
+}
+
+impl AccountQuery {
+    pub fn platform(&self) -> PlatformRoute {
+        match self {
+            Self::RiotId(_, platform) | Self::Puuid(_, platform) => *platform,
+        }
+    }
+}
+
+/// Keyed by platform and either PUUID or Riot ID, e.g. `NA1:<PUUID>` or `NA1:Name#Tag`.
+impl DeadLetterKey for AccountQuery {
+    fn to_key(&self) -> String {
+        match self {
+            Self::RiotId(UsernameAndTag(game_name, tag_line), platform) => {
+                platform_key(*platform, &format!("{game_name}#{tag_line}"))
+            }
+            Self::Puuid(puuid, platform) => platform_key(*platform, puuid.as_str()),
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        let (platform, key) = split_platform_key(key)?;
+        Some(match key.rsplit_once('#') {
+            Some((game_name, tag_line)) => {
+                Self::RiotId(UsernameAndTag(game_name.into(), tag_line.into()), platform)
+            }
+            None => Self::Puuid(Puuid::from(key), platform),
+        })
+    }
+}
+
+/// An [`Account`] fetched for an [`AccountQuery`], along with the platform of the
+/// summoner it was queried for.
+#[derive(Debug)]
+pub struct SummonerAccount {
+    pub account: Account,
+    pub platform: PlatformRoute,
+}
+
+/// Handler for retrieving [`Account`] information from Riot API, given an
+/// [`AccountQuery`]. [`Publish::start`] should be called within
 /// its own Tokio task.
 pub struct AccountRequester {
-    riot_api: Arc<RiotApi>,
-    account_queue: Mutex<VecDeque<UsernameAndTag>>,
+    scheduler: Arc<Scheduler>,
+    db_conn: Arc<DbHandler>,
+    retry_policy: RetryPolicy,
+    account_queue: RequestQueue<(AccountQuery, Priority)>,
 }
 
 impl std::fmt::Debug for AccountRequester {
@@ -26,48 +83,119 @@ impl std::fmt::Debug for AccountRequester {
 }
 
 impl AccountRequester {
-    pub fn new(riot_api: Arc<RiotApi>) -> Self {
-        let account_queue = tokio::sync::Mutex::new(VecDeque::new());
+    pub fn new(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        capacity: usize,
+        cancel: CancellationToken,
+    ) -> Self {
+        let account_queue = RequestQueue::new(capacity, cancel);
         Self {
-            riot_api,
+            scheduler,
+            db_conn,
+            retry_policy,
             account_queue,
         }
     }
 
-    /// Retrieve an [`Account`] from a [`UsernameAndTag`]. If no account information
-    /// is found, return [`None`].
-    async fn get_account(&self, account_info: &UsernameAndTag) -> Option<Account> {
-        self.riot_api
+    /// Fetch the [`Account`] of a query through the scheduler, and send it to the
+    /// publishing channel if one is found. The query is recorded as a dead letter if
+    /// it exhausts its retries.
+    async fn fetch(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        query: AccountQuery,
+        priority: Priority,
+        publishing_channel: Sender<SummonerAccount>,
+        mut handling: Handling,
+    ) -> anyhow::Result<()> {
+        let result = retry_policy
+            .run(&mut handling, || {
+                let query = query.clone();
+                scheduler.call(priority, move |riot_api| async move {
+                    Ok(get_account(&riot_api, &query).await?)
+                })
+            })
+            .await;
+        match result {
+            Ok(Some(account)) => {
+                debug!("Fetched account: {account:?}");
+                let platform = query.platform();
+                publishing_channel
+                    .send(SummonerAccount { account, platform })
+                    .await?;
+            }
+            Ok(None) => error!("No account found for {query:?}"),
+            Err(exhausted) => {
+                record_dead_letter(&db_conn, DeadLetterKind::Account, &query, &exhausted).await?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Retrieve an [`Account`] from an [`AccountQuery`]. If no account information
+/// is found, return [`None`].
+async fn get_account(
+    riot_api: &RiotApi,
+    query: &AccountQuery,
+) -> Result<Option<Account>, RiotApiError> {
+    let route = account_route(query.platform());
+    match query {
+        AccountQuery::RiotId(UsernameAndTag(game_name, tag_line), _) => {
+            riot_api
+                .account_v1()
+                .get_by_riot_id(route, game_name, tag_line)
+                .await
+        }
+        AccountQuery::Puuid(puuid, _) => riot_api
             .account_v1()
-            .get_by_riot_id(AMERICAS, &account_info.0, &account_info.1)
+            .get_by_puuid(route, puuid.as_str())
             .await
-            .unwrap()
+            .map(Some),
     }
 }
 
 impl Publish for AccountRequester {
-    type Input = UsernameAndTag;
-    type Output = Account;
+    type Input = AccountQuery;
+    type Output = SummonerAccount;
 
-    /// Push a [`UsernameAndTag`] onto the queue.
-    async fn push(&self, data: Self::Input) {
-        let mut lock = self.account_queue.lock().await;
-        lock.push_back(data);
+    /// Push an [`AccountQuery`] onto the queue.
+    async fn push(&self, data: Self::Input, priority: Priority) {
+        self.account_queue.push((data, priority)).await;
     }
 
-    /// Loop the queue of [`UsernameAndTag`], and fetch [`Account`] data from Riot's API.
+    /// Wait on the queue of [`AccountQuery`], and fetch [`Account`] data from Riot's API
+    /// through the scheduler, which decides the order they are fetched in.
     ///
     /// Fetched data is sent to the provided publishing channel.
     #[tracing::instrument]
-    async fn start(&self, publishing_channel: tokio::sync::mpsc::UnboundedSender<Self::Output>) {
-        loop {
-            let mut lock = self.account_queue.lock().await;
-            if let Some(user_data) = lock.pop_front() {
-                drop(lock);
-                let account = self.get_account(&user_data).await.unwrap();
-                debug!("Fetched account: {account:?}");
-                publishing_channel.send(account).unwrap();
-            }
+    async fn start(&self, publishing_channel: Sender<Self::Output>) {
+        while let Some(((query, priority), handling)) = self.account_queue.next().await {
+            let fetch = Self::fetch(
+                self.scheduler.clone(),
+                self.db_conn.clone(),
+                self.retry_policy,
+                query,
+                priority,
+                publishing_channel.clone(),
+                handling,
+            );
+            tokio::task::spawn(async move {
+                if let Err(e) = fetch.await {
+                    error!("Error retrieving account: {e:?}");
+                }
+            });
+        }
+
+        let pending = self.account_queue.drain().await;
+        if !pending.is_empty() {
+            warn!(
+                "Dropping {} queued account queries: {pending:?}",
+                pending.len()
+            );
         }
     }
 }
@@ -0,0 +1,199 @@
+use super::queue::{Handling, RequestQueue};
+use super::retry::{record_dead_letter, DeadLetterKey, FailureKind, RetryPolicy};
+use super::scheduler::{Priority, Scheduler};
+use super::Publish;
+use anyhow::Context;
+use riven::reqwest::Method;
+use riven::{models::league_v4::LeagueEntry, RiotApi};
+use std::sync::Arc;
+use the_collector_db::model::{DeadLetterKind, JobStatus};
+use the_collector_db::DbHandler;
+use the_collector_types::{MatchId, Puuid};
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
+
+/// Query for the ranked standings of a summoner after one of their ranked matches.
+#[derive(Debug, Clone)]
+pub struct LeagueQuery {
+    pub puuid: Puuid,
+    pub match_id: MatchId,
+}
+
+/// Keyed by match ID and PUUID, e.g. `NA1_5000000001:<PUUID>`.
+impl DeadLetterKey for LeagueQuery {
+    fn to_key(&self) -> String {
+        format!("{}:{}", self.match_id, self.puuid)
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        let (match_id, puuid) = key.split_once(':')?;
+        Some(Self {
+            puuid: Puuid::from(puuid),
+            match_id: MatchId::from(match_id),
+        })
+    }
+}
+
+/// League entries of a summoner in each ranked queue they have played, fetched for a
+/// [`LeagueQuery`].
+#[derive(Debug)]
+pub struct LeagueEntries {
+    pub query: LeagueQuery,
+    /// Empty if the summoner is unranked, or if fetching the entries failed.
+    pub entries: Vec<LeagueEntry>,
+}
+
+/// Requester for fetching the [`LeagueEntry`]s of summoners from the Riot API.
+///
+/// Each query has a league job, which is left pending if shutdown interrupts it.
+/// Otherwise entries are published for every query, even if fetching them fails, as the
+/// match still has to be handled. This should have its [`Publish::start`] method called
+/// within a Tokio task.
+pub struct LeagueRequester {
+    scheduler: Arc<Scheduler>,
+    db_conn: Arc<DbHandler>,
+    retry_policy: RetryPolicy,
+    league_queue: RequestQueue<(LeagueQuery, Priority)>,
+}
+
+impl std::fmt::Debug for LeagueRequester {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LeagueRequester")
+            .field("league_queue", &self.league_queue)
+            .finish()
+    }
+}
+
+impl LeagueRequester {
+    pub fn new(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        capacity: usize,
+        cancel: CancellationToken,
+    ) -> Self {
+        let league_queue = RequestQueue::new(capacity, cancel);
+        Self {
+            scheduler,
+            db_conn,
+            retry_policy,
+            league_queue,
+        }
+    }
+
+    /// Fetch the [`LeagueEntry`]s of a query through the scheduler, and send them to
+    /// the publishing channel. The league job of the query is marked as in flight until
+    /// handled, or left pending if interrupted by shutdown. The query is recorded as a
+    /// dead letter if it exhausts its retries.
+    async fn fetch(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        query: LeagueQuery,
+        priority: Priority,
+        publishing_channel: Sender<LeagueEntries>,
+        mut handling: Handling,
+    ) -> anyhow::Result<()> {
+        db_conn
+            .update_league_job(&query.puuid, &query.match_id, JobStatus::InFlight)
+            .await?;
+        let result = retry_policy
+            .run(&mut handling, || {
+                let query = query.clone();
+                scheduler.call(priority, move |riot_api| async move {
+                    get_league_entries(&riot_api, &query).await
+                })
+            })
+            .await;
+        let entries = match result {
+            Ok(entries) => {
+                debug!("Fetched {} league entries for {query:?}", entries.len());
+                entries
+            }
+            Err(exhausted) if exhausted.kind == FailureKind::Cancelled => {
+                db_conn
+                    .update_league_job(&query.puuid, &query.match_id, JobStatus::Pending)
+                    .await?;
+                return record_dead_letter(&db_conn, DeadLetterKind::League, &query, &exhausted)
+                    .await;
+            }
+            Err(exhausted) => {
+                if let Err(e) =
+                    record_dead_letter(&db_conn, DeadLetterKind::League, &query, &exhausted).await
+                {
+                    error!("Error recording dead letter for {query:?}: {e:?}");
+                }
+                Vec::new()
+            }
+        };
+        publishing_channel
+            .send(LeagueEntries { query, entries })
+            .await?;
+        Ok(())
+    }
+}
+
+/// Fetch the [`LeagueEntry`]s of the summoner of a query from Riot API, on the
+/// platform that the match was played on.
+///
+/// The league-v4 by-PUUID endpoint is not wrapped by this version of riven, so it is
+/// requested directly, still going through riven's rate limiting.
+async fn get_league_entries(
+    riot_api: &RiotApi,
+    query: &LeagueQuery,
+) -> anyhow::Result<Vec<LeagueEntry>> {
+    let platform = query
+        .match_id
+        .platform()
+        .with_context(|| format!("Unknown platform of match ID {:?}", query.match_id))?;
+    let route = platform.into();
+    let request = riot_api.request(
+        Method::GET,
+        route,
+        &format!("/lol/league/v4/entries/by-puuid/{}", query.puuid.as_str()),
+    );
+    Ok(riot_api
+        .execute_val("league-v4.getLeagueEntriesByPUUID", route, request)
+        .await?)
+}
+
+impl Publish for LeagueRequester {
+    type Input = LeagueQuery;
+    type Output = LeagueEntries;
+
+    /// Add a [`LeagueQuery`] to the queue.
+    async fn push(&self, data: Self::Input, priority: Priority) {
+        self.league_queue.push((data, priority)).await;
+    }
+
+    /// Wait on the queue of [`LeagueQuery`], fetching [`LeagueEntry`]s for them through the
+    /// scheduler and pushing the entries to the provided publishing channel.
+    #[tracing::instrument]
+    async fn start(&self, publishing_channel: Sender<Self::Output>) {
+        while let Some(((query, priority), handling)) = self.league_queue.next().await {
+            let fetch = Self::fetch(
+                self.scheduler.clone(),
+                self.db_conn.clone(),
+                self.retry_policy,
+                query,
+                priority,
+                publishing_channel.clone(),
+                handling,
+            );
+            tokio::task::spawn(async move {
+                if let Err(e) = fetch.await {
+                    error!("Error publishing league entries: {e:?}");
+                }
+            });
+        }
+
+        let pending = self.league_queue.drain().await;
+        if !pending.is_empty() {
+            warn!(
+                "Leaving {} queued league jobs pending for the next run",
+                pending.len()
+            );
+        }
+    }
+}
@@ -1,16 +1,36 @@
+use super::queue::{Handling, RequestQueue};
+use super::retry::{found, record_dead_letter, FailureKind, RetryPolicy};
+use super::scheduler::{Priority, Scheduler};
 use super::Publish;
 use anyhow::Context;
-use riven::{models::match_v5::Match, RiotApi, RiotApiError};
-use std::{collections::VecDeque, sync::Arc};
-use tokio::sync::{mpsc::UnboundedSender, Mutex};
-use tracing::{debug, error};
+use riven::reqwest::Method;
+use riven::{models::match_v5::Match, RiotApi};
+use std::sync::Arc;
+use the_collector_db::model::{DeadLetterKind, JobKind, JobStatus};
+use the_collector_db::DbHandler;
+use the_collector_types::MatchId;
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
+
+/// [`Match`] data fetched from the Riot API, along with the response body it was parsed
+/// from, which is archived unchanged so that fields riven does not model are kept.
+#[derive(Debug)]
+pub struct MatchPayload {
+    pub data: Match,
+    pub raw: Vec<u8>,
+    /// Priority the match was fetched at, which requests following up on it share.
+    pub priority: Priority,
+}
 
 /// Requester for fetching [`Match`] data from the Riot API given match IDs.
 ///
 /// This should have its [`Publish::start`] method called within a Tokio task.
 pub struct MatchDataRequester {
-    riot_api: Arc<RiotApi>,
-    match_queue: Mutex<VecDeque<String>>,
+    scheduler: Arc<Scheduler>,
+    db_conn: Arc<DbHandler>,
+    retry_policy: RetryPolicy,
+    match_queue: RequestQueue<(MatchId, Priority)>,
 }
 
 impl std::fmt::Debug for MatchDataRequester {
@@ -22,61 +42,149 @@ impl std::fmt::Debug for MatchDataRequester {
 }
 
 impl MatchDataRequester {
-    pub fn new(riot_api: Arc<RiotApi>) -> Self {
-        let match_queue = Mutex::new(VecDeque::new());
+    pub fn new(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        capacity: usize,
+        cancel: CancellationToken,
+    ) -> Self {
+        let match_queue = RequestQueue::new(capacity, cancel);
         Self {
-            riot_api,
+            scheduler,
+            db_conn,
+            retry_policy,
             match_queue,
         }
     }
 
-    /// Fetch [`Match`] data from Riot API given a match ID.
-    async fn get_match(&self, match_id: &str) -> Result<Option<Match>, RiotApiError> {
-        self.riot_api
-            .match_v5()
-            .get_match(riven::consts::RegionalRoute::AMERICAS, match_id)
-            .await
-    }
-
-    async fn run(
-        &self,
-        publishing_channel: &UnboundedSender<<Self as Publish>::Output>,
+    /// Fetch [`Match`] data for a match ID through the scheduler, and send it to the
+    /// publishing channel. The job for the match is marked as in flight
+    /// until handled, or as failed if fetching exhausts its retries, in which case it is
+    /// also recorded as a dead letter. Jobs for matches that do not exist are done, and
+    /// those interrupted by shutdown are left pending.
+    async fn fetch(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        match_id: MatchId,
+        priority: Priority,
+        publishing_channel: Sender<MatchPayload>,
+        mut handling: Handling,
     ) -> anyhow::Result<()> {
-        let mut lock = self.match_queue.lock().await;
-        if let Some(match_id) = lock.pop_front() {
-            drop(lock);
-            let match_data = self
-                .get_match(&match_id)
-                .await?
-                .context("No match with ID {match_id:?} found")?;
-            debug!(
-                "Fetched match data for match: {:?}",
-                match_data.metadata.match_id
-            );
-            publishing_channel.send(match_data)?;
-        }
+        db_conn
+            .update_job(JobKind::MatchData, &match_id, JobStatus::InFlight)
+            .await?;
+        let result = retry_policy
+            .run(&mut handling, || {
+                let match_id = match_id.clone();
+                scheduler.call(priority, move |riot_api| async move {
+                    found(get_match(&riot_api, &match_id).await?, || {
+                        format!("match with ID {match_id:?}")
+                    })
+                })
+            })
+            .await;
+        let (data, raw) = match result {
+            Ok(payload) => payload,
+            Err(exhausted) => {
+                // There is nothing left to fetch for matches that do not exist, and jobs
+                // cancelled by shutdown are resumed on the next run
+                let status = match exhausted.kind {
+                    FailureKind::NotFound => JobStatus::Done,
+                    FailureKind::Cancelled => JobStatus::Pending,
+                    _ => JobStatus::Failed,
+                };
+                db_conn
+                    .update_job(JobKind::MatchData, &match_id, status)
+                    .await?;
+                return record_dead_letter(
+                    &db_conn,
+                    DeadLetterKind::MatchData,
+                    &match_id,
+                    &exhausted,
+                )
+                .await;
+            }
+        };
+        debug!("Fetched match data for match: {:?}", data.metadata.match_id);
+        publishing_channel
+            .send(MatchPayload {
+                data,
+                raw,
+                priority,
+            })
+            .await?;
         Ok(())
     }
 }
 
+/// Fetch [`Match`] data from Riot API given a match ID, routed to the region of the
+/// platform prefixing the ID.
+///
+/// This makes the same request as riven's `get_match`, but also returns the response body.
+async fn get_match(
+    riot_api: &RiotApi,
+    match_id: &MatchId,
+) -> anyhow::Result<Option<(Match, Vec<u8>)>> {
+    let platform = match_id
+        .platform()
+        .with_context(|| format!("Unknown platform of match ID {match_id:?}"))?;
+    let route: &'static str = platform.to_regional().into();
+    let request = riot_api.request(
+        Method::GET,
+        route,
+        &format!("/lol/match/v5/matches/{}", match_id.as_str()),
+    );
+    let response = riot_api
+        .execute_raw("match-v5.getMatch", route, request)
+        .await?;
+    if response.status_none {
+        return Ok(None);
+    }
+    let raw = response.response.bytes().await?.to_vec();
+    let data = serde_json::from_slice(&raw)?;
+    Ok(Some((data, raw)))
+}
+
 impl Publish for MatchDataRequester {
-    type Input = Vec<String>;
-    type Output = Match;
+    type Input = Vec<MatchId>;
+    type Output = MatchPayload;
 
-    /// Add a match ID to the queue.
-    async fn push(&self, data: Self::Input) {
-        let mut lock = self.match_queue.lock().await;
-        lock.extend(data);
+    /// Add match IDs to the queue, to be fetched at the given priority.
+    async fn push(&self, data: Self::Input, priority: Priority) {
+        for match_id in data {
+            self.match_queue.push((match_id, priority)).await;
+        }
     }
 
-    /// Loop the match IDs queue, fetching [`Match`] data for them and pushing the
-    /// data to the provided publishing channel.
+    /// Wait on the match IDs queue, fetching [`Match`] data for them through the scheduler
+    /// and pushing the data to the provided publishing channel.
     #[tracing::instrument]
-    async fn start(&self, publishing_channel: UnboundedSender<Self::Output>) {
-        loop {
-            if let Err(e) = self.run(&publishing_channel).await {
-                error!("Error retrieving match data: {e:?}");
-            }
+    async fn start(&self, publishing_channel: Sender<Self::Output>) {
+        while let Some(((match_id, priority), handling)) = self.match_queue.next().await {
+            let fetch = Self::fetch(
+                self.scheduler.clone(),
+                self.db_conn.clone(),
+                self.retry_policy,
+                match_id,
+                priority,
+                publishing_channel.clone(),
+                handling,
+            );
+            tokio::task::spawn(async move {
+                if let Err(e) = fetch.await {
+                    error!("Error retrieving match data: {e:?}");
+                }
+            });
+        }
+
+        let pending = self.match_queue.drain().await;
+        if !pending.is_empty() {
+            warn!(
+                "Leaving {} queued match data jobs pending for the next run",
+                pending.len()
+            );
         }
     }
 }
@@ -1,27 +1,64 @@
+use super::queue::{Handling, RequestQueue};
+use super::retry::{
+    platform_key, record_dead_letter, split_platform_key, DeadLetterKey, RetryPolicy,
+};
+use super::scheduler::{Priority, Scheduler};
 use super::Publish;
-use riven::consts::RegionalRoute::AMERICAS;
+use riven::consts::PlatformRoute;
 use riven::{RiotApi, RiotApiError};
-use std::collections::VecDeque;
 use std::sync::Arc;
-use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::Mutex;
-use tracing::{debug, error};
+use the_collector_db::model::DeadLetterKind;
+use the_collector_db::DbHandler;
+use the_collector_types::{MatchId, Puuid};
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
 
 // Max value that Riot API accepts for getting match IDs
 const MAX_MATCHES: i32 = 100;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GetMatchIdsQuery {
-    pub puuid: String,
+    pub puuid: Puuid,
+    /// Platform of the summoner, which determines the region to route to.
+    pub platform: PlatformRoute,
     pub start_time: Option<i64>,
     pub count: Option<i32>,
 }
 
+/// Keyed by platform and PUUID, e.g. `NA1:<PUUID>`. Requeued queries fetch the latest
+/// matches, as the start time is not kept.
+impl DeadLetterKey for GetMatchIdsQuery {
+    fn to_key(&self) -> String {
+        platform_key(self.platform, self.puuid.as_str())
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        let (platform, puuid) = split_platform_key(key)?;
+        Some(Self {
+            puuid: Puuid::from(puuid),
+            platform,
+            start_time: None,
+            count: None,
+        })
+    }
+}
+
+/// Match IDs fetched for a [`GetMatchIdsQuery`], in chronological order.
+#[derive(Debug)]
+pub struct MatchIds {
+    pub match_ids: Vec<MatchId>,
+    /// Priority of the query, which the matches should also be fetched at.
+    pub priority: Priority,
+}
+
 /// Requester for fetching Match IDs from the Riot API given
 /// a [`GetMatchesQuery`].
 pub struct MatchIdsRequester {
-    riot_api: Arc<RiotApi>,
-    matches_queue: Mutex<VecDeque<GetMatchIdsQuery>>,
+    scheduler: Arc<Scheduler>,
+    db_conn: Arc<DbHandler>,
+    retry_policy: RetryPolicy,
+    matches_queue: RequestQueue<(GetMatchIdsQuery, Priority)>,
 }
 
 impl std::fmt::Debug for MatchIdsRequester {
@@ -33,67 +70,121 @@ impl std::fmt::Debug for MatchIdsRequester {
 }
 
 impl MatchIdsRequester {
-    pub fn new(riot_api: Arc<RiotApi>) -> Self {
-        let matches_queue = Mutex::new(VecDeque::new());
+    pub fn new(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        capacity: usize,
+        cancel: CancellationToken,
+    ) -> Self {
+        let matches_queue = RequestQueue::new(capacity, cancel);
         Self {
-            riot_api,
+            scheduler,
+            db_conn,
+            retry_policy,
             matches_queue,
         }
     }
 
-    /// Get matches from Riot API given a [`GetMatchesQuery`].
-    async fn get_matches(&self, query: &GetMatchIdsQuery) -> Result<Vec<String>, RiotApiError> {
-        self.riot_api
-            .match_v5()
-            .get_match_ids_by_puuid(
-                AMERICAS,
-                &query.puuid,
-                query.count.or(Some(MAX_MATCHES)),
-                None,
-                None,
-                query.start_time,
-                None,
-                None,
-            )
-            .await
-    }
-
-    async fn run(
-        &self,
-        publishing_channel: &UnboundedSender<<Self as Publish>::Output>,
+    /// Fetch the match IDs of a query through the scheduler, and send them to the
+    /// publishing channel. The query is recorded as a dead letter if it exhausts its
+    /// retries.
+    async fn fetch(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        query: GetMatchIdsQuery,
+        priority: Priority,
+        publishing_channel: Sender<MatchIds>,
+        mut handling: Handling,
     ) -> anyhow::Result<()> {
-        let mut lock = self.matches_queue.lock().await;
-        if let Some(matches_query) = lock.pop_front() {
-            drop(lock);
-            let mut match_ids = self.get_matches(&matches_query).await?;
-            // Reverse the match IDs to iterate in chronological order
-            match_ids.reverse();
-            debug!("Got match IDs: {match_ids:?}");
-            publishing_channel.send(match_ids)?;
-        }
+        let result = retry_policy
+            .run(&mut handling, || {
+                let query = query.clone();
+                scheduler.call(priority, move |riot_api| async move {
+                    Ok(get_matches(&riot_api, &query).await?)
+                })
+            })
+            .await;
+        let mut match_ids = match result {
+            Ok(match_ids) => match_ids,
+            Err(exhausted) => {
+                return record_dead_letter(&db_conn, DeadLetterKind::MatchIds, &query, &exhausted)
+                    .await;
+            }
+        };
+        // Reverse the match IDs to iterate in chronological order
+        match_ids.reverse();
+        debug!("Got match IDs: {match_ids:?}");
+        publishing_channel
+            .send(MatchIds {
+                match_ids,
+                priority,
+            })
+            .await?;
         Ok(())
     }
 }
 
+/// Get matches from Riot API given a [`GetMatchesQuery`].
+async fn get_matches(
+    riot_api: &RiotApi,
+    query: &GetMatchIdsQuery,
+) -> Result<Vec<MatchId>, RiotApiError> {
+    let match_ids = riot_api
+        .match_v5()
+        .get_match_ids_by_puuid(
+            query.platform.to_regional(),
+            query.puuid.as_str(),
+            query.count.or(Some(MAX_MATCHES)),
+            None,
+            None,
+            query.start_time,
+            None,
+            None,
+        )
+        .await?;
+    Ok(match_ids.into_iter().map(MatchId::from).collect())
+}
+
 impl Publish for MatchIdsRequester {
     type Input = GetMatchIdsQuery;
-    type Output = Vec<String>;
+    type Output = MatchIds;
 
     /// Push a [`GetMatchesQuery`] to the queue to fetch match IDs for.
-    async fn push(&self, data: Self::Input) {
-        let mut lock = self.matches_queue.lock().await;
-        lock.push_back(data);
+    async fn push(&self, data: Self::Input, priority: Priority) {
+        self.matches_queue.push((data, priority)).await;
     }
 
-    /// Loop the matches queue, fetching Match IDs for each `[GetMatchesQuery]`.
+    /// Wait on the matches queue, fetching Match IDs for each `[GetMatchesQuery]` through
+    /// the scheduler.
     ///
     /// Fetched data is pushed to the provided publishing channel.
     #[tracing::instrument]
-    async fn start(&self, publishing_channel: UnboundedSender<Self::Output>) {
-        loop {
-            if let Err(e) = self.run(&publishing_channel).await {
-                error!("Error retrieving match data: {e:?}");
-            }
+    async fn start(&self, publishing_channel: Sender<Self::Output>) {
+        while let Some(((query, priority), handling)) = self.matches_queue.next().await {
+            let fetch = Self::fetch(
+                self.scheduler.clone(),
+                self.db_conn.clone(),
+                self.retry_policy,
+                query,
+                priority,
+                publishing_channel.clone(),
+                handling,
+            );
+            tokio::task::spawn(async move {
+                if let Err(e) = fetch.await {
+                    error!("Error retrieving match IDs: {e:?}");
+                }
+            });
+        }
+
+        let pending = self.matches_queue.drain().await;
+        if !pending.is_empty() {
+            warn!(
+                "Dropping {} queued match ID queries: {pending:?}",
+                pending.len()
+            );
         }
     }
 }
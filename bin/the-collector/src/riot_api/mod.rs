@@ -1,13 +1,27 @@
-use tokio::sync::mpsc::UnboundedSender;
+use scheduler::Priority;
+use tokio::sync::mpsc::Sender;
 
 pub mod account;
+pub mod league;
 pub mod match_data;
 pub mod match_ids;
+pub mod queue;
+pub mod retry;
+pub mod scheduler;
+pub mod spectator;
+pub mod timeline;
 
+/// A requester of data from the Riot API, which handles the data pushed to it in
+/// [`Publish::start`] and publishes what it fetches.
 pub trait Publish {
     type Input;
     type Output;
 
-    async fn push(&self, data: Self::Input);
-    async fn start(&self, publishing_channel: UnboundedSender<Self::Output>);
+    /// Queue data to be handled at a priority, waiting while the queue is full.
+    async fn push(&self, data: Self::Input, priority: Priority);
+
+    /// Wait for queued data and handle it, sending what is fetched to the publishing
+    /// channel. Runs until shutdown is requested, then waits for the data being handled
+    /// and logs what is left queued, so should be called within its own Tokio task.
+    async fn start(&self, publishing_channel: Sender<Self::Output>);
 }
@@ -0,0 +1,169 @@
+//! Bounded queue that requesters wait on for work, rather than polling.
+
+use std::sync::Arc;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Queue of items for a requester to handle, holding up to a fixed capacity.
+///
+/// At most that many items are handled at once as well, so pushing waits while the
+/// requester is busy and its queue is full, which holds back whatever is producing the
+/// items rather than letting work pile up in memory.
+///
+/// Once shutdown is requested through the cancellation token, no more items are taken
+/// or queued, leaving [`RequestQueue::drain`] to collect those still queued.
+#[derive(Debug)]
+pub struct RequestQueue<T> {
+    tx: Sender<T>,
+    rx: Mutex<Receiver<T>>,
+    handling: Arc<Semaphore>,
+    capacity: usize,
+    cancel: CancellationToken,
+}
+
+impl<T: std::fmt::Debug> RequestQueue<T> {
+    pub fn new(capacity: usize, cancel: CancellationToken) -> Self {
+        let capacity = capacity.max(1);
+        let (tx, rx) = channel(capacity);
+        Self {
+            tx,
+            rx: Mutex::new(rx),
+            handling: Arc::new(Semaphore::new(capacity)),
+            capacity,
+            cancel,
+        }
+    }
+
+    /// Add an item to the queue, waiting until there is room for it. Items pushed once
+    /// shutdown has been requested are logged and dropped.
+    pub async fn push(&self, item: T) {
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => {
+                warn!("Dropping item queued during shutdown: {item:?}");
+            }
+            permit = self.tx.reserve() => {
+                permit.expect("Queue receiver is never dropped").send(item);
+            }
+        }
+    }
+
+    /// Wait until an item can be handled and one is queued, and take it. The returned
+    /// [`Handling`] should be held until the item has been handled. Returns [`None`] once
+    /// shutdown has been requested.
+    pub async fn next(&self) -> Option<(T, Handling)> {
+        let take = async {
+            let permit = self
+                .handling
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("Semaphore is never closed");
+            let item = self
+                .rx
+                .lock()
+                .await
+                .recv()
+                .await
+                .expect("Queue sender is never dropped");
+            let handling = Handling {
+                permit: Some(permit),
+                cancel: self.cancel.clone(),
+            };
+            (item, handling)
+        };
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => None,
+            next = take => Some(next),
+        }
+    }
+
+    /// Wait for the items being handled to be done with, and take the items still
+    /// queued, which will not be handled.
+    pub async fn drain(&self) -> Vec<T> {
+        let _permits = self
+            .handling
+            .acquire_many(self.capacity as u32)
+            .await
+            .expect("Semaphore is never closed");
+        let mut rx = self.rx.lock().await;
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+}
+
+/// An item's slot amongst those a [`RequestQueue`] handles at once, which is given up
+/// when dropped.
+#[derive(Debug)]
+pub struct Handling {
+    permit: Option<OwnedSemaphorePermit>,
+    cancel: CancellationToken,
+}
+
+impl Handling {
+    /// Handle an item that was not taken from a queue, so has no slot to give up.
+    pub fn unqueued(cancel: CancellationToken) -> Self {
+        Self {
+            permit: None,
+            cancel,
+        }
+    }
+
+    /// Give up the slot before the item has been handled, so that other items can be
+    /// handled while this one waits (e.g. to be retried).
+    pub fn release(&mut self) {
+        self.permit.take();
+    }
+
+    /// Wait until shutdown has been requested.
+    pub async fn cancelled(&self) {
+        self.cancel.cancelled().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_push_waits_while_full() {
+        let queue = RequestQueue::new(1, CancellationToken::new());
+        queue.push(0).await;
+        assert!(timeout(Duration::from_millis(10), queue.push(1))
+            .await
+            .is_err());
+
+        // Taking an item makes room, but handling it still holds a slot
+        let (item, permit) = queue.next().await.unwrap();
+        assert_eq!(item, 0);
+        queue.push(1).await;
+        assert!(timeout(Duration::from_millis(10), queue.next())
+            .await
+            .is_err());
+        drop(permit);
+        assert_eq!(queue.next().await.unwrap().0, 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_queue() {
+        let cancel = CancellationToken::new();
+        let queue = RequestQueue::new(2, cancel.clone());
+        queue.push(0).await;
+        queue.push(1).await;
+        let (_, permit) = queue.next().await.unwrap();
+
+        // Nothing more is taken or queued, and draining waits for the item being handled
+        cancel.cancel();
+        assert!(queue.next().await.is_none());
+        queue.push(2).await;
+        assert!(timeout(Duration::from_millis(10), queue.drain())
+            .await
+            .is_err());
+        drop(permit);
+        assert_eq!(queue.drain().await, [1]);
+    }
+}
@@ -0,0 +1,418 @@
+//! Retrying failed Riot API requests with exponential backoff, and recording those that
+//! exhaust their retries as dead letters.
+//!
+//! riven already retries rate-limited and server errors a few times within each call,
+//! so retries here are spaced much further apart, to ride out outages.
+
+use super::queue::Handling;
+use riven::consts::PlatformRoute;
+use riven::RiotApiError;
+use std::future::Future;
+use std::time::Duration;
+use the_collector_db::model::DeadLetterKind;
+use the_collector_db::DbHandler;
+use the_collector_types::MatchId;
+use tracing::{error, warn};
+
+/// How a requester retries a failed request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each retry after it.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// What a failed request means for retrying it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// 404: what was requested does not exist, so is not retried.
+    NotFound,
+    /// 429: retried once the rate limit has reset, if the response says when.
+    RateLimited { retry_after: Option<Duration> },
+    /// 5xx, or no response at all: retried with backoff.
+    Unavailable,
+    /// 401 or 403: the API key is invalid or expired, so is not retried.
+    Unauthorized,
+    /// Any other error (e.g. a bad request), which is not retried.
+    Permanent,
+    /// Shutdown was requested while waiting to retry, so the request is left for the
+    /// next run rather than given up on.
+    Cancelled,
+}
+
+impl FailureKind {
+    /// Classify an error returned by a request, based on the [`RiotApiError`] causing it.
+    pub fn classify(error: &anyhow::Error) -> Self {
+        if error.downcast_ref::<NotFound>().is_some() {
+            return Self::NotFound;
+        }
+        // Failing to read the body of a response is treated like getting no response
+        if error.downcast_ref::<riven::reqwest::Error>().is_some() {
+            return Self::Unavailable;
+        }
+        let Some(error) = error.downcast_ref::<RiotApiError>() else {
+            return Self::Permanent;
+        };
+        let retry_after = error
+            .response()
+            .and_then(|response| response.headers().get("retry-after"))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs);
+        Self::from_status(
+            error.status_code().map(|status| status.as_u16()),
+            retry_after,
+        )
+    }
+
+    fn from_status(status: Option<u16>, retry_after: Option<Duration>) -> Self {
+        match status {
+            None => Self::Unavailable,
+            Some(404) => Self::NotFound,
+            Some(429) => Self::RateLimited { retry_after },
+            Some(401 | 403) => Self::Unauthorized,
+            Some(500..=599) => Self::Unavailable,
+            Some(_) => Self::Permanent,
+        }
+    }
+
+    fn is_retried(self) -> bool {
+        matches!(self, Self::RateLimited { .. } | Self::Unavailable)
+    }
+}
+
+/// Error for a request that found nothing, which riven returns as [`None`] rather
+/// than as an error.
+#[derive(Debug)]
+pub struct NotFound(pub String);
+
+impl std::fmt::Display for NotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No {} found", self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// Unwrap the response of a request, turning [`None`] into a [`NotFound`] error about
+/// `what`, so that it is classified as [`FailureKind::NotFound`].
+pub fn found<T>(data: Option<T>, what: impl FnOnce() -> String) -> anyhow::Result<T> {
+    data.ok_or_else(|| NotFound(what()).into())
+}
+
+/// A request that failed for good, along with its last error.
+#[derive(Debug)]
+pub struct Exhausted {
+    pub error: anyhow::Error,
+    pub kind: FailureKind,
+    pub attempts: u32,
+}
+
+impl RetryPolicy {
+    /// Delay before retrying a request that has failed `attempts` times.
+    pub fn delay(&self, attempts: u32, kind: FailureKind) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.max_delay);
+        match kind {
+            FailureKind::RateLimited {
+                retry_after: Some(retry_after),
+            } => retry_after.max(backoff),
+            _ => backoff,
+        }
+    }
+
+    /// Longest time a request can wait between its attempts, unless rate limits ask
+    /// for longer.
+    pub fn max_total_delay(&self) -> Duration {
+        (1..self.max_attempts)
+            .map(|attempts| self.delay(attempts, FailureKind::Unavailable))
+            .sum()
+    }
+
+    /// Make a request until it succeeds, it fails in a way that is not retried, or it
+    /// has been attempted [`Self::max_attempts`] times.
+    ///
+    /// The request's slot in its queue is released before waiting to retry, so that
+    /// other requests are handled in the meantime. Waiting is cut short once shutdown
+    /// is requested, failing the request as [`FailureKind::Cancelled`].
+    pub async fn run<T, F, Fut>(
+        &self,
+        handling: &mut Handling,
+        mut request: F,
+    ) -> Result<T, Exhausted>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let error = match request().await {
+                Ok(data) => return Ok(data),
+                Err(error) => error,
+            };
+            let kind = FailureKind::classify(&error);
+            if kind == FailureKind::Unauthorized {
+                error!("Riot API rejected the API key, which may have expired");
+            }
+            if !kind.is_retried() || attempts >= self.max_attempts {
+                return Err(Exhausted {
+                    error,
+                    kind,
+                    attempts,
+                });
+            }
+            let delay = self.delay(attempts, kind);
+            warn!("Retrying in {delay:?} after attempt {attempts} failed: {error:?}");
+            handling.release();
+            tokio::select! {
+                _ = handling.cancelled() => {
+                    return Err(Exhausted {
+                        error: error.context("Shutdown requested while waiting to retry"),
+                        kind: FailureKind::Cancelled,
+                        attempts,
+                    });
+                }
+                _ = tokio::time::sleep(delay) => {}
+            }
+        }
+    }
+}
+
+/// Items that are stored as the key of a dead letter, and read back when requeued.
+pub trait DeadLetterKey: Sized {
+    fn to_key(&self) -> String;
+
+    fn from_key(key: &str) -> Option<Self>;
+}
+
+impl DeadLetterKey for MatchId {
+    fn to_key(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Some(Self::from(key))
+    }
+}
+
+/// Prefix the key of an item requested on a platform with it, e.g. `NA1:<PUUID>`.
+pub fn platform_key(platform: PlatformRoute, key: &str) -> String {
+    format!("{platform}:{key}")
+}
+
+/// Split a key made by [`platform_key`] back into the platform and the rest of the key.
+pub fn split_platform_key(key: &str) -> Option<(PlatformRoute, &str)> {
+    let (platform, key) = key.split_once(':')?;
+    Some((platform.parse().ok()?, key))
+}
+
+/// Record a request that exhausted its retries as a dead letter. Not found errors are
+/// only logged, as there is nothing to requeue, as are requests cancelled by shutdown.
+pub async fn record_dead_letter(
+    db_conn: &DbHandler,
+    kind: DeadLetterKind,
+    item: &impl DeadLetterKey,
+    exhausted: &Exhausted,
+) -> anyhow::Result<()> {
+    let key = item.to_key();
+    if exhausted.kind == FailureKind::Cancelled {
+        warn!(
+            "Stopped retrying {kind:?} request for {key:?} on shutdown: {:?}",
+            exhausted.error
+        );
+        return Ok(());
+    }
+    error!(
+        "Giving up on {kind:?} request for {key:?} after {} attempts: {:?}",
+        exhausted.attempts, exhausted.error
+    );
+    if exhausted.kind == FailureKind::NotFound {
+        return Ok(());
+    }
+    db_conn
+        .insert_dead_letter(
+            kind,
+            &key,
+            &format!("{:?}", exhausted.error),
+            exhausted.attempts.into(),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_util::sync::CancellationToken;
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+        };
+        let delays: Vec<u64> = (1..=6)
+            .map(|attempts| policy.delay(attempts, FailureKind::Unavailable).as_secs())
+            .collect();
+        assert_eq!(delays, [1, 2, 4, 8, 10, 10]);
+        assert_eq!(policy.max_total_delay(), Duration::from_secs(65));
+
+        let rate_limited = FailureKind::RateLimited {
+            retry_after: Some(Duration::from_secs(30)),
+        };
+        assert_eq!(policy.delay(1, rate_limited), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_failure_kinds() {
+        assert_eq!(
+            FailureKind::from_status(Some(404), None),
+            FailureKind::NotFound
+        );
+        assert_eq!(
+            FailureKind::from_status(Some(403), None),
+            FailureKind::Unauthorized
+        );
+        assert_eq!(
+            FailureKind::from_status(Some(503), None),
+            FailureKind::Unavailable
+        );
+        assert_eq!(
+            FailureKind::from_status(None, None),
+            FailureKind::Unavailable
+        );
+        assert_eq!(
+            FailureKind::from_status(Some(400), None),
+            FailureKind::Permanent
+        );
+        assert!(FailureKind::from_status(Some(429), None).is_retried());
+        assert_eq!(
+            FailureKind::classify(&anyhow::anyhow!("No match found")),
+            FailureKind::Permanent
+        );
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_are_not_retried() {
+        let policy = RetryPolicy {
+            base_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        let mut attempts = 0;
+        let result: Result<(), _> = policy
+            .run(&mut Handling::unqueued(CancellationToken::new()), || {
+                attempts += 1;
+                async { anyhow::bail!("No match found") }
+            })
+            .await;
+        let exhausted = result.unwrap_err();
+        assert_eq!(exhausted.attempts, 1);
+        assert_eq!(exhausted.kind, FailureKind::Permanent);
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn test_dead_letter_keys() {
+        let key = platform_key(PlatformRoute::EUW1, "puuid-0");
+        assert_eq!(key, "EUW1:puuid-0");
+        assert_eq!(
+            split_platform_key(&key),
+            Some((PlatformRoute::EUW1, "puuid-0"))
+        );
+        assert_eq!(split_platform_key("puuid-0"), None);
+        assert_eq!(split_platform_key("XX1:puuid-0"), None);
+        assert_eq!(
+            MatchId::from_key("NA1_5000000001"),
+            Some(MatchId::from("NA1_5000000001"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_items_are_not_dead_lettered() {
+        use the_collector_db::sqlite::SqliteStorage;
+        use the_collector_db::SqlitePoolOptions;
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let db_conn = DbHandler::new(SqliteStorage::new(pool));
+        db_conn.migrate().await.unwrap();
+
+        let policy = RetryPolicy {
+            base_delay: Duration::ZERO,
+            ..Default::default()
+        };
+        let match_id = MatchId::from("NA1_5000000001");
+        let result = policy
+            .run(
+                &mut Handling::unqueued(CancellationToken::new()),
+                || async { found(None::<()>, || format!("match with ID {match_id:?}")) },
+            )
+            .await;
+        let exhausted = result.unwrap_err();
+        assert_eq!(exhausted.kind, FailureKind::NotFound);
+        assert_eq!(exhausted.attempts, 1);
+
+        record_dead_letter(&db_conn, DeadLetterKind::MatchData, &match_id, &exhausted)
+            .await
+            .unwrap();
+        assert!(db_conn.get_dead_letters().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_waiting_to_retry_releases_slot_until_shutdown() {
+        use super::super::queue::RequestQueue;
+        use std::sync::Arc;
+
+        let policy = RetryPolicy {
+            base_delay: Duration::from_secs(60 * 60),
+            ..Default::default()
+        };
+        let cancel = CancellationToken::new();
+        let queue = Arc::new(RequestQueue::new(1, cancel.clone()));
+        queue.push(0).await;
+        let (_, mut handling) = queue.next().await.unwrap();
+        queue.push(1).await;
+        // The next item can only be taken once the first gives up its slot
+        tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                let _next = queue.next().await.unwrap();
+                cancel.cancel();
+            }
+        });
+
+        let run = policy.run(&mut handling, || async {
+            // Errors without a response are retried
+            let error = riven::reqwest::Client::new()
+                .get("not a url")
+                .build()
+                .unwrap_err();
+            Err::<(), _>(error.into())
+        });
+        let exhausted = tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(exhausted.kind, FailureKind::Cancelled);
+        assert_eq!(exhausted.attempts, 1);
+    }
+}
@@ -0,0 +1,360 @@
+//! Scheduling of every call to the Riot API made by the requesters, so that urgent
+//! requests are not stuck behind a backlog of less urgent ones.
+//!
+//! riven waits out rate limits within each call, so only a few calls are let through
+//! at a time. The rest wait here by [`Priority`], where a newly queued urgent request
+//! goes ahead of any less urgent ones still waiting. Backfill calls can never take
+//! every slot, so that a long backfill waiting out rate limits leaves one free for
+//! more urgent calls.
+
+use anyhow::Context;
+use riven::RiotApi;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::{oneshot, Mutex, Notify, OwnedSemaphorePermit, Semaphore};
+
+/// How urgent a request is, from most to least urgent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Priority {
+    /// Requests that someone is waiting on, such as the first matches of a summoner who
+    /// was just followed.
+    Interactive,
+    /// Requests for new matches of followed summoners.
+    Polling,
+    /// Requests for historical or slowly changing data, such as imports and account
+    /// refreshes.
+    Backfill,
+}
+
+impl Priority {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How many requests of each [`Priority`] are let through per round while requests of
+/// several priorities are waiting, so that less urgent requests still make progress.
+/// A budget of 0 is treated as 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budgets {
+    pub interactive: u32,
+    pub polling: u32,
+    pub backfill: u32,
+}
+
+impl Budgets {
+    fn as_array(&self) -> [u32; 3] {
+        [self.interactive, self.polling, self.backfill].map(|budget| budget.max(1))
+    }
+}
+
+impl Default for Budgets {
+    fn default() -> Self {
+        Self {
+            interactive: 8,
+            polling: 4,
+            backfill: 1,
+        }
+    }
+}
+
+/// Number of requests of each [`Priority`] waiting to be let through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueDepths {
+    pub interactive: usize,
+    pub polling: usize,
+    pub backfill: usize,
+}
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Waiting jobs of each priority, along with what is left of each budget this round.
+struct Queues<J> {
+    jobs: [VecDeque<J>; 3],
+    remaining: [u32; 3],
+}
+
+impl<J> Queues<J> {
+    fn new() -> Self {
+        Self {
+            jobs: Default::default(),
+            remaining: [0; 3],
+        }
+    }
+
+    fn push(&mut self, priority: Priority, job: J) {
+        self.jobs[priority.index()].push_back(job);
+    }
+
+    /// Take the most urgent job whose priority has budget left, along with its priority,
+    /// starting a new round once every priority with waiting jobs has used up its budget.
+    /// Backfill jobs are left waiting unless `backfill` is set.
+    fn pop(&mut self, budgets: &Budgets, backfill: bool) -> Option<(Priority, J)> {
+        let waiting = |queues: &Self, index: usize| {
+            !queues.jobs[index].is_empty() && (backfill || index != Priority::Backfill.index())
+        };
+        if (0..3).all(|index| !waiting(self, index) || self.remaining[index] == 0) {
+            self.remaining = budgets.as_array();
+        }
+        let index = (0..3).find(|&index| waiting(self, index) && self.remaining[index] > 0)?;
+        self.remaining[index] -= 1;
+        let priority = [Priority::Interactive, Priority::Polling, Priority::Backfill][index];
+        Some((priority, self.jobs[index].pop_front()?))
+    }
+
+    fn depths(&self) -> QueueDepths {
+        QueueDepths {
+            interactive: self.jobs[Priority::Interactive.index()].len(),
+            polling: self.jobs[Priority::Polling.index()].len(),
+            backfill: self.jobs[Priority::Backfill.index()].len(),
+        }
+    }
+}
+
+/// Scheduler that every call to the Riot API goes through. [`Scheduler::start`] should
+/// be called within its own Tokio task, as calls wait until it lets them through.
+pub struct Scheduler {
+    riot_api: Arc<RiotApi>,
+    budgets: Budgets,
+    queues: Mutex<Queues<Job>>,
+    queued: Arc<Notify>,
+    in_flight: Arc<Semaphore>,
+    /// Slots that backfill calls may take, one fewer than `in_flight`.
+    backfill_slots: Arc<Semaphore>,
+}
+
+impl std::fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("budgets", &self.budgets)
+            .field("in_flight", &self.in_flight)
+            .finish()
+    }
+}
+
+impl Scheduler {
+    /// Create a scheduler letting up to `max_in_flight` calls run at once, all but one
+    /// of which may be backfill calls. At least 2 calls are let through.
+    pub fn new(riot_api: Arc<RiotApi>, max_in_flight: usize, budgets: Budgets) -> Self {
+        let max_in_flight = max_in_flight.max(2);
+        Self {
+            riot_api,
+            budgets,
+            queues: Mutex::new(Queues::new()),
+            queued: Arc::new(Notify::new()),
+            in_flight: Arc::new(Semaphore::new(max_in_flight)),
+            backfill_slots: Arc::new(Semaphore::new(max_in_flight - 1)),
+        }
+    }
+
+    /// Make a call to the Riot API once the scheduler lets it through, returning its
+    /// result, or an error if the call was dropped without finishing (e.g. if it
+    /// panicked).
+    pub async fn call<T, F, Fut>(&self, priority: Priority, request: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(Arc<RiotApi>) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<T>> + Send,
+        T: Send + 'static,
+    {
+        let (result_tx, result_rx) = oneshot::channel();
+        let riot_api = self.riot_api.clone();
+        let job = Box::pin(async move {
+            // The caller may have stopped waiting for the result
+            let _ = result_tx.send(request(riot_api).await);
+        });
+        self.queues.lock().await.push(priority, job);
+        self.queued.notify_one();
+        result_rx.await.context("Scheduler dropped a queued call")?
+    }
+
+    /// Number of calls of each priority waiting to be let through.
+    pub async fn queue_depths(&self) -> QueueDepths {
+        self.queues.lock().await.depths()
+    }
+
+    /// Loop letting through the most urgent waiting call whenever fewer than the
+    /// maximum number of calls are running, and backfill calls only while another slot
+    /// is left free.
+    #[tracing::instrument]
+    pub async fn start(&self) {
+        loop {
+            let permit = self
+                .in_flight
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("Semaphore is never closed");
+            let (job, backfill_permit) = self.next_job().await;
+            let queued = self.queued.clone();
+            tokio::task::spawn(async move {
+                job.await;
+                drop(permit);
+                if backfill_permit.is_some() {
+                    // Waiting backfill calls may now be let through
+                    drop(backfill_permit);
+                    queued.notify_one();
+                }
+            });
+        }
+    }
+
+    /// Wait for the next call to let through, along with the backfill slot it takes if
+    /// it is a backfill call.
+    async fn next_job(&self) -> (Job, Option<OwnedSemaphorePermit>) {
+        loop {
+            let backfill_permit = self.backfill_slots.clone().try_acquire_owned().ok();
+            let popped = self
+                .queues
+                .lock()
+                .await
+                .pop(&self.budgets, backfill_permit.is_some());
+            match popped {
+                Some((Priority::Backfill, job)) => return (job, backfill_permit),
+                Some((_, job)) => return (job, None),
+                None => self.queued.notified().await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Priority::*;
+
+    fn drain(queues: &mut Queues<(Priority, u32)>, budgets: &Budgets) -> Vec<(Priority, u32)> {
+        std::iter::from_fn(|| queues.pop(budgets, true).map(|(_, job)| job)).collect()
+    }
+
+    #[test]
+    fn test_urgent_jobs_go_first() {
+        let mut queues = Queues::new();
+        for job in 0..3 {
+            queues.push(Backfill, (Backfill, job));
+        }
+        queues.push(Polling, (Polling, 0));
+        queues.push(Interactive, (Interactive, 0));
+        assert_eq!(
+            queues.depths(),
+            QueueDepths {
+                interactive: 1,
+                polling: 1,
+                backfill: 3,
+            }
+        );
+
+        assert_eq!(
+            drain(&mut queues, &Budgets::default()),
+            [
+                (Interactive, 0),
+                (Polling, 0),
+                (Backfill, 0),
+                (Backfill, 1),
+                (Backfill, 2),
+            ]
+        );
+        assert_eq!(queues.depths(), QueueDepths::default());
+    }
+
+    #[test]
+    fn test_budgets_share_rounds() {
+        let budgets = Budgets {
+            interactive: 1,
+            polling: 2,
+            backfill: 1,
+        };
+        let mut queues = Queues::new();
+        for priority in [Interactive, Polling, Backfill] {
+            for job in 0..3 {
+                queues.push(priority, (priority, job));
+            }
+        }
+
+        let order: Vec<Priority> = drain(&mut queues, &budgets)
+            .into_iter()
+            .map(|(priority, _)| priority)
+            .collect();
+        assert_eq!(
+            order,
+            [
+                Interactive,
+                Polling,
+                Polling,
+                Backfill,
+                Interactive,
+                Polling,
+                Backfill,
+                Interactive,
+                Backfill,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dropped_calls_are_errors() {
+        let riot_api = Arc::new(RiotApi::new("RGAPI-TEST"));
+        let scheduler = Arc::new(Scheduler::new(riot_api, 1, Budgets::default()));
+        tokio::task::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.start().await }
+        });
+
+        let result: anyhow::Result<()> = scheduler
+            .call(Polling, |_| async { panic!("Call panicked") })
+            .await;
+        assert!(result.is_err());
+        // Later calls are still let through
+        let result = scheduler.call(Polling, |_| async { Ok(1) }).await;
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[test]
+    fn test_backfill_can_be_held_back() {
+        let mut queues = Queues::new();
+        queues.push(Backfill, (Backfill, 0));
+        assert!(queues.pop(&Budgets::default(), false).is_none());
+        queues.push(Polling, (Polling, 0));
+        assert_eq!(
+            queues.pop(&Budgets::default(), false),
+            Some((Polling, (Polling, 0)))
+        );
+        assert_eq!(
+            queues.pop(&Budgets::default(), true),
+            Some((Backfill, (Backfill, 0)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backfill_leaves_a_slot_free() {
+        let riot_api = Arc::new(RiotApi::new("RGAPI-TEST"));
+        let scheduler = Arc::new(Scheduler::new(riot_api, 2, Budgets::default()));
+        tokio::task::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.start().await }
+        });
+
+        // Backfill calls that never finish, as if waiting out a rate limit
+        for _ in 0..3 {
+            tokio::task::spawn({
+                let scheduler = scheduler.clone();
+                async move {
+                    scheduler
+                        .call(Backfill, |_| std::future::pending::<anyhow::Result<()>>())
+                        .await
+                }
+            });
+        }
+        tokio::task::yield_now().await;
+
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            scheduler.call(Interactive, |_| async { Ok(1) }),
+        )
+        .await
+        .expect("Interactive call should get the free slot");
+        assert_eq!(result.unwrap(), 1);
+        assert_eq!(scheduler.queue_depths().await.backfill, 2);
+    }
+}
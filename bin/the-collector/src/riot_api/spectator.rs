@@ -0,0 +1,22 @@
+use super::scheduler::{Priority, Scheduler};
+use riven::consts::PlatformRoute;
+use the_collector_types::Puuid;
+
+/// Whether a summoner is in a game right now, according to spectator-v5. Checked
+/// through the scheduler at [`Priority::Polling`], without retries, as a failed check
+/// only means polling as if the summoner were not in a game.
+pub async fn is_in_game(
+    scheduler: &Scheduler,
+    platform: PlatformRoute,
+    puuid: Puuid,
+) -> anyhow::Result<bool> {
+    let game = scheduler
+        .call(Priority::Polling, move |riot_api| async move {
+            Ok(riot_api
+                .spectator_v5()
+                .get_current_game_info_by_puuid(platform, puuid.as_str())
+                .await?)
+        })
+        .await?;
+    Ok(game.is_some())
+}
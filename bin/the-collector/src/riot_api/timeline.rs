@@ -0,0 +1,160 @@
+use super::queue::{Handling, RequestQueue};
+use super::retry::{found, record_dead_letter, FailureKind, RetryPolicy};
+use super::scheduler::{Priority, Scheduler};
+use super::Publish;
+use anyhow::Context;
+use riven::{models::match_v5::Timeline, RiotApi};
+use std::sync::Arc;
+use the_collector_db::model::{DeadLetterKind, JobKind, JobStatus};
+use the_collector_db::DbHandler;
+use the_collector_types::MatchId;
+use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, warn};
+
+/// Requester for fetching match [`Timeline`] data from the Riot API given match IDs.
+///
+/// This should have its [`Publish::start`] method called within a Tokio task.
+pub struct TimelineRequester {
+    scheduler: Arc<Scheduler>,
+    db_conn: Arc<DbHandler>,
+    retry_policy: RetryPolicy,
+    timeline_queue: RequestQueue<(MatchId, Priority)>,
+}
+
+impl std::fmt::Debug for TimelineRequester {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimelineRequester")
+            .field("timeline_queue", &self.timeline_queue)
+            .finish()
+    }
+}
+
+impl TimelineRequester {
+    pub fn new(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        capacity: usize,
+        cancel: CancellationToken,
+    ) -> Self {
+        let timeline_queue = RequestQueue::new(capacity, cancel);
+        Self {
+            scheduler,
+            db_conn,
+            retry_policy,
+            timeline_queue,
+        }
+    }
+
+    /// Fetch [`Timeline`] data for a match ID through the scheduler, and send it to the
+    /// publishing channel. The job for the match is marked as in flight
+    /// until handled, or as failed if fetching exhausts its retries, in which case it is
+    /// also recorded as a dead letter. Jobs for matches that do not exist are done, and
+    /// those interrupted by shutdown are left pending.
+    async fn fetch(
+        scheduler: Arc<Scheduler>,
+        db_conn: Arc<DbHandler>,
+        retry_policy: RetryPolicy,
+        match_id: MatchId,
+        priority: Priority,
+        publishing_channel: Sender<Timeline>,
+        mut handling: Handling,
+    ) -> anyhow::Result<()> {
+        db_conn
+            .update_job(JobKind::Timeline, &match_id, JobStatus::InFlight)
+            .await?;
+        let result = retry_policy
+            .run(&mut handling, || {
+                let match_id = match_id.clone();
+                scheduler.call(priority, move |riot_api| async move {
+                    found(get_timeline(&riot_api, &match_id).await?, || {
+                        format!("timeline for match with ID {match_id:?}")
+                    })
+                })
+            })
+            .await;
+        let timeline = match result {
+            Ok(timeline) => timeline,
+            Err(exhausted) => {
+                // There is nothing left to fetch for matches that do not exist, and jobs
+                // cancelled by shutdown are resumed on the next run
+                let status = match exhausted.kind {
+                    FailureKind::NotFound => JobStatus::Done,
+                    FailureKind::Cancelled => JobStatus::Pending,
+                    _ => JobStatus::Failed,
+                };
+                db_conn
+                    .update_job(JobKind::Timeline, &match_id, status)
+                    .await?;
+                return record_dead_letter(
+                    &db_conn,
+                    DeadLetterKind::Timeline,
+                    &match_id,
+                    &exhausted,
+                )
+                .await;
+            }
+        };
+        debug!(
+            "Fetched timeline for match: {:?}",
+            timeline.metadata.match_id
+        );
+        publishing_channel.send(timeline).await?;
+        Ok(())
+    }
+}
+
+/// Fetch [`Timeline`] data from Riot API given a match ID, routed to the region of the
+/// platform prefixing the ID.
+async fn get_timeline(riot_api: &RiotApi, match_id: &MatchId) -> anyhow::Result<Option<Timeline>> {
+    let platform = match_id
+        .platform()
+        .with_context(|| format!("Unknown platform of match ID {match_id:?}"))?;
+    Ok(riot_api
+        .match_v5()
+        .get_timeline(platform.to_regional(), match_id.as_str())
+        .await?)
+}
+
+impl Publish for TimelineRequester {
+    type Input = Vec<MatchId>;
+    type Output = Timeline;
+
+    /// Add match IDs to the queue, to be fetched at the given priority.
+    async fn push(&self, data: Self::Input, priority: Priority) {
+        for match_id in data {
+            self.timeline_queue.push((match_id, priority)).await;
+        }
+    }
+
+    /// Wait on the match IDs queue, fetching [`Timeline`] data for them through the scheduler
+    /// and pushing the data to the provided publishing channel.
+    #[tracing::instrument]
+    async fn start(&self, publishing_channel: Sender<Self::Output>) {
+        while let Some(((match_id, priority), handling)) = self.timeline_queue.next().await {
+            let fetch = Self::fetch(
+                self.scheduler.clone(),
+                self.db_conn.clone(),
+                self.retry_policy,
+                match_id,
+                priority,
+                publishing_channel.clone(),
+                handling,
+            );
+            tokio::task::spawn(async move {
+                if let Err(e) = fetch.await {
+                    error!("Error retrieving timeline data: {e:?}");
+                }
+            });
+        }
+
+        let pending = self.timeline_queue.drain().await;
+        if !pending.is_empty() {
+            warn!(
+                "Leaving {} queued timeline jobs pending for the next run",
+                pending.len()
+            );
+        }
+    }
+}
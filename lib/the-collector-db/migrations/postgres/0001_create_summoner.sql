@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS summoner (
+    puuid TEXT PRIMARY KEY NOT NULL,
+    game_name TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    create_time TIMESTAMP NOT NULL
+);
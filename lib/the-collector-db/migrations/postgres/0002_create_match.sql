@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS match (
+    id TEXT PRIMARY KEY NOT NULL,
+    start_time TIMESTAMP NOT NULL,
+    duration BIGINT NOT NULL,
+    queue_id BIGINT NOT NULL,
+    game_version TEXT NOT NULL,
+    game_mode TEXT NOT NULL,
+    winning_team_id BIGINT NOT NULL,
+    surrender BOOLEAN NOT NULL
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS summoner_match (
+    puuid TEXT NOT NULL REFERENCES summoner (puuid) ON DELETE CASCADE,
+    match_id TEXT NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    kills BIGINT NOT NULL,
+    deaths BIGINT NOT NULL,
+    assists BIGINT NOT NULL,
+    champion_id BIGINT NOT NULL,
+    position TEXT,
+    longest_time_living BIGINT NOT NULL,
+    time_dead BIGINT NOT NULL,
+    team_id BIGINT NOT NULL,
+    PRIMARY KEY (puuid, match_id)
+);
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS guild (
+    id BIGINT PRIMARY KEY NOT NULL,
+    channel_id BIGINT
+);
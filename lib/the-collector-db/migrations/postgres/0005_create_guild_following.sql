@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS guild_following (
+    guild_id BIGINT NOT NULL REFERENCES guild (id) ON DELETE CASCADE,
+    puuid TEXT NOT NULL REFERENCES summoner (puuid) ON DELETE CASCADE,
+    PRIMARY KEY (guild_id, puuid)
+);
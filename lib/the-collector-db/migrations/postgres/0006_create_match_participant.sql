@@ -0,0 +1,39 @@
+CREATE TABLE IF NOT EXISTS match_participant (
+    match_id TEXT NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    puuid TEXT NOT NULL,
+    participant_id BIGINT NOT NULL,
+    team_id BIGINT NOT NULL,
+    game_name TEXT,
+    tag TEXT,
+    champion_id BIGINT NOT NULL,
+    champion_level BIGINT NOT NULL,
+    position TEXT,
+    win BOOLEAN NOT NULL,
+    kills BIGINT NOT NULL,
+    deaths BIGINT NOT NULL,
+    assists BIGINT NOT NULL,
+    gold_earned BIGINT NOT NULL,
+    gold_spent BIGINT NOT NULL,
+    total_damage_dealt BIGINT NOT NULL,
+    total_damage_dealt_to_champions BIGINT NOT NULL,
+    total_damage_taken BIGINT NOT NULL,
+    damage_self_mitigated BIGINT NOT NULL,
+    vision_score BIGINT NOT NULL,
+    wards_placed BIGINT NOT NULL,
+    wards_killed BIGINT NOT NULL,
+    total_minions_killed BIGINT NOT NULL,
+    neutral_minions_killed BIGINT NOT NULL,
+    time_dead BIGINT NOT NULL,
+    item0 BIGINT NOT NULL,
+    item1 BIGINT NOT NULL,
+    item2 BIGINT NOT NULL,
+    item3 BIGINT NOT NULL,
+    item4 BIGINT NOT NULL,
+    item5 BIGINT NOT NULL,
+    item6 BIGINT NOT NULL,
+    summoner1_id BIGINT NOT NULL,
+    summoner2_id BIGINT NOT NULL,
+    PRIMARY KEY (match_id, puuid)
+);
+
+CREATE INDEX IF NOT EXISTS match_participant_puuid ON match_participant (puuid);
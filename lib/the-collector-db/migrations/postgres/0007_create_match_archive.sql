@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS match_archive (
+    match_id TEXT PRIMARY KEY NOT NULL,
+    data BYTEA NOT NULL,
+    archive_time TIMESTAMP NOT NULL
+);
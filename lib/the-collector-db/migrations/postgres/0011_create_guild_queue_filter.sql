@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS guild_queue_filter (
+    guild_id BIGINT NOT NULL REFERENCES guild (id) ON DELETE CASCADE,
+    queue_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, queue_id)
+);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS rank_snapshot (
+    puuid TEXT NOT NULL,
+    match_id TEXT NOT NULL,
+    queue_type TEXT NOT NULL,
+    tier TEXT NOT NULL,
+    division TEXT NOT NULL,
+    league_points BIGINT NOT NULL,
+    lp_delta BIGINT,
+    snapshot_time TIMESTAMP NOT NULL,
+    PRIMARY KEY (puuid, match_id),
+    FOREIGN KEY (puuid, match_id)
+        REFERENCES summoner_match (puuid, match_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS rank_snapshot_queue ON rank_snapshot (puuid, queue_type, snapshot_time);
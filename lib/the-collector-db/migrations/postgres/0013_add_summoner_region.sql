@@ -0,0 +1 @@
+ALTER TABLE summoner ADD COLUMN region TEXT NOT NULL DEFAULT 'NA1';
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS job (
+    kind TEXT NOT NULL,
+    match_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts BIGINT NOT NULL,
+    create_time TIMESTAMP NOT NULL,
+    update_time TIMESTAMP NOT NULL,
+    PRIMARY KEY (kind, match_id)
+);
+
+CREATE INDEX IF NOT EXISTS job_status ON job (status);
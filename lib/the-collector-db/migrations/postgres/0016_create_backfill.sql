@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS backfill (
+    puuid TEXT PRIMARY KEY NOT NULL REFERENCES summoner (puuid) ON DELETE CASCADE,
+    guild_id BIGINT REFERENCES guild (id) ON DELETE SET NULL,
+    status TEXT NOT NULL,
+    matches BIGINT NOT NULL,
+    create_time TIMESTAMP NOT NULL,
+    update_time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS backfill_status ON backfill (status);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS league_job (
+    puuid TEXT NOT NULL,
+    match_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts BIGINT NOT NULL,
+    create_time TIMESTAMP NOT NULL,
+    update_time TIMESTAMP NOT NULL,
+    PRIMARY KEY (puuid, match_id)
+);
+
+CREATE INDEX IF NOT EXISTS league_job_status ON league_job (status);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS match (
+    id TEXT PRIMARY KEY NOT NULL,
+    start_time DATETIME NOT NULL,
+    duration INTEGER NOT NULL,
+    queue_id INTEGER NOT NULL,
+    game_version TEXT NOT NULL,
+    game_mode TEXT NOT NULL,
+    winning_team_id INTEGER NOT NULL,
+    surrender BOOLEAN NOT NULL
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS summoner_match (
+    puuid TEXT NOT NULL REFERENCES summoner (puuid) ON DELETE CASCADE,
+    match_id TEXT NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    kills INTEGER NOT NULL,
+    deaths INTEGER NOT NULL,
+    assists INTEGER NOT NULL,
+    champion_id INTEGER NOT NULL,
+    position TEXT,
+    longest_time_living INTEGER NOT NULL,
+    time_dead INTEGER NOT NULL,
+    team_id INTEGER NOT NULL,
+    PRIMARY KEY (puuid, match_id)
+);
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS guild (
+    id INTEGER PRIMARY KEY NOT NULL,
+    channel_id INTEGER
+);
@@ -0,0 +1,39 @@
+CREATE TABLE IF NOT EXISTS match_participant (
+    match_id TEXT NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    puuid TEXT NOT NULL,
+    participant_id INTEGER NOT NULL,
+    team_id INTEGER NOT NULL,
+    game_name TEXT,
+    tag TEXT,
+    champion_id INTEGER NOT NULL,
+    champion_level INTEGER NOT NULL,
+    position TEXT,
+    win BOOLEAN NOT NULL,
+    kills INTEGER NOT NULL,
+    deaths INTEGER NOT NULL,
+    assists INTEGER NOT NULL,
+    gold_earned INTEGER NOT NULL,
+    gold_spent INTEGER NOT NULL,
+    total_damage_dealt INTEGER NOT NULL,
+    total_damage_dealt_to_champions INTEGER NOT NULL,
+    total_damage_taken INTEGER NOT NULL,
+    damage_self_mitigated INTEGER NOT NULL,
+    vision_score INTEGER NOT NULL,
+    wards_placed INTEGER NOT NULL,
+    wards_killed INTEGER NOT NULL,
+    total_minions_killed INTEGER NOT NULL,
+    neutral_minions_killed INTEGER NOT NULL,
+    time_dead INTEGER NOT NULL,
+    item0 INTEGER NOT NULL,
+    item1 INTEGER NOT NULL,
+    item2 INTEGER NOT NULL,
+    item3 INTEGER NOT NULL,
+    item4 INTEGER NOT NULL,
+    item5 INTEGER NOT NULL,
+    item6 INTEGER NOT NULL,
+    summoner1_id INTEGER NOT NULL,
+    summoner2_id INTEGER NOT NULL,
+    PRIMARY KEY (match_id, puuid)
+);
+
+CREATE INDEX IF NOT EXISTS match_participant_puuid ON match_participant (puuid);
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS death_event (
+    match_id TEXT NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    puuid TEXT NOT NULL REFERENCES summoner (puuid) ON DELETE CASCADE,
+    timestamp INTEGER NOT NULL,
+    killer_puuid TEXT,
+    position_x INTEGER,
+    position_y INTEGER,
+    PRIMARY KEY (match_id, puuid, timestamp)
+);
+
+CREATE TABLE IF NOT EXISTS death_event_assist (
+    match_id TEXT NOT NULL,
+    puuid TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    assister_puuid TEXT NOT NULL,
+    PRIMARY KEY (match_id, puuid, timestamp, assister_puuid),
+    FOREIGN KEY (match_id, puuid, timestamp)
+        REFERENCES death_event (match_id, puuid, timestamp) ON DELETE CASCADE
+);
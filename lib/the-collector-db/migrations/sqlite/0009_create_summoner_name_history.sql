@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS summoner_name_history (
+    puuid TEXT NOT NULL REFERENCES summoner (puuid) ON DELETE CASCADE,
+    game_name TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    change_time DATETIME NOT NULL,
+    PRIMARY KEY (puuid, game_name, tag)
+);
+
+CREATE INDEX IF NOT EXISTS summoner_name_history_name ON summoner_name_history (game_name, tag);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS notification (
+    puuid TEXT NOT NULL REFERENCES summoner (puuid) ON DELETE CASCADE,
+    match_id TEXT NOT NULL REFERENCES match (id) ON DELETE CASCADE,
+    guild_id INTEGER NOT NULL REFERENCES guild (id) ON DELETE CASCADE,
+    level TEXT NOT NULL,
+    message TEXT NOT NULL,
+    message_id INTEGER,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    update_time DATETIME NOT NULL,
+    PRIMARY KEY (puuid, match_id, guild_id)
+);
+
+CREATE INDEX IF NOT EXISTS notification_status ON notification (status);
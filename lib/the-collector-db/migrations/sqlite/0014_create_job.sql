@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS job (
+    kind TEXT NOT NULL,
+    match_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    create_time DATETIME NOT NULL,
+    update_time DATETIME NOT NULL,
+    PRIMARY KEY (kind, match_id)
+);
+
+CREATE INDEX IF NOT EXISTS job_status ON job (status);
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS dead_letter (
+    kind TEXT NOT NULL,
+    key TEXT NOT NULL,
+    error TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    requeued BOOLEAN NOT NULL,
+    create_time DATETIME NOT NULL,
+    update_time DATETIME NOT NULL,
+    PRIMARY KEY (kind, key)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS league_job (
+    puuid TEXT NOT NULL,
+    match_id TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    create_time DATETIME NOT NULL,
+    update_time DATETIME NOT NULL,
+    PRIMARY KEY (puuid, match_id)
+);
+
+CREATE INDEX IF NOT EXISTS league_job_status ON league_job (status);
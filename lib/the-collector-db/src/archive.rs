@@ -0,0 +1,25 @@
+//! Encoding of raw match-v5 payloads for the match archive.
+//!
+//! Payloads are stored as gzip compressed JSON, exactly as returned by the API, so
+//! that they remain readable by other tools and keep fields that riven does not
+//! model. Their rows can then be re-derived after the API no longer serves the match.
+
+use crate::error::Error;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Read, Write};
+
+/// Compress a match payload, leaving its bytes unchanged.
+pub(crate) fn encode(payload: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(payload)?;
+    Ok(encoder.finish()?)
+}
+
+/// Decompress a match payload produced by [`encode`].
+pub(crate) fn decode(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut payload = Vec::new();
+    GzDecoder::new(bytes).read_to_end(&mut payload)?;
+    Ok(payload)
+}
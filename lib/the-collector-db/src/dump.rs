@@ -0,0 +1,203 @@
+//! Portable dumps of the core tables, for backing up, moving or analysing data outside
+//! of the database.
+//!
+//! A dump is a directory with one file per table (e.g. `summoner.jsonl`), in either
+//! format. Rows are written in primary key order, so dumps of the same data are equal.
+
+use crate::error::Error;
+use crate::model::{Guild, GuildFollowing, Match, Summoner, SummonerMatch};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// File format of the tables of a dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// One JSON object per line.
+    JsonLines,
+    /// Comma separated values with a header row.
+    Csv,
+}
+
+impl DumpFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::JsonLines => "jsonl",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+impl FromStr for DumpFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jsonl" => Ok(Self::JsonLines),
+            "csv" => Ok(Self::Csv),
+            _ => Err(Error::InvalidDump(format!("unknown format {s:?}"))),
+        }
+    }
+}
+
+/// The rows of every table included in a dump.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Dump {
+    pub guilds: Vec<Guild>,
+    pub summoners: Vec<Summoner>,
+    pub matches: Vec<Match>,
+    pub summoner_matches: Vec<SummonerMatch>,
+    pub guild_followings: Vec<GuildFollowing>,
+}
+
+impl Dump {
+    /// Write each table to a file in the directory, creating it if needed and
+    /// replacing any previous dump in the same format.
+    pub fn write(&self, dir: impl AsRef<Path>, format: DumpFormat) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        write_rows(&table_path(dir, "guild", format), &self.guilds, format)?;
+        write_rows(
+            &table_path(dir, "summoner", format),
+            &self.summoners,
+            format,
+        )?;
+        write_rows(&table_path(dir, "match", format), &self.matches, format)?;
+        write_rows(
+            &table_path(dir, "summoner_match", format),
+            &self.summoner_matches,
+            format,
+        )?;
+        write_rows(
+            &table_path(dir, "guild_following", format),
+            &self.guild_followings,
+            format,
+        )
+    }
+
+    /// Read each table from its file in the directory.
+    pub fn read(dir: impl AsRef<Path>, format: DumpFormat) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        Ok(Self {
+            guilds: read_rows(&table_path(dir, "guild", format), format)?,
+            summoners: read_rows(&table_path(dir, "summoner", format), format)?,
+            matches: read_rows(&table_path(dir, "match", format), format)?,
+            summoner_matches: read_rows(&table_path(dir, "summoner_match", format), format)?,
+            guild_followings: read_rows(&table_path(dir, "guild_following", format), format)?,
+        })
+    }
+
+    /// Check that no primary key appears twice, and that every row only refers to rows
+    /// included in the dump.
+    pub fn validate(&self) -> Result<(), Error> {
+        let guild_ids = unique_keys("guild", self.guilds.iter().map(|guild| guild.id))?;
+        let puuids = unique_keys(
+            "summoner",
+            self.summoners
+                .iter()
+                .map(|summoner| summoner.puuid.as_str()),
+        )?;
+        let match_ids = unique_keys("match", self.matches.iter().map(|data| data.id.as_str()))?;
+        unique_keys(
+            "summoner_match",
+            self.summoner_matches
+                .iter()
+                .map(|row| (row.puuid.as_str(), row.match_id.as_str())),
+        )?;
+        unique_keys(
+            "guild_following",
+            self.guild_followings
+                .iter()
+                .map(|row| (row.guild_id, row.puuid.as_str())),
+        )?;
+
+        for row in &self.summoner_matches {
+            if !puuids.contains(row.puuid.as_str()) {
+                return Err(missing_reference("summoner_match", "summoner", &row.puuid));
+            }
+            if !match_ids.contains(row.match_id.as_str()) {
+                return Err(missing_reference("summoner_match", "match", &row.match_id));
+            }
+        }
+        for row in &self.guild_followings {
+            if !guild_ids.contains(&row.guild_id) {
+                return Err(missing_reference("guild_following", "guild", row.guild_id));
+            }
+            if !puuids.contains(row.puuid.as_str()) {
+                return Err(missing_reference("guild_following", "summoner", &row.puuid));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn table_path(dir: &Path, table: &str, format: DumpFormat) -> std::path::PathBuf {
+    dir.join(format!("{table}.{}", format.extension()))
+}
+
+fn write_rows<T: Serialize>(path: &Path, rows: &[T], format: DumpFormat) -> Result<(), Error> {
+    match format {
+        DumpFormat::JsonLines => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            for row in rows {
+                serde_json::to_writer(&mut writer, row)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        }
+        DumpFormat::Csv => {
+            let mut writer = csv::Writer::from_path(path)?;
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn read_rows<T: DeserializeOwned>(path: &Path, format: DumpFormat) -> Result<Vec<T>, Error> {
+    match format {
+        DumpFormat::JsonLines => {
+            let mut rows = Vec::new();
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    rows.push(serde_json::from_str(&line)?);
+                }
+            }
+            Ok(rows)
+        }
+        DumpFormat::Csv => Ok(csv::Reader::from_path(path)?
+            .deserialize()
+            .collect::<Result<_, _>>()?),
+    }
+}
+
+/// Collect the primary keys of a table, failing if any appears more than once.
+fn unique_keys<K: Eq + Hash + std::fmt::Debug>(
+    table: &str,
+    keys: impl Iterator<Item = K>,
+) -> Result<HashSet<K>, Error> {
+    let mut unique = HashSet::new();
+    for key in keys {
+        if unique.contains(&key) {
+            return Err(Error::InvalidDump(format!(
+                "{table} {key:?} appears more than once"
+            )));
+        }
+        unique.insert(key);
+    }
+    Ok(unique)
+}
+
+fn missing_reference(table: &str, referenced: &str, key: impl std::fmt::Debug) -> Error {
+    Error::InvalidDump(format!(
+        "{table} refers to {referenced} {key:?}, which is not in the dump"
+    ))
+}
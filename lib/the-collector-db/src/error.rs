@@ -5,11 +5,23 @@ pub enum Error {
     #[error(transparent)]
     SqlxError(#[from] sqlx::Error),
     #[error(transparent)]
+    MigrateError(#[from] sqlx::migrate::MigrateError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    CsvError(#[from] csv::Error),
+    #[error(transparent)]
     ParseChampionError(#[from] riven::consts::ParseChampionError),
     #[error("missing data: {0} is not available")]
     MissingData(String),
     #[error("not enough matches for leaderboard")]
     NotEnoughLeaderboardMatches,
+    #[error("unsupported database: {0:?}")]
+    UnsupportedDatabase(String),
     #[error("time is out of range")]
     DateTimeOutOfRange,
+    #[error("invalid dump: {0}")]
+    InvalidDump(String),
 }
@@ -0,0 +1,267 @@
+//! Filters and ordering of guild leaderboards.
+
+use crate::push_in;
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
+use sqlx::{Database, Encode, QueryBuilder, Type};
+use std::collections::BTreeMap;
+use the_collector_types::GuildId;
+
+/// Default number of entries on a leaderboard.
+const DEFAULT_SIZE: usize = 10;
+
+/// Period of time that the matches of a leaderboard must have started in.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum TimeWindow {
+    #[default]
+    AllTime,
+    /// Since the start of the current week (Monday, UTC).
+    ThisWeek,
+    /// Since the start of the current month (UTC).
+    ThisMonth,
+    /// From `start` (inclusive) until `end` (exclusive).
+    Range {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+}
+
+impl TimeWindow {
+    /// Get the start (inclusive) and end (exclusive) of the window relative to `now`, or
+    /// [`None`] if the window is unbounded.
+    pub fn bounds(&self, now: DateTime<Utc>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let today = now.date_naive();
+        let start = match self {
+            Self::AllTime => return None,
+            Self::ThisWeek => today - Days::new(today.weekday().num_days_from_monday().into()),
+            Self::ThisMonth => NaiveDate::from_ymd_opt(today.year(), today.month(), 1)?,
+            Self::Range { start, end } => return Some((*start, *end)),
+        };
+        Some((start.and_hms_opt(0, 0, 0)?.and_utc(), now))
+    }
+}
+
+/// Weights used to combine kills, deaths and assists into a single score.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KdaWeights {
+    pub kills: f64,
+    pub deaths: f64,
+    pub assists: f64,
+}
+
+/// [`KdaWeights`] for each position, so that leaderboards can weigh matches the same way
+/// the match evaluator does for notifications.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PositionKdaWeights {
+    /// Keyed by position as given by the Riot API (e.g. `"MIDDLE"`).
+    pub positions: BTreeMap<String, KdaWeights>,
+    /// Weights of matches without a position, or in a position without weights. Such
+    /// matches are left off the leaderboard if unset.
+    pub other: Option<KdaWeights>,
+}
+
+/// Metric that leaderboard entries are ranked by, worst performance first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum LeaderboardMetric {
+    /// Most deaths.
+    #[default]
+    Deaths,
+    /// Lowest weighted KDA.
+    WeightedKda(PositionKdaWeights),
+    /// Most time spent dead.
+    TimeDead,
+    /// Most deaths per minute played.
+    DeathsPerMinute,
+}
+
+/// Query for the summoner matches on a guild's leaderboard, built up from the
+/// defaults of [`LeaderboardQuery::new`]:
+///
+/// ```
+/// use the_collector_db::leaderboard::{LeaderboardMetric, LeaderboardQuery, TimeWindow};
+///
+/// let query = LeaderboardQuery::new(1234)
+///     .size(5)
+///     .window(TimeWindow::ThisWeek)
+///     .metric(LeaderboardMetric::TimeDead);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardQuery {
+    pub guild_id: GuildId,
+    pub size: usize,
+    pub window: TimeWindow,
+    /// [`None`] to use the guild's queues (see [`Storage::get_guild_queues`]).
+    ///
+    /// [`Storage::get_guild_queues`]: crate::Storage::get_guild_queues
+    pub queue_ids: Option<Vec<u16>>,
+    /// Position as given by the Riot API (e.g. `"MIDDLE"`).
+    pub position: Option<String>,
+    pub champion_id: Option<i16>,
+    pub metric: LeaderboardMetric,
+}
+
+impl LeaderboardQuery {
+    /// Create a query for the top 10 summoner matches with the most deaths of all time,
+    /// in the queues the guild counts.
+    pub fn new(guild_id: impl Into<GuildId>) -> Self {
+        Self {
+            guild_id: guild_id.into(),
+            size: DEFAULT_SIZE,
+            window: TimeWindow::default(),
+            queue_ids: None,
+            position: None,
+            champion_id: None,
+            metric: LeaderboardMetric::default(),
+        }
+    }
+
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn window(mut self, window: TimeWindow) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn queues(mut self, queue_ids: impl Into<Vec<u16>>) -> Self {
+        self.queue_ids = Some(queue_ids.into());
+        self
+    }
+
+    pub fn position(mut self, position: impl Into<String>) -> Self {
+        self.position = Some(position.into());
+        self
+    }
+
+    pub fn champion(mut self, champion_id: i16) -> Self {
+        self.champion_id = Some(champion_id);
+        self
+    }
+
+    pub fn metric(mut self, metric: LeaderboardMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    /// Build the SQL selecting the rows of the leaderboard, joined with their summoner
+    /// and match. Only standard SQL is used, so that the same query works for every
+    /// database.
+    ///
+    /// `queue_ids` are the queues to use if the query does not specify any.
+    pub(crate) fn build<'a, DB: Database>(
+        &'a self,
+        now: DateTime<Utc>,
+        queue_ids: &'a [u16],
+    ) -> QueryBuilder<'a, DB>
+    where
+        i64: Encode<'a, DB> + Type<DB>,
+        f64: Encode<'a, DB> + Type<DB>,
+        GuildId: Encode<'a, DB> + Type<DB>,
+        DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+        &'a str: Encode<'a, DB> + Type<DB>,
+    {
+        let mut builder = QueryBuilder::new(
+            "SELECT
+                summoner_match.puuid, summoner_match.match_id, summoner.game_name, summoner.tag,
+                summoner_match.kills, summoner_match.deaths, summoner_match.assists,
+                summoner_match.champion_id, summoner_match.position, summoner_match.time_dead,
+                match.start_time, match.duration, match.queue_id, match.game_version
+            FROM summoner_match
+            INNER JOIN summoner ON summoner.puuid = summoner_match.puuid
+            INNER JOIN guild_following ON guild_following.puuid = summoner_match.puuid
+            INNER JOIN match ON summoner_match.match_id = match.id
+            WHERE guild_following.guild_id = ",
+        );
+        builder.push_bind(self.guild_id);
+
+        let queue_ids = self.queue_ids.as_deref().unwrap_or(queue_ids);
+        builder.push(" AND ");
+        push_in(
+            &mut builder,
+            "match.queue_id",
+            queue_ids.iter().map(|queue_id| i64::from(*queue_id)),
+        );
+
+        if let Some((start, end)) = self.window.bounds(now) {
+            builder.push(" AND match.start_time >= ");
+            builder.push_bind(start);
+            builder.push(" AND match.start_time < ");
+            builder.push_bind(end);
+        }
+        if let Some(position) = &self.position {
+            builder.push(" AND summoner_match.position = ");
+            builder.push_bind(position.as_str());
+        }
+        if let Some(champion_id) = self.champion_id {
+            builder.push(" AND summoner_match.champion_id = ");
+            builder.push_bind(i64::from(champion_id));
+        }
+        if let LeaderboardMetric::WeightedKda(PositionKdaWeights {
+            positions,
+            other: None,
+        }) = &self.metric
+        {
+            builder.push(" AND ");
+            push_in(
+                &mut builder,
+                "summoner_match.position",
+                positions.keys().map(String::as_str),
+            );
+        }
+
+        builder.push(" ORDER BY ");
+        match &self.metric {
+            LeaderboardMetric::Deaths => {
+                builder.push("summoner_match.deaths DESC");
+            }
+            LeaderboardMetric::WeightedKda(weights) if weights.positions.is_empty() => {
+                // A CASE needs at least one WHEN, and without any weights nothing is left
+                match &weights.other {
+                    Some(other_weights) => push_weighted_kda(&mut builder, other_weights),
+                    None => {
+                        builder.push("0");
+                    }
+                }
+                builder.push(" ASC");
+            }
+            LeaderboardMetric::WeightedKda(weights) => {
+                builder.push("CASE");
+                for (position, position_weights) in &weights.positions {
+                    builder.push(" WHEN summoner_match.position = ");
+                    builder.push_bind(position.as_str());
+                    builder.push(" THEN ");
+                    push_weighted_kda(&mut builder, position_weights);
+                }
+                if let Some(other_weights) = &weights.other {
+                    builder.push(" ELSE ");
+                    push_weighted_kda(&mut builder, other_weights);
+                }
+                builder.push(" END ASC");
+            }
+            LeaderboardMetric::TimeDead => {
+                builder.push("summoner_match.time_dead DESC");
+            }
+            LeaderboardMetric::DeathsPerMinute => {
+                builder.push("summoner_match.deaths * 60.0 / match.duration DESC");
+            }
+        }
+        // Break ties in favour of the most recent match
+        builder.push(", match.start_time DESC LIMIT ");
+        builder.push_bind(self.size as i64);
+        builder
+    }
+}
+
+/// Push the weighted sum of a summoner match's kills, deaths and assists.
+fn push_weighted_kda<'a, DB: Database>(builder: &mut QueryBuilder<'a, DB>, weights: &KdaWeights)
+where
+    f64: Encode<'a, DB> + Type<DB>,
+{
+    builder.push_bind(weights.kills);
+    builder.push(" * summoner_match.kills + ");
+    builder.push_bind(weights.deaths);
+    builder.push(" * summoner_match.deaths + ");
+    builder.push_bind(weights.assists);
+    builder.push(" * summoner_match.assists");
+}
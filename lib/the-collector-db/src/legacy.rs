@@ -0,0 +1,220 @@
+//! Reading the SQLite database of the original Python int-bot, so that its history can
+//! be imported.
+//!
+//! Legacy tables are read through queries, each returning the columns of the matching
+//! record below. As the schema of legacy databases differs, the queries have to be given
+//! for each database. Columns of optional fields may be left out, in which case they are
+//! filled in from match data fetched from the Riot API.
+
+use crate::error::Error;
+use crate::{get_participant, get_surrender, get_winning_team, model};
+use chrono::DateTime;
+use riven::models::match_v5::Match;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{FromRow, SqlitePool};
+use std::str::FromStr;
+use the_collector_types::{GuildId, MatchId, Puuid};
+
+/// Queries selecting the legacy records to import.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LegacyQueries {
+    /// Selects [`LegacySummoner`] columns.
+    pub summoners: String,
+    /// Selects [`LegacyFollowing`] columns.
+    pub followings: String,
+    /// Selects [`LegacyMatch`] columns.
+    pub matches: String,
+    /// Selects [`LegacySummonerMatch`] columns.
+    pub summoner_matches: String,
+}
+
+/// A summoner identified by PUUID, or by name and tag if the PUUID was not stored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, FromRow, Serialize)]
+pub struct LegacySummoner {
+    #[sqlx(default)]
+    pub puuid: Option<Puuid>,
+    #[sqlx(default)]
+    pub game_name: Option<String>,
+    #[sqlx(default)]
+    pub tag: Option<String>,
+}
+
+/// A guild following a summoner.
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize)]
+pub struct LegacyFollowing {
+    pub guild_id: GuildId,
+    #[sqlx(flatten)]
+    pub summoner: LegacySummoner,
+}
+
+/// A match, with the columns of a [`model::Match`].
+#[derive(Debug, Clone, PartialEq, Eq, Default, FromRow, Serialize)]
+pub struct LegacyMatch {
+    /// As text, which may lack the platform prefix of match-v5 IDs.
+    pub match_id: String,
+    /// As Unix time in milliseconds.
+    #[sqlx(default)]
+    pub start_time: Option<i64>,
+    /// In seconds.
+    #[sqlx(default)]
+    pub duration: Option<i64>,
+    #[sqlx(default)]
+    pub queue_id: Option<i64>,
+    #[sqlx(default)]
+    pub game_version: Option<String>,
+    #[sqlx(default)]
+    pub game_mode: Option<String>,
+    #[sqlx(default)]
+    pub winning_team_id: Option<i64>,
+    #[sqlx(default)]
+    pub surrender: Option<bool>,
+}
+
+impl LegacyMatch {
+    /// Whether every column was selected, so that the match need not be fetched.
+    pub fn is_complete(&self) -> bool {
+        self.start_time.is_some()
+            && self.duration.is_some()
+            && self.queue_id.is_some()
+            && self.game_version.is_some()
+            && self.game_mode.is_some()
+            && self.winning_team_id.is_some()
+            && self.surrender.is_some()
+    }
+
+    /// Map the record onto a match row with the given ID, filling in missing columns from
+    /// the fetched match data. Returns [`None`] if columns are still missing.
+    pub fn to_row(&self, match_id: &MatchId, fetched: Option<&Match>) -> Option<model::Match> {
+        let start_time = self
+            .start_time
+            .or(fetched.map(|data| data.info.game_start_timestamp))?;
+        Some(model::Match {
+            id: match_id.clone(),
+            start_time: DateTime::from_timestamp_millis(start_time)?.naive_utc(),
+            duration: self
+                .duration
+                .or(fetched.map(|data| data.info.game_duration))?,
+            queue_id: self
+                .queue_id
+                .or(fetched.map(|data| u16::from(data.info.queue_id).into()))?,
+            game_version: self
+                .game_version
+                .clone()
+                .or(fetched.map(|data| data.info.game_version.clone()))?,
+            game_mode: self
+                .game_mode
+                .clone()
+                .or(fetched.map(|data| data.info.game_mode.to_string()))?,
+            winning_team_id: self.winning_team_id.or(fetched
+                .and_then(|data| get_winning_team(data).ok())
+                .map(i64::from))?,
+            surrender: self
+                .surrender
+                .or(fetched.and_then(|data| get_surrender(data).ok()))?,
+        })
+    }
+}
+
+/// The stats of a summoner in a match, with the columns of a [`model::SummonerMatch`].
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize)]
+pub struct LegacySummonerMatch {
+    /// As with [`LegacyMatch::match_id`].
+    pub match_id: String,
+    #[sqlx(flatten)]
+    pub summoner: LegacySummoner,
+    #[sqlx(default)]
+    pub kills: Option<i64>,
+    #[sqlx(default)]
+    pub deaths: Option<i64>,
+    #[sqlx(default)]
+    pub assists: Option<i64>,
+    #[sqlx(default)]
+    pub champion_id: Option<i64>,
+    /// Position as given by the Riot API (e.g. `"MIDDLE"`), which is left unset if
+    /// neither selected nor fetched.
+    #[sqlx(default)]
+    pub position: Option<String>,
+    /// In seconds.
+    #[sqlx(default)]
+    pub longest_time_living: Option<i64>,
+    /// In seconds.
+    #[sqlx(default)]
+    pub time_dead: Option<i64>,
+    #[sqlx(default)]
+    pub team_id: Option<i64>,
+}
+
+impl LegacySummonerMatch {
+    /// Whether every required column was selected, so that the match need not be fetched.
+    pub fn is_complete(&self) -> bool {
+        self.kills.is_some()
+            && self.deaths.is_some()
+            && self.assists.is_some()
+            && self.champion_id.is_some()
+            && self.longest_time_living.is_some()
+            && self.time_dead.is_some()
+            && self.team_id.is_some()
+    }
+
+    /// Map the record onto a summoner match row for the given PUUID and match ID, filling
+    /// in missing columns from the summoner's stats in the fetched match data. Returns
+    /// [`None`] if required columns are still missing.
+    pub fn to_row(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        fetched: Option<&Match>,
+    ) -> Option<model::SummonerMatch> {
+        let stats = fetched.and_then(|data| get_participant(data, puuid).ok());
+        Some(model::SummonerMatch {
+            puuid: puuid.clone(),
+            match_id: match_id.clone(),
+            kills: self.kills.or(stats.map(|stats| stats.kills.into()))?,
+            deaths: self.deaths.or(stats.map(|stats| stats.deaths.into()))?,
+            assists: self.assists.or(stats.map(|stats| stats.assists.into()))?,
+            champion_id: self.champion_id.or(stats
+                .and_then(|stats| stats.champion().ok())
+                .map(|champion| i16::from(champion).into()))?,
+            position: self
+                .position
+                .clone()
+                .or(stats.map(|stats| stats.team_position.clone())),
+            longest_time_living: self
+                .longest_time_living
+                .or(stats.map(|stats| stats.longest_time_spent_living.into()))?,
+            time_dead: self
+                .time_dead
+                .or(stats.map(|stats| stats.total_time_spent_dead.into()))?,
+            team_id: self
+                .team_id
+                .or(stats.map(|stats| u16::from(stats.team_id).into()))?,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LegacyData {
+    pub summoners: Vec<LegacySummoner>,
+    pub followings: Vec<LegacyFollowing>,
+    pub matches: Vec<LegacyMatch>,
+    pub summoner_matches: Vec<LegacySummonerMatch>,
+}
+
+/// Read the records selected by the queries from the legacy database, which is opened
+/// read-only.
+pub async fn read(database_url: &str, queries: &LegacyQueries) -> Result<LegacyData, Error> {
+    let options = SqliteConnectOptions::from_str(database_url)?.read_only(true);
+    let pool = SqlitePool::connect_with(options).await?;
+
+    let data = LegacyData {
+        summoners: sqlx::query_as(&queries.summoners).fetch_all(&pool).await?,
+        followings: sqlx::query_as(&queries.followings).fetch_all(&pool).await?,
+        matches: sqlx::query_as(&queries.matches).fetch_all(&pool).await?,
+        summoner_matches: sqlx::query_as(&queries.summoner_matches)
+            .fetch_all(&pool)
+            .await?,
+    };
+    pool.close().await;
+    Ok(data)
+}
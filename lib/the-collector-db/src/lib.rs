@@ -1,362 +1,637 @@
+use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use dump::Dump;
 use error::Error;
+use leaderboard::LeaderboardQuery;
+use retention::{PruneReport, RetentionPolicy};
+use riven::consts::PlatformRoute;
 use riven::models::account_v1::Account;
-use riven::models::match_v5::Match;
-use sqlx::sqlite::SqliteQueryResult;
-use sqlx::types::chrono::{DateTime, Utc};
-use sqlx::{Pool, Sqlite};
+use riven::models::league_v4::LeagueEntry;
+use riven::models::match_v5::{Match, Participant, Timeline};
+use sqlite::SqliteStorage;
+use sqlx::{Database, Encode, QueryBuilder, Type};
+use stats::{StatsGrouping, StatsQuery};
+use std::collections::HashSet;
+use std::ops::Deref;
+use the_collector_types::{ChannelId, GuildId, MatchId, Puuid};
 
 // Re-export so that clients can avoid having sqlx as a dependency
+#[cfg(feature = "postgres")]
+pub use sqlx::postgres::PgPoolOptions;
 pub use sqlx::sqlite::SqlitePoolOptions;
 
+mod archive;
+pub mod dump;
 pub mod error;
+pub mod leaderboard;
+pub mod legacy;
 pub mod model;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod rank;
+pub mod retention;
+pub mod sqlite;
+pub mod stats;
+mod timeline;
 
-/// Draft, Ranked Solo, Ranked Flex
-const QUEUE_IDS: [u16; 3] = [400, 420, 440];
+/// Draft, Ranked Solo, Ranked Flex — the queues counted by guilds without a queue filter.
+pub const DEFAULT_QUEUE_IDS: [u16; 3] = [400, 420, 440];
 
-// TODO: Job queue for DB tasks?
-// TODO: Improve this organization
+/// Maximum number of connections kept by pools created through [`DbHandler::connect`].
+const MAX_CONNECTIONS: u32 = 5;
 
-/// Wrapper around common database operations — by using this wrapper, clients
-/// of [`DbHandler`] can remain database agnostic.
-#[derive(Debug)]
-pub struct DbHandler {
-    // TODO: Support Postgres
-    pool: Pool<Sqlite>,
-}
-
-impl DbHandler {
-    pub fn new(pool: Pool<Sqlite>) -> Self {
-        Self { pool }
-    }
+/// Common database operations, implemented once for each supported database.
+///
+/// Operations that modify data return the number of rows affected.
+#[async_trait]
+pub trait Storage: std::fmt::Debug + Send + Sync {
+    /// Apply any schema migrations that have not yet been run against the database.
+    /// Should be called once at startup, prior to any other operations.
+    async fn migrate(&self) -> Result<(), Error>;
 
     /// Get a summoner from the database given the PUUID.
-    pub async fn get_summoner(&self, puuid: &str) -> Result<Option<model::Summoner>, Error> {
-        sqlx::query_as!(
-            model::Summoner,
-            "SELECT * FROM summoner WHERE puuid = ?",
-            puuid
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
-    }
+    async fn get_summoner(&self, puuid: &Puuid) -> Result<Option<model::Summoner>, Error>;
 
     /// Get all summoners from the database.
-    pub async fn get_summoners(&self) -> Result<Vec<model::Summoner>, Error> {
-        sqlx::query_as!(model::Summoner, "SELECT * FROM summoner")
-            .fetch_all(&self.pool)
-            .await
-            .map_err(Error::SqlxError)
-    }
+    async fn get_summoners(&self) -> Result<Vec<model::Summoner>, Error>;
+
+    /// Get a summoner from the database given their name and tag. Former names are
+    /// resolved to the summoner that used them most recently, unless the name is now
+    /// used by another summoner.
+    async fn get_summoner_by_name(
+        &self,
+        name: &str,
+        tag: &str,
+    ) -> Result<Option<model::Summoner>, Error>;
+
+    /// Insert account data for a summoner playing on the given platform - does not return
+    /// an error if an account with the same PUUID already exists (keeping the original,
+    /// including its platform). Returns 0 if the summoner already existed.
+    async fn insert_summoner(&self, account: &Account, region: PlatformRoute)
+        -> Result<u64, Error>;
+
+    /// Move an existing summoner to another platform, which applies to every guild
+    /// following them.
+    ///
+    /// Returns 0 if the summoner does not exist or is already on the platform.
+    async fn update_summoner_region(
+        &self,
+        puuid: &Puuid,
+        region: PlatformRoute,
+    ) -> Result<u64, Error>;
+
+    /// Update the name and tag of an existing summoner from account data, recording the
+    /// previous name in the summoner's name history if it changed.
+    ///
+    /// Returns 0 if the summoner does not exist or the name is unchanged.
+    async fn update_summoner_name(&self, account: &Account) -> Result<u64, Error>;
+
+    /// Get the former names of a summoner, most recently changed first.
+    async fn get_summoner_name_history(
+        &self,
+        puuid: &Puuid,
+    ) -> Result<Vec<model::SummonerName>, Error>;
+
+    /// Get all summoners that no guild follows.
+    async fn get_unfollowed_summoners(&self) -> Result<Vec<model::Summoner>, Error>;
+
+    /// Delete the summoners with the given PUUIDs, along with their stats, name history
+    /// and notifications.
+    async fn delete_summoners(&self, puuids: &[Puuid]) -> Result<u64, Error>;
 
     /// Get all guilds from the database.
-    pub async fn get_guilds(&self) -> Result<Vec<model::Guild>, Error> {
-        sqlx::query_as!(model::Guild, "SELECT * FROM guild")
-            .fetch_all(&self.pool)
-            .await
-            .map_err(Error::SqlxError)
-    }
+    async fn get_guilds(&self) -> Result<Vec<model::Guild>, Error>;
 
-    /// Get all matches from the database.
-    pub async fn get_matches(&self, match_ids: &[String]) -> Result<Vec<model::Match>, Error> {
-        let queue_parameters = match_ids
-            .iter()
-            .map(|_| "?")
-            .collect::<Vec<&str>>()
-            .join(", ");
-        let raw_query = format!("SELECT * FROM match WHERE id IN ({queue_parameters})");
-        let mut query = sqlx::query_as(&raw_query);
-        for match_id in match_ids {
-            query = query.bind(match_id);
-        }
-        query.fetch_all(&self.pool).await.map_err(Error::SqlxError)
-    }
+    /// Insert guild data
+    async fn insert_guild(&self, guild_id: GuildId) -> Result<u64, Error>;
 
-    /// Get all matches from the database.
-    pub async fn get_match(&self, match_id: &str) -> Result<Option<model::Match>, Error> {
-        sqlx::query_as!(model::Match, "SELECT * FROM match WHERE id = ?", match_id)
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(Error::SqlxError)
-    }
+    /// Delete guild data
+    async fn delete_guild(&self, guild_id: GuildId) -> Result<u64, Error>;
+
+    /// Set the notification channel of a guild, or unset it if [`None`].
+    async fn update_channel(
+        &self,
+        guild_id: GuildId,
+        channel_id: Option<ChannelId>,
+    ) -> Result<u64, Error>;
+
+    /// Unset the notification channel of any guild using the given channel.
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<u64, Error>;
 
     /// Get guild followings that match the provided guild ID.
-    pub async fn get_guild_follows(
+    async fn get_guild_follows(
         &self,
-        guild_id: u64,
-    ) -> Result<Vec<model::GuildFollowing>, Error> {
-        let guild_id = guild_id as i64;
-        sqlx::query_as!(
-            model::GuildFollowing,
-            "SELECT * FROM guild_following WHERE guild_id = ?",
-            guild_id,
-        )
-        .fetch_all(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
-    }
+        guild_id: GuildId,
+    ) -> Result<Vec<model::GuildFollowing>, Error>;
 
-    /// Get the latest match information and respective stats of a PUUID.
-    pub async fn get_summoner_latest_match(
+    /// Get the summoners that a guild follows, ordered by name.
+    async fn get_followed_summoners(
         &self,
-        puuid: &str,
-    ) -> Result<Option<model::Match>, Error> {
-        sqlx::query_as!(model::Match,
-            "SELECT match.* FROM match INNER JOIN summoner_match ON match.id = summoner_match.match_id
-            WHERE summoner_match.puuid = ? ORDER BY start_time DESC LIMIT 1", puuid
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
-    }
+        guild_id: GuildId,
+    ) -> Result<Vec<model::Summoner>, Error>;
+
+    /// Get the guilds that follow a PUUID.
+    async fn get_following_guilds(&self, puuid: &Puuid) -> Result<Vec<model::Guild>, Error>;
+
+    /// Insert a guild following of a PUUID.
+    async fn insert_guild_following(&self, guild_id: GuildId, puuid: &Puuid) -> Result<u64, Error>;
+
+    /// Delete a guild following of a PUUID.
+    async fn delete_guild_following(&self, guild_id: GuildId, puuid: &Puuid) -> Result<u64, Error>;
+
+    /// Record a pending notification of a summoner match for a guild, unless one has
+    /// already been recorded. Returns 0 if the guild has already been notified (or is
+    /// being notified), in which case the notification should not be sent.
+    async fn insert_notification(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        guild_id: GuildId,
+        level: &str,
+        message: &str,
+    ) -> Result<u64, Error>;
+
+    /// Record the outcome of an attempt to send a notification, along with the ID of the
+    /// sent message if successful.
+    async fn update_notification(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        guild_id: GuildId,
+        status: model::NotificationStatus,
+        message_id: Option<u64>,
+    ) -> Result<u64, Error>;
+
+    /// Get all notifications with the given status, least recently updated first.
+    async fn get_notifications(
+        &self,
+        status: model::NotificationStatus,
+    ) -> Result<Vec<model::Notification>, Error>;
+
+    /// Queue a job for a match, unless one is already pending or in flight. A failed or
+    /// done job is queued again. Returns 0 if the job was not queued, in which case the
+    /// work should not be started.
+    async fn insert_job(&self, kind: model::JobKind, match_id: &MatchId) -> Result<u64, Error>;
+
+    /// Set the status of a job, counting an attempt if it is now in flight.
+    ///
+    /// Returns 0 if the job does not exist.
+    async fn update_job(
+        &self,
+        kind: model::JobKind,
+        match_id: &MatchId,
+        status: model::JobStatus,
+    ) -> Result<u64, Error>;
+
+    /// Get all jobs with the given status, oldest first.
+    async fn get_jobs(&self, status: model::JobStatus) -> Result<Vec<model::Job>, Error>;
+
+    /// Delete all jobs with the given status, including league jobs.
+    async fn delete_jobs(&self, status: model::JobStatus) -> Result<u64, Error>;
+
+    /// Queue a league job for a summoner's match, as with [`Self::insert_job`].
+    async fn insert_league_job(&self, puuid: &Puuid, match_id: &MatchId) -> Result<u64, Error>;
+
+    /// Set the status of a league job, as with [`Self::update_job`].
+    async fn update_league_job(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        status: model::JobStatus,
+    ) -> Result<u64, Error>;
+
+    /// Get all league jobs with the given status, oldest first.
+    async fn get_league_jobs(
+        &self,
+        status: model::JobStatus,
+    ) -> Result<Vec<model::LeagueJob>, Error>;
+
+    /// Request a backfill of a summoner's match history on behalf of the guild that
+    /// followed them, unless the summoner already has one, so that each summoner's
+    /// history is only backfilled once.
+    async fn insert_backfill(&self, puuid: &Puuid, guild_id: GuildId) -> Result<u64, Error>;
+
+    /// Set the status of a backfill, along with the number of match IDs found so far.
+    ///
+    /// Returns 0 if the backfill does not exist.
+    async fn update_backfill(
+        &self,
+        puuid: &Puuid,
+        status: model::BackfillStatus,
+        matches: i64,
+    ) -> Result<u64, Error>;
+
+    /// Get all backfills with the given status, oldest first.
+    async fn get_backfills(
+        &self,
+        status: model::BackfillStatus,
+    ) -> Result<Vec<model::Backfill>, Error>;
+
+    /// Record a request that exhausted its retries, replacing any previous dead letter of
+    /// the same request.
+    async fn insert_dead_letter(
+        &self,
+        kind: model::DeadLetterKind,
+        key: &str,
+        error: &str,
+        attempts: i64,
+    ) -> Result<u64, Error>;
+
+    /// Get all dead letters, least recently updated first.
+    async fn get_dead_letters(&self) -> Result<Vec<model::DeadLetter>, Error>;
+
+    /// Mark the dead letters of a kind as requeued, or of every kind if [`None`], so
+    /// that the collector makes their requests again.
+    async fn requeue_dead_letters(&self, kind: Option<model::DeadLetterKind>)
+        -> Result<u64, Error>;
+
+    /// Delete and return the dead letters marked as requeued.
+    async fn take_requeued_dead_letters(&self) -> Result<Vec<model::DeadLetter>, Error>;
+
+    /// Get the IDs of the queues whose matches a guild counts, which are the
+    /// [`DEFAULT_QUEUE_IDS`] unless the guild has set its own.
+    async fn get_guild_queues(&self, guild_id: GuildId) -> Result<Vec<u16>, Error>;
+
+    /// Set the IDs of the queues whose matches a guild counts, replacing any previously
+    /// set. Setting no queues restores the [`DEFAULT_QUEUE_IDS`].
+    async fn set_guild_queues(&self, guild_id: GuildId, queue_ids: &[u16]) -> Result<u64, Error>;
+
+    /// Get all matches from the database with the given IDs.
+    async fn get_matches(&self, match_ids: &[MatchId]) -> Result<Vec<model::Match>, Error>;
+
+    /// Get a match from the database given the match ID.
+    async fn get_match(&self, match_id: &MatchId) -> Result<Option<model::Match>, Error>;
 
     /// Get the latest match information and respective stats of a PUUID.
-    pub async fn get_following_guilds(&self, puuid: &str) -> Result<Vec<model::Guild>, Error> {
-        sqlx::query_as!(model::Guild,
-            "SELECT guild.* FROM guild_following INNER JOIN guild ON guild.id = guild_following.guild_id WHERE guild_following.puuid = ?", puuid
-        )
-        .fetch_all(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
-    }
+    async fn get_summoner_latest_match(&self, puuid: &Puuid)
+        -> Result<Option<model::Match>, Error>;
 
-    /// Insert account data - does not return an error if an account with the same PUUID
-    /// already exists (keeping the original).
-    pub async fn insert_summoner(&self, account: &Account) -> Result<SqliteQueryResult, Error> {
-        let now = Utc::now().naive_utc();
-        let game_name = account
-            .game_name
-            .as_ref()
-            .ok_or(Error::MissingData("Game Name".into()))?;
-        let tag = &account
-            .tag_line
-            .as_ref()
-            .ok_or(Error::MissingData("Tag".into()))?;
-        sqlx::query!(
-            "INSERT OR IGNORE
-            INTO summoner (puuid, game_name, tag, create_time)
-            VALUES (?, ?, ?, ?)",
-            account.puuid,
-            game_name,
-            tag,
-            now
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
-    }
+    /// Get the IDs of matches that started before the given time and that no guild has
+    /// been notified of, oldest first.
+    async fn get_prunable_match_ids(
+        &self,
+        started_before: DateTime<Utc>,
+    ) -> Result<Vec<MatchId>, Error>;
 
-    /// Insert guild data
-    pub async fn insert_guild(&self, guild_id: u64) -> Result<SqliteQueryResult, Error> {
-        let guild_id = guild_id as i64;
-        sqlx::query!("INSERT OR IGNORE INTO guild (id) VALUES (?)", guild_id)
-            .execute(&self.pool)
-            .await
-            .map_err(Error::SqlxError)
+    /// Delete the matches with the given IDs, along with all of their stats, death
+    /// events and archived payloads.
+    async fn delete_matches(&self, match_ids: &[MatchId]) -> Result<u64, Error>;
+
+    /// Delete summoners and matches as with [`Self::delete_summoners`] and
+    /// [`Self::delete_matches`], in a single transaction.
+    async fn delete_pruned(&self, puuids: &[Puuid], match_ids: &[MatchId]) -> Result<(), Error>;
+
+    /// Insert or update match data, the stats of every participant, and the stats of
+    /// each tracked PUUID that participated, in a single transaction. Either all of the
+    /// data is written or none of it is, and ingesting the same match again is safe.
+    ///
+    /// Returns the number of summoner match rows written.
+    async fn ingest_match(&self, data: &Match, tracked_puuids: &[Puuid]) -> Result<u64, Error>;
+
+    /// Store the full payload of a match in the archive, replacing any previously
+    /// archived payload, so that its rows can later be rebuilt by
+    /// [`DbHandler::reprocess_match`] without fetching it again. The payload is the
+    /// match-v5 response body, which is stored unchanged.
+    async fn archive_match(&self, match_id: &MatchId, payload: &[u8]) -> Result<u64, Error>;
+
+    /// Get the archived payload of a match given the match ID, as it was archived.
+    async fn get_archived_payload(&self, match_id: &MatchId) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Get the IDs of all archived matches, oldest archived first.
+    async fn get_archived_match_ids(&self) -> Result<Vec<MatchId>, Error>;
+
+    /// Get the stats of every participant of a match, ordered by participant ID.
+    async fn get_match_participants(
+        &self,
+        match_id: &MatchId,
+    ) -> Result<Vec<model::MatchParticipant>, Error>;
+
+    /// Get the stats of the participant that played the same position as a PUUID on
+    /// the opposing team of a match.
+    async fn get_lane_opponent(
+        &self,
+        match_id: &MatchId,
+        puuid: &Puuid,
+    ) -> Result<Option<model::MatchParticipant>, Error>;
+
+    /// Replace the death events of each tracked PUUID in a match with those of the
+    /// match's timeline, in a single transaction. The match itself must already exist.
+    ///
+    /// Returns the number of death events written.
+    async fn ingest_timeline(
+        &self,
+        data: &Timeline,
+        tracked_puuids: &[Puuid],
+    ) -> Result<u64, Error>;
+
+    /// Get the death events of a PUUID in a single match, in the order they happened.
+    async fn get_death_events(
+        &self,
+        match_id: &MatchId,
+        puuid: &Puuid,
+    ) -> Result<Vec<model::DeathEvent>, Error>;
+
+    /// Get the stats of a PUUID in a single match.
+    async fn get_summoner_match(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+    ) -> Result<Option<model::SummonerMatch>, Error>;
+
+    /// Record the rank of a PUUID after a match, unless one has already been recorded.
+    /// The summoner match must already exist. See [`DbHandler::record_rank`].
+    async fn insert_rank_snapshot(&self, snapshot: &model::RankSnapshot) -> Result<u64, Error>;
+
+    /// Get the rank of a PUUID recorded after a match.
+    async fn get_rank_snapshot(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+    ) -> Result<Option<model::RankSnapshot>, Error>;
+
+    /// Get the most recently recorded rank of a PUUID in a ranked queue.
+    async fn get_latest_rank_snapshot(
+        &self,
+        puuid: &Puuid,
+        queue_type: &str,
+    ) -> Result<Option<model::RankSnapshot>, Error>;
+
+    /// Get the worst summoner matches amongst the summoners a guild follows, filtered
+    /// and ranked as described by the query.
+    async fn get_leaderboard(
+        &self,
+        query: &LeaderboardQuery,
+    ) -> Result<Vec<model::LeaderboardEntry>, Error>;
+
+    /// Get every row of the tables included in a [`Dump`].
+    async fn export_dump(&self) -> Result<Dump, Error>;
+
+    /// Insert the rows of a dump in a single transaction, ignoring any whose primary
+    /// key already exists, so that importing the same dump again changes nothing. The
+    /// dump should first be validated, as done by [`DbHandler::import_dump`].
+    ///
+    /// Returns the number of rows inserted.
+    async fn insert_dump(&self, dump: &Dump) -> Result<u64, Error>;
+
+    /// Get the stats of a summoner aggregated over all of their matches matching the
+    /// query. Former names are resolved as with [`Storage::get_summoner_by_name`].
+    async fn get_summoner_stats(
+        &self,
+        query: &StatsQuery,
+    ) -> Result<Option<model::SummonerAggregateStats>, Error>;
+
+    /// Get the stats of a summoner aggregated separately for each group of their
+    /// matches matching the query, with the most deaths per game first.
+    async fn get_summoner_grouped_stats(
+        &self,
+        query: &StatsQuery,
+        grouping: StatsGrouping,
+    ) -> Result<Vec<model::SummonerGroupStats>, Error>;
+}
+
+/// Wrapper around common database operations — by using this wrapper, clients
+/// of [`DbHandler`] can remain database agnostic.
+///
+/// Operations are provided by the wrapped [`Storage`] implementation, which is
+/// accessible through [`Deref`].
+#[derive(Debug)]
+pub struct DbHandler {
+    storage: Box<dyn Storage>,
+}
+
+impl DbHandler {
+    pub fn new(storage: impl Storage + 'static) -> Self {
+        Self {
+            storage: Box::new(storage),
+        }
     }
 
-    /// Delete guild data
-    pub async fn delete_guild(&self, guild_id: u64) -> Result<SqliteQueryResult, Error> {
-        let guild_id = guild_id as i64;
-        sqlx::query!("DELETE FROM guild WHERE id = ?", guild_id)
-            .execute(&self.pool)
-            .await
-            .map_err(Error::SqlxError)
+    /// Connect to the database at the given URL, choosing the [`Storage`] implementation
+    /// based on the URL's scheme (e.g. `sqlite://` or `postgres://`).
+    pub async fn connect(database_url: &str) -> Result<Self, Error> {
+        let scheme = database_url
+            .split_once(':')
+            .map(|(scheme, _)| scheme)
+            .unwrap_or_default();
+        match scheme {
+            "sqlite" => {
+                let pool = SqlitePoolOptions::new()
+                    .max_connections(MAX_CONNECTIONS)
+                    .connect(database_url)
+                    .await?;
+                Ok(Self::new(SqliteStorage::new(pool)))
+            }
+            #[cfg(feature = "postgres")]
+            "postgres" | "postgresql" => {
+                let pool = PgPoolOptions::new()
+                    .max_connections(MAX_CONNECTIONS)
+                    .connect(database_url)
+                    .await?;
+                Ok(Self::new(postgres::PostgresStorage::new(pool)))
+            }
+            _ => Err(Error::UnsupportedDatabase(scheme.into())),
+        }
     }
 
-    /// Insert match data
-    pub async fn insert_match(&self, data: &Match) -> Result<SqliteQueryResult, Error> {
-        let winning_team_id = get_winning_team(data)?;
-        let surrender = get_surrender(data)?;
-
-        sqlx::query("INSERT INTO match (id, start_time, duration, queue_id, game_version, game_mode, winning_team_id, surrender)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)")
-            .bind(&data.metadata.match_id)
-            .bind(DateTime::from_timestamp_millis(data.info.game_start_timestamp).ok_or(Error::DateTimeOutOfRange)?)
-            .bind(data.info.game_duration)
-            .bind(u16::from(data.info.queue_id))
-            .bind(&data.info.game_version)
-            .bind(data.info.game_mode.to_string())
-            .bind(winning_team_id)
-            .bind(surrender)
-            .execute(&self.pool)
-            .await
-            .map_err(Error::SqlxError)
+    /// Get the archived payload of a match given the match ID, parsed into [`Match`] data.
+    pub async fn get_archived_match(&self, match_id: &MatchId) -> Result<Option<Match>, Error> {
+        self.get_archived_payload(match_id)
+            .await?
+            .map(|payload| serde_json::from_slice(&payload).map_err(Error::from))
+            .transpose()
     }
 
-    /// Insert summoner match data
-    pub async fn insert_summoner_match(
-        &self,
-        puuid: &str,
-        data: &Match,
-    ) -> Result<SqliteQueryResult, Error> {
-        let summoner_stats = data
-            .info
-            .participants
-            .iter()
-            .find(|p| p.puuid == puuid)
-            .ok_or(Error::MissingData("Matching PUUID".into()))?;
-
-        sqlx::query("INSERT INTO summoner_match (puuid, match_id, kills, deaths, assists, champion_id, position, longest_time_living, time_dead, team_id)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)")
-            .bind(puuid)
-            .bind(&data.metadata.match_id)
-            .bind(summoner_stats.kills)
-            .bind(summoner_stats.deaths)
-            .bind(summoner_stats.assists)
-            .bind(i16::from(summoner_stats.champion()?))
-            .bind(&summoner_stats.team_position)
-            .bind(summoner_stats.longest_time_spent_living)
-            .bind(summoner_stats.total_time_spent_dead)
-            .bind(u16::from(summoner_stats.team_id))
-            .execute(&self.pool)
-            .await
-            .map_err(Error::SqlxError)
+    /// Rebuild the rows of an archived match from its archived payload, without any
+    /// network access. Stats are written for every participant that is a known summoner.
+    ///
+    /// Returns the number of summoner match rows written.
+    pub async fn reprocess_match(&self, match_id: &MatchId) -> Result<u64, Error> {
+        let data = self
+            .get_archived_match(match_id)
+            .await?
+            .ok_or(Error::MissingData(format!("Archived match {match_id}")))?;
+        let puuids = self.summoner_puuids().await?;
+        self.ingest_archived_match(&data, &puuids).await
     }
 
-    pub async fn delete_channel(&self, channel_id: u64) -> Result<SqliteQueryResult, Error> {
-        let channel_id = channel_id as i64;
-        sqlx::query!(
-            "UPDATE guild SET channel_id = NULL WHERE channel_id = ?",
-            channel_id
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
+    /// Rebuild the rows of every archived match, as with [`Self::reprocess_match`].
+    ///
+    /// Returns the number of matches reprocessed.
+    pub async fn reprocess_archive(&self) -> Result<u64, Error> {
+        let puuids = self.summoner_puuids().await?;
+        let mut count = 0;
+        for match_id in self.get_archived_match_ids().await? {
+            if let Some(data) = self.get_archived_match(&match_id).await? {
+                self.ingest_archived_match(&data, &puuids).await?;
+                count += 1;
+            }
+        }
+        Ok(count)
     }
 
-    pub async fn update_channel(
+    /// Delete the matches and summoners that the retention policy no longer keeps, or
+    /// only report what would be deleted if `dry_run` is set.
+    ///
+    /// Besides flagged matches, the matches on each guild's leaderboards as retained by
+    /// the policy are kept; older matches that leaderboards filtered further (e.g. by
+    /// champion) could include are not. The latest match of each remaining summoner is
+    /// always kept, as polling for new matches starts from it.
+    pub async fn prune(
         &self,
-        guild_id: u64,
-        channel_id: Option<u64>,
-    ) -> Result<SqliteQueryResult, Error> {
-        let guild_id = guild_id as i64;
-        let channel_id = channel_id.map(|id| id as i64);
-        sqlx::query!(
-            "UPDATE guild SET channel_id = ? WHERE id = ?",
-            channel_id,
-            guild_id
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
+        policy: &RetentionPolicy,
+        dry_run: bool,
+    ) -> Result<PruneReport, Error> {
+        let mut report = PruneReport::default();
+        if policy.prune_unfollowed_summoners {
+            report.puuids = self
+                .get_unfollowed_summoners()
+                .await?
+                .into_iter()
+                .map(|summoner| summoner.puuid)
+                .collect();
+        }
+
+        if let Some(days) = policy.match_retention_days {
+            let mut kept_match_ids = HashSet::new();
+            for summoner in self.get_summoners().await? {
+                if report.puuids.contains(&summoner.puuid) {
+                    continue;
+                }
+                if let Some(latest_match) = self.get_summoner_latest_match(&summoner.puuid).await? {
+                    kept_match_ids.insert(latest_match.id);
+                }
+            }
+
+            for guild in self.get_guilds().await? {
+                for window in &policy.leaderboard_windows {
+                    for metric in &policy.leaderboard_metrics {
+                        let query = LeaderboardQuery::new(guild.id)
+                            .size(policy.leaderboard_size)
+                            .window(window.clone())
+                            .metric(metric.clone());
+                        let entries = self.get_leaderboard(&query).await?;
+                        kept_match_ids.extend(entries.into_iter().map(|entry| entry.match_id));
+                    }
+                }
+            }
+
+            let started_before = Utc::now() - TimeDelta::days(days as i64);
+            report.match_ids = self
+                .get_prunable_match_ids(started_before)
+                .await?
+                .into_iter()
+                .filter(|match_id| !kept_match_ids.contains(match_id))
+                .collect();
+        }
+
+        if !dry_run {
+            self.delete_pruned(&report.puuids, &report.match_ids)
+                .await?;
+        }
+        Ok(report)
     }
 
-    pub async fn get_leaderboard(
+    /// Record the rank of a PUUID in a league entry's queue after a match, along with the
+    /// LP gained or lost since the previous rank recorded in that queue. Nothing is
+    /// recorded for an unranked entry.
+    ///
+    /// Returns the number of rank snapshots written.
+    pub async fn record_rank(
         &self,
-        guild_id: u64,
-        size: usize,
-    ) -> Result<Vec<model::SummonerMatch>, Error> {
-        // TODO: Only get ints?
-        let queue_parameters = QUEUE_IDS.map(|_| "?").join(", ");
-        let raw_query = format!(
-            "SELECT summoner_match.* FROM summoner_match
-            INNER JOIN guild_following ON guild_following.puuid = summoner_match.puuid
-            INNER JOIN match ON summoner_match.match_id = match.id
-            WHERE guild_following.guild_id = ? AND match.queue_id IN ({})
-            ORDER BY deaths DESC LIMIT ?",
-            queue_parameters
-        );
-        let mut query = sqlx::query_as(&raw_query).bind(guild_id as i64);
-        for queue_id in QUEUE_IDS {
-            query = query.bind(queue_id);
+        puuid: &Puuid,
+        match_id: &MatchId,
+        entry: &LeagueEntry,
+    ) -> Result<u64, Error> {
+        let (Some(tier), Some(division)) = (entry.tier, entry.rank) else {
+            return Ok(0);
+        };
+        let queue_type: &str = entry.queue_type.clone().into();
+        let mut snapshot = model::RankSnapshot {
+            puuid: puuid.clone(),
+            match_id: match_id.clone(),
+            queue_type: queue_type.into(),
+            tier: tier.as_ref().into(),
+            division: division.as_ref().into(),
+            league_points: entry.league_points.into(),
+            lp_delta: None,
+            snapshot_time: Utc::now().naive_utc(),
+        };
+        if let Some(previous) = self.get_latest_rank_snapshot(puuid, queue_type).await? {
+            snapshot.lp_delta =
+                rank::ladder_points(&snapshot.tier, &snapshot.division, snapshot.league_points)
+                    .zip(rank::ladder_points(
+                        &previous.tier,
+                        &previous.division,
+                        previous.league_points,
+                    ))
+                    .map(|(points, previous_points)| points - previous_points);
         }
-        query
-            .bind(size as i64)
-            .fetch_all(&self.pool)
-            .await
-            .map_err(Error::SqlxError)
+        self.insert_rank_snapshot(&snapshot).await
     }
 
-    pub async fn insert_guild_following(
-        &self,
-        guild_id: u64,
-        puuid: &str,
-    ) -> Result<SqliteQueryResult, Error> {
-        let guild_id = guild_id as i64;
-        sqlx::query!(
-            "INSERT INTO guild_following (guild_id, puuid) VALUES (?, ?)",
-            guild_id,
-            puuid
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
+    /// Validate a dump, then insert its rows as with [`Storage::insert_dump`]. Nothing
+    /// is inserted if the dump is invalid.
+    ///
+    /// Returns the number of rows inserted.
+    pub async fn import_dump(&self, dump: &Dump) -> Result<u64, Error> {
+        dump.validate()?;
+        self.insert_dump(dump).await
     }
 
-    pub async fn get_summoner_match(
-        &self,
-        puuid: &str,
-        match_id: &str,
-    ) -> Result<Option<model::SummonerMatch>, Error> {
-        sqlx::query_as!(
-            model::SummonerMatch,
-            "SELECT * FROM summoner_match WHERE puuid = ? AND match_id = ?",
-            puuid,
-            match_id
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
+    async fn summoner_puuids(&self) -> Result<HashSet<Puuid>, Error> {
+        Ok(self
+            .get_summoners()
+            .await?
+            .into_iter()
+            .map(|summoner| summoner.puuid)
+            .collect())
     }
 
-    pub async fn delete_guild_following(
+    async fn ingest_archived_match(
         &self,
-        guild_id: u64,
-        puuid: &str,
-    ) -> Result<SqliteQueryResult, Error> {
-        let guild_id = guild_id as i64;
-        sqlx::query!(
-            "DELETE FROM guild_following WHERE guild_id = ? AND puuid = ?",
-            guild_id,
-            puuid
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
+        data: &Match,
+        puuids: &HashSet<Puuid>,
+    ) -> Result<u64, Error> {
+        let tracked_puuids: Vec<Puuid> = data
+            .metadata
+            .participants
+            .iter()
+            .map(|puuid| Puuid::from(puuid.as_str()))
+            .filter(|puuid| puuids.contains(puuid))
+            .collect();
+        self.ingest_match(data, &tracked_puuids).await
     }
+}
 
-    pub async fn get_summoner_by_name(
-        &self,
-        name: &str,
-        tag: &str,
-    ) -> Result<Option<model::Summoner>, Error> {
-        sqlx::query_as!(
-            model::Summoner,
-            "SELECT * FROM summoner WHERE game_name = ? AND tag = ?",
-            name,
-            tag
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
+impl Deref for DbHandler {
+    type Target = dyn Storage;
+
+    fn deref(&self) -> &Self::Target {
+        self.storage.as_ref()
     }
+}
 
-    pub async fn get_summoner_stats(
-        &self,
-        name: &str,
-        tag: &str,
-    ) -> Result<Option<model::SummonerAggregateStats>, Error> {
-        sqlx::query_as!(
-            model::SummonerAggregateStats,
-            r#"SELECT
-                game_name AS "game_name!", tag AS "tag!", COUNT(*) AS num_matches,
-                SUM(kills) AS "kills!: u16", SUM(deaths) AS "deaths!", SUM(assists) AS "assists!",
-                SUM(duration) AS "total_duration!", SUM(time_dead) AS "total_time_dead!"
-            FROM summoner_match 
-            INNER JOIN summoner ON summoner_match.puuid = summoner.puuid
-            INNER JOIN match ON summoner_match.match_id = match.id
-            WHERE game_name = ? and tag = ?
-            HAVING num_matches > 0
-            LIMIT 1"#,
-            name,
-            tag
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(Error::SqlxError)
+/// Push a condition that `column` is one of the values, each bound, or that matches
+/// nothing if there are no values, as an empty IN list is not valid SQL.
+fn push_in<'a, DB: Database, T>(
+    builder: &mut QueryBuilder<'a, DB>,
+    column: &str,
+    values: impl IntoIterator<Item = T>,
+) where
+    T: Encode<'a, DB> + Type<DB> + Send + 'a,
+{
+    let mut values = values.into_iter().peekable();
+    if values.peek().is_none() {
+        builder.push("1 = 0");
+        return;
+    }
+    builder.push(column);
+    builder.push(" IN (");
+    let mut separated = builder.separated(", ");
+    for value in values {
+        separated.push_bind(value);
     }
+    builder.push(")");
 }
 
 fn get_winning_team(data: &Match) -> Result<u16, Error> {
@@ -370,6 +645,14 @@ fn get_winning_team(data: &Match) -> Result<u16, Error> {
         .into())
 }
 
+fn get_participant<'a>(data: &'a Match, puuid: &Puuid) -> Result<&'a Participant, Error> {
+    data.info
+        .participants
+        .iter()
+        .find(|p| p.puuid == puuid.as_str())
+        .ok_or(Error::MissingData("Matching PUUID".into()))
+}
+
 fn get_surrender(data: &Match) -> Result<bool, Error> {
     Ok(data
         .info
@@ -1,23 +1,50 @@
+use riven::consts::PlatformRoute;
 use serde::{Deserialize, Serialize};
 use sqlx::{prelude::FromRow, types::chrono::NaiveDateTime};
+use the_collector_types::{ChannelId, GuildId, MatchId, Puuid};
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Guild {
-    pub id: i64,
-    pub channel_id: Option<i64>,
+    pub id: GuildId,
+    pub channel_id: Option<ChannelId>,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Summoner {
-    pub puuid: String,
+    pub puuid: Puuid,
     pub game_name: String,
     pub tag: String,
     pub create_time: NaiveDateTime,
+    /// Platform the summoner plays on, as a [`PlatformRoute`] (e.g. `"EUW1"`). Defaults
+    /// to `"NA1"` in dumps from before regions were stored.
+    #[serde(default = "default_region")]
+    pub region: String,
+}
+
+impl Summoner {
+    /// Platform the summoner plays on, or [`None`] if the stored region is not known
+    /// to riven.
+    pub fn platform(&self) -> Option<PlatformRoute> {
+        self.region.parse().ok()
+    }
+}
+
+fn default_region() -> String {
+    PlatformRoute::NA1.to_string()
+}
+
+/// A former name of a summoner, and when it stopped being used.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct SummonerName {
+    pub puuid: Puuid,
+    pub game_name: String,
+    pub tag: String,
+    pub change_time: NaiveDateTime,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Match {
-    pub id: String,
+    pub id: MatchId,
     pub start_time: NaiveDateTime,
     pub duration: i64,
     pub queue_id: i64,
@@ -29,14 +56,14 @@ pub struct Match {
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct GuildFollowing {
-    pub guild_id: i64,
-    pub puuid: String,
+    pub guild_id: GuildId,
+    pub puuid: Puuid,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct SummonerMatch {
-    pub puuid: String,
-    pub match_id: String,
+    pub puuid: Puuid,
+    pub match_id: MatchId,
     pub kills: i64,
     pub deaths: i64,
     pub assists: i64,
@@ -47,14 +74,277 @@ pub struct SummonerMatch {
     pub team_id: i64,
 }
 
+/// A summoner match on a leaderboard, along with the summoner and match data needed
+/// to display it.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub puuid: Puuid,
+    pub match_id: MatchId,
+    pub game_name: String,
+    pub tag: String,
+    pub kills: i64,
+    pub deaths: i64,
+    pub assists: i64,
+    pub champion_id: i64,
+    pub position: Option<String>,
+    pub time_dead: i64,
+    pub start_time: NaiveDateTime,
+    pub duration: i64,
+    pub queue_id: i64,
+    pub game_version: String,
+}
+
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct SummonerAggregateStats {
     pub game_name: String,
     pub tag: String,
     pub num_matches: i64,
+    pub wins: i64,
     pub kills: i64,
     pub deaths: i64,
     pub assists: i64,
     pub total_duration: i64,
     pub total_time_dead: i64,
+    /// Total LP gained or lost over the matches with a [`RankSnapshot`] delta, or
+    /// [`None`] if there are none.
+    pub lp_delta: Option<i64>,
+}
+
+/// Stats of a summoner aggregated over one group of their matches.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct SummonerGroupStats {
+    /// Value of the [`StatsGrouping`] shared by the matches, or [`None`] for matches
+    /// with no position.
+    ///
+    /// [`StatsGrouping`]: crate::stats::StatsGrouping
+    pub group_key: Option<String>,
+    pub num_matches: i64,
+    pub wins: i64,
+    pub kills: i64,
+    pub deaths: i64,
+    pub assists: i64,
+    pub total_duration: i64,
+    pub total_time_dead: i64,
+    /// Total LP gained or lost over the matches with a [`RankSnapshot`] delta, or
+    /// [`None`] if there are none.
+    pub lp_delta: Option<i64>,
+}
+
+/// Stats of any participant of a match, whether or not they are followed.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct MatchParticipant {
+    pub match_id: MatchId,
+    pub puuid: Puuid,
+    pub participant_id: i64,
+    pub team_id: i64,
+    pub game_name: Option<String>,
+    pub tag: Option<String>,
+    pub champion_id: i64,
+    pub champion_level: i64,
+    pub position: Option<String>,
+    pub win: bool,
+    pub kills: i64,
+    pub deaths: i64,
+    pub assists: i64,
+    pub gold_earned: i64,
+    pub gold_spent: i64,
+    pub total_damage_dealt: i64,
+    pub total_damage_dealt_to_champions: i64,
+    pub total_damage_taken: i64,
+    pub damage_self_mitigated: i64,
+    pub vision_score: i64,
+    pub wards_placed: i64,
+    pub wards_killed: i64,
+    pub total_minions_killed: i64,
+    pub neutral_minions_killed: i64,
+    pub time_dead: i64,
+    pub item0: i64,
+    pub item1: i64,
+    pub item2: i64,
+    pub item3: i64,
+    pub item4: i64,
+    pub item5: i64,
+    pub item6: i64,
+    pub summoner1_id: i64,
+    pub summoner2_id: i64,
+}
+
+impl MatchParticipant {
+    /// Total creep score, including both lane minions and jungle monsters.
+    pub fn creep_score(&self) -> i64 {
+        self.total_minions_killed + self.neutral_minions_killed
+    }
+
+    /// Item IDs of the participant's final build, excluding empty slots.
+    pub fn items(&self) -> Vec<i64> {
+        [
+            self.item0, self.item1, self.item2, self.item3, self.item4, self.item5, self.item6,
+        ]
+        .into_iter()
+        .filter(|item| *item != 0)
+        .collect()
+    }
+}
+
+/// A death of a summoner, taken from a match timeline.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct DeathEvent {
+    pub match_id: MatchId,
+    pub puuid: Puuid,
+    /// Milliseconds since the start of the match.
+    pub timestamp: i64,
+    /// [`None`] if the summoner was not killed by a champion (e.g. executed by a turret).
+    pub killer_puuid: Option<Puuid>,
+    pub position_x: Option<i64>,
+    pub position_y: Option<i64>,
+    #[sqlx(skip)]
+    pub assister_puuids: Vec<Puuid>,
+}
+
+/// Delivery status of a [`Notification`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum NotificationStatus {
+    /// Recorded, but not yet sent.
+    Pending,
+    Sent,
+    Failed,
+}
+
+/// An alert about a summoner match sent (or to be sent) to a guild.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct Notification {
+    pub puuid: Puuid,
+    pub match_id: MatchId,
+    pub guild_id: GuildId,
+    pub level: String,
+    pub message: String,
+    /// ID of the sent message, if it has been sent.
+    pub message_id: Option<i64>,
+    pub status: NotificationStatus,
+    /// Number of times sending has been attempted.
+    pub attempts: i64,
+    pub update_time: NaiveDateTime,
+}
+
+/// Kind of Riot API work that a [`Job`] is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum JobKind {
+    /// Fetching and ingesting the [`Match`] data of the match.
+    MatchData,
+    /// Fetching and ingesting the timeline of the match.
+    Timeline,
+}
+
+/// State of a [`Job`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Queued, but not yet attempted since it was last queued.
+    Pending,
+    /// Being fetched from the Riot API.
+    InFlight,
+    Failed,
+    /// Fetched and ingested, left to be deleted.
+    Done,
+}
+
+/// Pending Riot API work for a match, stored so that it survives collector restarts.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Job {
+    pub kind: JobKind,
+    pub match_id: MatchId,
+    pub status: JobStatus,
+    /// Number of times the job has been started.
+    pub attempts: i64,
+    pub create_time: NaiveDateTime,
+    pub update_time: NaiveDateTime,
+}
+
+/// Pending fetch of the rank of a summoner after their ranked match, which the guilds
+/// following them are only notified of once it is done, stored so that notifications
+/// survive collector restarts.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct LeagueJob {
+    pub puuid: Puuid,
+    pub match_id: MatchId,
+    pub status: JobStatus,
+    /// Number of times the job has been started.
+    pub attempts: i64,
+    pub create_time: NaiveDateTime,
+    pub update_time: NaiveDateTime,
+}
+
+/// State of a [`Backfill`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum BackfillStatus {
+    /// Requested, but not yet started by the collector.
+    Pending,
+    /// Being paged through by the collector.
+    InProgress,
+    Failed,
+    Done,
+    /// Requested while the collector was not configured to backfill, so never started.
+    Skipped,
+}
+
+/// Fetching the match history of a summoner from before they were first followed.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct Backfill {
+    pub puuid: Puuid,
+    /// Guild that followed the summoner, which is told about the progress. [`None`] if
+    /// the guild has since been deleted.
+    pub guild_id: Option<GuildId>,
+    pub status: BackfillStatus,
+    /// Number of match IDs found so far, from which paging resumes.
+    pub matches: i64,
+    pub create_time: NaiveDateTime,
+    pub update_time: NaiveDateTime,
+}
+
+/// Kind of Riot API request that a [`DeadLetter`] is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "text", rename_all = "snake_case")]
+pub enum DeadLetterKind {
+    Account,
+    MatchIds,
+    MatchData,
+    Timeline,
+    League,
+}
+
+/// A Riot API request that failed after exhausting its retries, kept for operators to
+/// inspect and requeue.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub kind: DeadLetterKind,
+    /// Identifies what was requested, in a format given by the collector for each kind.
+    pub key: String,
+    /// The last error of the request.
+    pub error: String,
+    pub attempts: i64,
+    /// Whether an operator has asked for the request to be made again.
+    pub requeued: bool,
+    pub create_time: NaiveDateTime,
+    pub update_time: NaiveDateTime,
+}
+
+/// The rank of a summoner in a ranked queue, as fetched after one of their matches in it.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct RankSnapshot {
+    pub puuid: Puuid,
+    pub match_id: MatchId,
+    /// Ranked queue as given by the Riot API (e.g. `"RANKED_SOLO_5x5"`).
+    pub queue_type: String,
+    /// Tier as given by the Riot API (e.g. `"GOLD"`).
+    pub tier: String,
+    /// Division as given by the Riot API (e.g. `"II"`).
+    pub division: String,
+    pub league_points: i64,
+    /// Change in LP since the summoner's previous snapshot in the same queue, across
+    /// tiers and divisions. [`None`] for a summoner's first snapshot in a queue.
+    pub lp_delta: Option<i64>,
+    pub snapshot_time: NaiveDateTime,
 }
@@ -0,0 +1,1184 @@
+use crate::dump::Dump;
+use crate::error::Error;
+use crate::leaderboard::LeaderboardQuery;
+use crate::stats::{StatsGrouping, StatsQuery};
+use crate::{
+    archive, get_participant, get_surrender, get_winning_team, model, timeline, Storage,
+    DEFAULT_QUEUE_IDS,
+};
+use async_trait::async_trait;
+use riven::consts::PlatformRoute;
+use riven::models::account_v1::Account;
+use riven::models::match_v5::{Match, Timeline};
+use sqlx::migrate::Migrator;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::{PgConnection, Pool, Postgres};
+use the_collector_types::{ChannelId, GuildId, MatchId, Puuid};
+
+/// Schema migrations, embedded at compile time from the crate's `migrations` directory.
+static MIGRATOR: Migrator = sqlx::migrate!("migrations/postgres");
+
+/// Month of a match's start time, formatted as `YYYY-MM`.
+const START_MONTH: &str = "to_char(match.start_time, 'YYYY-MM')";
+
+/// [`Storage`] implementation for PostgreSQL.
+///
+/// Queries are checked at runtime rather than compile time so that building
+/// does not require a Postgres database to be available.
+#[derive(Debug)]
+pub struct PostgresStorage {
+    pool: Pool<Postgres>,
+}
+
+impl PostgresStorage {
+    pub fn new(pool: Pool<Postgres>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn migrate(&self) -> Result<(), Error> {
+        MIGRATOR.run(&self.pool).await.map_err(Error::MigrateError)
+    }
+
+    async fn get_summoner(&self, puuid: &Puuid) -> Result<Option<model::Summoner>, Error> {
+        sqlx::query_as("SELECT * FROM summoner WHERE puuid = $1")
+            .bind(puuid)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_summoners(&self) -> Result<Vec<model::Summoner>, Error> {
+        sqlx::query_as("SELECT * FROM summoner")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_summoner_by_name(
+        &self,
+        name: &str,
+        tag: &str,
+    ) -> Result<Option<model::Summoner>, Error> {
+        sqlx::query_as(
+            "SELECT * FROM summoner WHERE puuid = COALESCE(
+                (SELECT puuid FROM summoner WHERE game_name = $1 AND tag = $2),
+                (SELECT puuid FROM summoner_name_history WHERE game_name = $1 AND tag = $2
+                ORDER BY change_time DESC LIMIT 1)
+            )",
+        )
+        .bind(name)
+        .bind(tag)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_summoner(
+        &self,
+        account: &Account,
+        region: PlatformRoute,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let region = region.to_string();
+        let game_name = account
+            .game_name
+            .as_ref()
+            .ok_or(Error::MissingData("Game Name".into()))?;
+        let tag = &account
+            .tag_line
+            .as_ref()
+            .ok_or(Error::MissingData("Tag".into()))?;
+        sqlx::query(
+            "INSERT INTO summoner (puuid, game_name, tag, create_time, region)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(&account.puuid)
+        .bind(game_name)
+        .bind(tag)
+        .bind(now)
+        .bind(region)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_summoner_region(
+        &self,
+        puuid: &Puuid,
+        region: PlatformRoute,
+    ) -> Result<u64, Error> {
+        sqlx::query("UPDATE summoner SET region = $1 WHERE puuid = $2 AND region != $1")
+            .bind(region.to_string())
+            .bind(puuid)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn update_summoner_name(&self, account: &Account) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let game_name = account
+            .game_name
+            .as_ref()
+            .ok_or(Error::MissingData("Game Name".into()))?;
+        let tag = account
+            .tag_line
+            .as_ref()
+            .ok_or(Error::MissingData("Tag".into()))?;
+
+        let mut tx = self.pool.begin().await?;
+        let Some((current_game_name, current_tag)): Option<(String, String)> =
+            sqlx::query_as("SELECT game_name, tag FROM summoner WHERE puuid = $1 FOR UPDATE")
+                .bind(&account.puuid)
+                .fetch_optional(&mut *tx)
+                .await?
+        else {
+            return Ok(0);
+        };
+        if current_game_name == *game_name && current_tag == *tag {
+            return Ok(0);
+        }
+
+        sqlx::query(
+            "INSERT INTO summoner_name_history (puuid, game_name, tag, change_time)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (puuid, game_name, tag) DO UPDATE SET change_time = excluded.change_time",
+        )
+        .bind(&account.puuid)
+        .bind(current_game_name)
+        .bind(current_tag)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        let count = sqlx::query("UPDATE summoner SET game_name = $1, tag = $2 WHERE puuid = $3")
+            .bind(game_name)
+            .bind(tag)
+            .bind(&account.puuid)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        tx.commit().await?;
+        Ok(count)
+    }
+
+    async fn get_summoner_name_history(
+        &self,
+        puuid: &Puuid,
+    ) -> Result<Vec<model::SummonerName>, Error> {
+        sqlx::query_as(
+            "SELECT * FROM summoner_name_history WHERE puuid = $1 ORDER BY change_time DESC",
+        )
+        .bind(puuid)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_unfollowed_summoners(&self) -> Result<Vec<model::Summoner>, Error> {
+        sqlx::query_as(
+            "SELECT * FROM summoner WHERE NOT EXISTS (
+                SELECT 1 FROM guild_following WHERE guild_following.puuid = summoner.puuid
+            )",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn delete_summoners(&self, puuids: &[Puuid]) -> Result<u64, Error> {
+        let mut conn = self.pool.acquire().await?;
+        delete_summoner_rows(&mut conn, puuids).await
+    }
+
+    async fn get_guilds(&self) -> Result<Vec<model::Guild>, Error> {
+        sqlx::query_as("SELECT * FROM guild")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn insert_guild(&self, guild_id: GuildId) -> Result<u64, Error> {
+        sqlx::query("INSERT INTO guild (id) VALUES ($1) ON CONFLICT DO NOTHING")
+            .bind(guild_id)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn delete_guild(&self, guild_id: GuildId) -> Result<u64, Error> {
+        sqlx::query("DELETE FROM guild WHERE id = $1")
+            .bind(guild_id)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn update_channel(
+        &self,
+        guild_id: GuildId,
+        channel_id: Option<ChannelId>,
+    ) -> Result<u64, Error> {
+        sqlx::query("UPDATE guild SET channel_id = $1 WHERE id = $2")
+            .bind(channel_id)
+            .bind(guild_id)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<u64, Error> {
+        sqlx::query("UPDATE guild SET channel_id = NULL WHERE channel_id = $1")
+            .bind(channel_id)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_guild_follows(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<model::GuildFollowing>, Error> {
+        sqlx::query_as("SELECT * FROM guild_following WHERE guild_id = $1")
+            .bind(guild_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_followed_summoners(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<model::Summoner>, Error> {
+        sqlx::query_as(
+            "SELECT summoner.* FROM summoner
+            INNER JOIN guild_following ON guild_following.puuid = summoner.puuid
+            WHERE guild_following.guild_id = $1 ORDER BY game_name, tag",
+        )
+        .bind(guild_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_following_guilds(&self, puuid: &Puuid) -> Result<Vec<model::Guild>, Error> {
+        sqlx::query_as(
+            "SELECT guild.* FROM guild_following INNER JOIN guild ON guild.id = guild_following.guild_id WHERE guild_following.puuid = $1",
+        )
+        .bind(puuid)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_guild_following(&self, guild_id: GuildId, puuid: &Puuid) -> Result<u64, Error> {
+        sqlx::query("INSERT INTO guild_following (guild_id, puuid) VALUES ($1, $2)")
+            .bind(guild_id)
+            .bind(puuid)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn delete_guild_following(&self, guild_id: GuildId, puuid: &Puuid) -> Result<u64, Error> {
+        sqlx::query("DELETE FROM guild_following WHERE guild_id = $1 AND puuid = $2")
+            .bind(guild_id)
+            .bind(puuid)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn insert_notification(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        guild_id: GuildId,
+        level: &str,
+        message: &str,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "INSERT INTO notification
+                (puuid, match_id, guild_id, level, message, status, attempts, update_time)
+            VALUES ($1, $2, $3, $4, $5, $6, 0, $7)
+            ON CONFLICT (puuid, match_id, guild_id) DO NOTHING",
+        )
+        .bind(puuid)
+        .bind(match_id)
+        .bind(guild_id)
+        .bind(level)
+        .bind(message)
+        .bind(model::NotificationStatus::Pending)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_notification(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        guild_id: GuildId,
+        status: model::NotificationStatus,
+        message_id: Option<u64>,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "UPDATE notification
+            SET status = $1, message_id = $2, attempts = attempts + 1, update_time = $3
+            WHERE puuid = $4 AND match_id = $5 AND guild_id = $6",
+        )
+        .bind(status)
+        .bind(message_id.map(|message_id| message_id as i64))
+        .bind(now)
+        .bind(puuid)
+        .bind(match_id)
+        .bind(guild_id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_notifications(
+        &self,
+        status: model::NotificationStatus,
+    ) -> Result<Vec<model::Notification>, Error> {
+        sqlx::query_as("SELECT * FROM notification WHERE status = $1 ORDER BY update_time")
+            .bind(status)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn insert_job(&self, kind: model::JobKind, match_id: &MatchId) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "INSERT INTO job (kind, match_id, status, attempts, create_time, update_time)
+            VALUES ($1, $2, $3, 0, $4, $4)
+            ON CONFLICT (kind, match_id) DO UPDATE
+            SET status = excluded.status, update_time = excluded.update_time
+            WHERE job.status IN ('failed', 'done')",
+        )
+        .bind(kind)
+        .bind(match_id)
+        .bind(model::JobStatus::Pending)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_job(
+        &self,
+        kind: model::JobKind,
+        match_id: &MatchId,
+        status: model::JobStatus,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "UPDATE job SET status = $1, attempts = attempts + $2, update_time = $3
+            WHERE kind = $4 AND match_id = $5",
+        )
+        .bind(status)
+        .bind(i64::from(status == model::JobStatus::InFlight))
+        .bind(now)
+        .bind(kind)
+        .bind(match_id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_jobs(&self, status: model::JobStatus) -> Result<Vec<model::Job>, Error> {
+        sqlx::query_as("SELECT * FROM job WHERE status = $1 ORDER BY create_time")
+            .bind(status)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn delete_jobs(&self, status: model::JobStatus) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        let jobs = sqlx::query("DELETE FROM job WHERE status = $1")
+            .bind(status)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let league_jobs = sqlx::query("DELETE FROM league_job WHERE status = $1")
+            .bind(status)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(jobs + league_jobs)
+    }
+
+    async fn insert_league_job(&self, puuid: &Puuid, match_id: &MatchId) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "INSERT INTO league_job (puuid, match_id, status, attempts, create_time, update_time)
+            VALUES ($1, $2, $3, 0, $4, $4)
+            ON CONFLICT (puuid, match_id) DO UPDATE
+            SET status = excluded.status, update_time = excluded.update_time
+            WHERE league_job.status IN ('failed', 'done')",
+        )
+        .bind(puuid)
+        .bind(match_id)
+        .bind(model::JobStatus::Pending)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_league_job(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        status: model::JobStatus,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "UPDATE league_job SET status = $1, attempts = attempts + $2, update_time = $3
+            WHERE puuid = $4 AND match_id = $5",
+        )
+        .bind(status)
+        .bind(i64::from(status == model::JobStatus::InFlight))
+        .bind(now)
+        .bind(puuid)
+        .bind(match_id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_league_jobs(
+        &self,
+        status: model::JobStatus,
+    ) -> Result<Vec<model::LeagueJob>, Error> {
+        sqlx::query_as("SELECT * FROM league_job WHERE status = $1 ORDER BY create_time")
+            .bind(status)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn insert_backfill(&self, puuid: &Puuid, guild_id: GuildId) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "INSERT INTO backfill (puuid, guild_id, status, matches, create_time, update_time)
+            VALUES ($1, $2, $3, 0, $4, $4)
+            ON CONFLICT (puuid) DO NOTHING",
+        )
+        .bind(puuid)
+        .bind(guild_id)
+        .bind(model::BackfillStatus::Pending)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_backfill(
+        &self,
+        puuid: &Puuid,
+        status: model::BackfillStatus,
+        matches: i64,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "UPDATE backfill SET status = $1, matches = $2, update_time = $3 WHERE puuid = $4",
+        )
+        .bind(status)
+        .bind(matches)
+        .bind(now)
+        .bind(puuid)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_backfills(
+        &self,
+        status: model::BackfillStatus,
+    ) -> Result<Vec<model::Backfill>, Error> {
+        sqlx::query_as("SELECT * FROM backfill WHERE status = $1 ORDER BY create_time")
+            .bind(status)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn insert_dead_letter(
+        &self,
+        kind: model::DeadLetterKind,
+        key: &str,
+        error: &str,
+        attempts: i64,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query(
+            "INSERT INTO dead_letter
+                (kind, key, error, attempts, requeued, create_time, update_time)
+            VALUES ($1, $2, $3, $4, FALSE, $5, $5)
+            ON CONFLICT (kind, key) DO UPDATE
+            SET error = excluded.error, attempts = excluded.attempts,
+                requeued = FALSE, update_time = excluded.update_time",
+        )
+        .bind(kind)
+        .bind(key)
+        .bind(error)
+        .bind(attempts)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<model::DeadLetter>, Error> {
+        sqlx::query_as("SELECT * FROM dead_letter ORDER BY update_time")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn requeue_dead_letters(
+        &self,
+        kind: Option<model::DeadLetterKind>,
+    ) -> Result<u64, Error> {
+        sqlx::query("UPDATE dead_letter SET requeued = TRUE WHERE $1::TEXT IS NULL OR kind = $1")
+            .bind(kind)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn take_requeued_dead_letters(&self) -> Result<Vec<model::DeadLetter>, Error> {
+        sqlx::query_as("DELETE FROM dead_letter WHERE requeued RETURNING *")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_guild_queues(&self, guild_id: GuildId) -> Result<Vec<u16>, Error> {
+        let queue_ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT queue_id FROM guild_queue_filter WHERE guild_id = $1 ORDER BY queue_id",
+        )
+        .bind(guild_id)
+        .fetch_all(&self.pool)
+        .await?;
+        if queue_ids.is_empty() {
+            return Ok(DEFAULT_QUEUE_IDS.to_vec());
+        }
+        Ok(queue_ids
+            .into_iter()
+            .map(|queue_id| queue_id as u16)
+            .collect())
+    }
+
+    async fn set_guild_queues(&self, guild_id: GuildId, queue_ids: &[u16]) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM guild_queue_filter WHERE guild_id = $1")
+            .bind(guild_id)
+            .execute(&mut *tx)
+            .await?;
+        let mut count = 0;
+        for queue_id in queue_ids {
+            count += sqlx::query(
+                "INSERT INTO guild_queue_filter (guild_id, queue_id) VALUES ($1, $2)
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(guild_id)
+            .bind(i64::from(*queue_id))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(count)
+    }
+
+    async fn get_matches(&self, match_ids: &[MatchId]) -> Result<Vec<model::Match>, Error> {
+        sqlx::query_as("SELECT * FROM match WHERE id = ANY($1)")
+            .bind(match_ids)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_match(&self, match_id: &MatchId) -> Result<Option<model::Match>, Error> {
+        sqlx::query_as("SELECT * FROM match WHERE id = $1")
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_summoner_latest_match(
+        &self,
+        puuid: &Puuid,
+    ) -> Result<Option<model::Match>, Error> {
+        sqlx::query_as(
+            "SELECT match.* FROM match INNER JOIN summoner_match ON match.id = summoner_match.match_id
+            WHERE summoner_match.puuid = $1 ORDER BY start_time DESC LIMIT 1",
+        )
+        .bind(puuid)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_prunable_match_ids(
+        &self,
+        started_before: DateTime<Utc>,
+    ) -> Result<Vec<MatchId>, Error> {
+        sqlx::query_scalar(
+            "SELECT id FROM match WHERE start_time < $1 AND NOT EXISTS (
+                SELECT 1 FROM notification WHERE notification.match_id = match.id
+            ) ORDER BY start_time",
+        )
+        .bind(started_before)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn delete_matches(&self, match_ids: &[MatchId]) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = delete_match_rows(&mut tx, match_ids).await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn delete_pruned(&self, puuids: &[Puuid], match_ids: &[MatchId]) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        delete_summoner_rows(&mut tx, puuids).await?;
+        delete_match_rows(&mut tx, match_ids).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn ingest_match(&self, data: &Match, tracked_puuids: &[Puuid]) -> Result<u64, Error> {
+        let winning_team_id = get_winning_team(data)?;
+        let surrender = get_surrender(data)?;
+        let start_time = DateTime::from_timestamp_millis(data.info.game_start_timestamp)
+            .ok_or(Error::DateTimeOutOfRange)?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO match (id, start_time, duration, queue_id, game_version, game_mode, winning_team_id, surrender)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (id) DO UPDATE SET
+                start_time = excluded.start_time, duration = excluded.duration,
+                queue_id = excluded.queue_id, game_version = excluded.game_version,
+                game_mode = excluded.game_mode, winning_team_id = excluded.winning_team_id,
+                surrender = excluded.surrender",
+        )
+        .bind(&data.metadata.match_id)
+        .bind(start_time.naive_utc())
+        .bind(data.info.game_duration)
+        .bind(i64::from(u16::from(data.info.queue_id)))
+        .bind(&data.info.game_version)
+        .bind(data.info.game_mode.to_string())
+        .bind(i64::from(winning_team_id))
+        .bind(surrender)
+        .execute(&mut *tx)
+        .await?;
+
+        // Participants are replaced as a whole so that re-ingesting stays consistent
+        sqlx::query("DELETE FROM match_participant WHERE match_id = $1")
+            .bind(&data.metadata.match_id)
+            .execute(&mut *tx)
+            .await?;
+        for participant in &data.info.participants {
+            sqlx::query(
+                "INSERT INTO match_participant (
+                    match_id, puuid, participant_id, team_id, game_name, tag, champion_id,
+                    champion_level, position, win, kills, deaths, assists, gold_earned, gold_spent,
+                    total_damage_dealt, total_damage_dealt_to_champions, total_damage_taken,
+                    damage_self_mitigated, vision_score, wards_placed, wards_killed,
+                    total_minions_killed, neutral_minions_killed, time_dead,
+                    item0, item1, item2, item3, item4, item5, item6, summoner1_id, summoner2_id)
+                VALUES (
+                    $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12,
+                    $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24,
+                    $25, $26, $27, $28, $29, $30, $31, $32, $33, $34)",
+            )
+            .bind(&data.metadata.match_id)
+            .bind(&participant.puuid)
+            .bind(i64::from(participant.participant_id))
+            .bind(i64::from(u16::from(participant.team_id)))
+            .bind(&participant.riot_id_game_name)
+            .bind(&participant.riot_id_tagline)
+            .bind(i64::from(i16::from(participant.champion()?)))
+            .bind(i64::from(participant.champ_level))
+            .bind(&participant.team_position)
+            .bind(participant.win)
+            .bind(i64::from(participant.kills))
+            .bind(i64::from(participant.deaths))
+            .bind(i64::from(participant.assists))
+            .bind(i64::from(participant.gold_earned))
+            .bind(i64::from(participant.gold_spent))
+            .bind(i64::from(participant.total_damage_dealt))
+            .bind(i64::from(participant.total_damage_dealt_to_champions))
+            .bind(i64::from(participant.total_damage_taken))
+            .bind(i64::from(participant.damage_self_mitigated))
+            .bind(i64::from(participant.vision_score))
+            .bind(i64::from(participant.wards_placed))
+            .bind(i64::from(participant.wards_killed))
+            .bind(i64::from(participant.total_minions_killed))
+            .bind(i64::from(participant.neutral_minions_killed))
+            .bind(i64::from(participant.total_time_spent_dead))
+            .bind(i64::from(participant.item0))
+            .bind(i64::from(participant.item1))
+            .bind(i64::from(participant.item2))
+            .bind(i64::from(participant.item3))
+            .bind(i64::from(participant.item4))
+            .bind(i64::from(participant.item5))
+            .bind(i64::from(participant.item6))
+            .bind(i64::from(participant.summoner1_id))
+            .bind(i64::from(participant.summoner2_id))
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let mut count = 0;
+        for puuid in tracked_puuids {
+            let summoner_stats = get_participant(data, puuid)?;
+            count += sqlx::query(
+                "INSERT INTO summoner_match (puuid, match_id, kills, deaths, assists, champion_id, position, longest_time_living, time_dead, team_id)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ON CONFLICT (puuid, match_id) DO UPDATE SET
+                    kills = excluded.kills, deaths = excluded.deaths, assists = excluded.assists,
+                    champion_id = excluded.champion_id, position = excluded.position,
+                    longest_time_living = excluded.longest_time_living,
+                    time_dead = excluded.time_dead, team_id = excluded.team_id",
+            )
+            .bind(puuid)
+            .bind(&data.metadata.match_id)
+            .bind(i64::from(summoner_stats.kills))
+            .bind(i64::from(summoner_stats.deaths))
+            .bind(i64::from(summoner_stats.assists))
+            .bind(i64::from(i16::from(summoner_stats.champion()?)))
+            .bind(&summoner_stats.team_position)
+            .bind(i64::from(summoner_stats.longest_time_spent_living))
+            .bind(i64::from(summoner_stats.total_time_spent_dead))
+            .bind(i64::from(u16::from(summoner_stats.team_id)))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(count)
+    }
+
+    async fn archive_match(&self, match_id: &MatchId, payload: &[u8]) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let bytes = archive::encode(payload)?;
+        sqlx::query(
+            "INSERT INTO match_archive (match_id, data, archive_time) VALUES ($1, $2, $3)
+            ON CONFLICT (match_id) DO UPDATE SET data = excluded.data, archive_time = excluded.archive_time",
+        )
+        .bind(match_id)
+        .bind(bytes)
+        .bind(now)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_archived_payload(&self, match_id: &MatchId) -> Result<Option<Vec<u8>>, Error> {
+        let bytes: Option<Vec<u8>> =
+            sqlx::query_scalar("SELECT data FROM match_archive WHERE match_id = $1")
+                .bind(match_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        bytes.map(|bytes| archive::decode(&bytes)).transpose()
+    }
+
+    async fn get_archived_match_ids(&self) -> Result<Vec<MatchId>, Error> {
+        sqlx::query_scalar("SELECT match_id FROM match_archive ORDER BY archive_time")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_match_participants(
+        &self,
+        match_id: &MatchId,
+    ) -> Result<Vec<model::MatchParticipant>, Error> {
+        sqlx::query_as(
+            "SELECT * FROM match_participant WHERE match_id = $1 ORDER BY participant_id",
+        )
+        .bind(match_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_lane_opponent(
+        &self,
+        match_id: &MatchId,
+        puuid: &Puuid,
+    ) -> Result<Option<model::MatchParticipant>, Error> {
+        sqlx::query_as(
+            "SELECT opponent.* FROM match_participant AS opponent
+            INNER JOIN match_participant AS participant ON participant.match_id = opponent.match_id
+            WHERE participant.match_id = $1 AND participant.puuid = $2
+                AND opponent.position = participant.position AND opponent.team_id != participant.team_id",
+        )
+        .bind(match_id)
+        .bind(puuid)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn ingest_timeline(
+        &self,
+        data: &Timeline,
+        tracked_puuids: &[Puuid],
+    ) -> Result<u64, Error> {
+        let deaths = timeline::get_deaths(data, tracked_puuids)?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM death_event_assist WHERE match_id = $1")
+            .bind(&data.metadata.match_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM death_event WHERE match_id = $1")
+            .bind(&data.metadata.match_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut count = 0;
+        for death in deaths {
+            count += sqlx::query(
+                "INSERT INTO death_event (match_id, puuid, timestamp, killer_puuid, position_x, position_y)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(&data.metadata.match_id)
+            .bind(death.puuid)
+            .bind(death.timestamp)
+            .bind(death.killer_puuid)
+            .bind(death.position.map(|(x, _)| i64::from(x)))
+            .bind(death.position.map(|(_, y)| i64::from(y)))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            for assister_puuid in death.assister_puuids {
+                sqlx::query(
+                    "INSERT INTO death_event_assist (match_id, puuid, timestamp, assister_puuid)
+                    VALUES ($1, $2, $3, $4)",
+                )
+                .bind(&data.metadata.match_id)
+                .bind(death.puuid)
+                .bind(death.timestamp)
+                .bind(assister_puuid)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(count)
+    }
+
+    async fn get_death_events(
+        &self,
+        match_id: &MatchId,
+        puuid: &Puuid,
+    ) -> Result<Vec<model::DeathEvent>, Error> {
+        let mut death_events: Vec<model::DeathEvent> = sqlx::query_as(
+            "SELECT * FROM death_event WHERE match_id = $1 AND puuid = $2 ORDER BY timestamp",
+        )
+        .bind(match_id)
+        .bind(puuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let assists: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT timestamp, assister_puuid FROM death_event_assist
+            WHERE match_id = $1 AND puuid = $2 ORDER BY assister_puuid",
+        )
+        .bind(match_id)
+        .bind(puuid)
+        .fetch_all(&self.pool)
+        .await?;
+        for (timestamp, assister_puuid) in assists {
+            if let Some(death_event) = death_events
+                .iter_mut()
+                .find(|death_event| death_event.timestamp == timestamp)
+            {
+                death_event.assister_puuids.push(assister_puuid.into());
+            }
+        }
+        Ok(death_events)
+    }
+
+    async fn get_summoner_match(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+    ) -> Result<Option<model::SummonerMatch>, Error> {
+        sqlx::query_as("SELECT * FROM summoner_match WHERE puuid = $1 AND match_id = $2")
+            .bind(puuid)
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn insert_rank_snapshot(&self, snapshot: &model::RankSnapshot) -> Result<u64, Error> {
+        sqlx::query(
+            "INSERT INTO rank_snapshot (
+                puuid, match_id, queue_type, tier, division, league_points, lp_delta,
+                snapshot_time)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (puuid, match_id) DO NOTHING",
+        )
+        .bind(&snapshot.puuid)
+        .bind(&snapshot.match_id)
+        .bind(&snapshot.queue_type)
+        .bind(&snapshot.tier)
+        .bind(&snapshot.division)
+        .bind(snapshot.league_points)
+        .bind(snapshot.lp_delta)
+        .bind(snapshot.snapshot_time)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_rank_snapshot(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+    ) -> Result<Option<model::RankSnapshot>, Error> {
+        sqlx::query_as("SELECT * FROM rank_snapshot WHERE puuid = $1 AND match_id = $2")
+            .bind(puuid)
+            .bind(match_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_latest_rank_snapshot(
+        &self,
+        puuid: &Puuid,
+        queue_type: &str,
+    ) -> Result<Option<model::RankSnapshot>, Error> {
+        sqlx::query_as(
+            "SELECT * FROM rank_snapshot WHERE puuid = $1 AND queue_type = $2
+            ORDER BY snapshot_time DESC LIMIT 1",
+        )
+        .bind(puuid)
+        .bind(queue_type)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_leaderboard(
+        &self,
+        query: &LeaderboardQuery,
+    ) -> Result<Vec<model::LeaderboardEntry>, Error> {
+        // TODO: Only get ints?
+        let guild_queue_ids = match query.queue_ids {
+            Some(_) => Vec::new(),
+            None => self.get_guild_queues(query.guild_id).await?,
+        };
+        query
+            .build(Utc::now(), &guild_queue_ids)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn export_dump(&self) -> Result<Dump, Error> {
+        Ok(Dump {
+            guilds: sqlx::query_as("SELECT * FROM guild ORDER BY id")
+                .fetch_all(&self.pool)
+                .await?,
+            summoners: sqlx::query_as("SELECT * FROM summoner ORDER BY puuid")
+                .fetch_all(&self.pool)
+                .await?,
+            matches: sqlx::query_as("SELECT * FROM match ORDER BY id")
+                .fetch_all(&self.pool)
+                .await?,
+            summoner_matches: sqlx::query_as(
+                "SELECT * FROM summoner_match ORDER BY puuid, match_id",
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            guild_followings: sqlx::query_as(
+                "SELECT * FROM guild_following ORDER BY guild_id, puuid",
+            )
+            .fetch_all(&self.pool)
+            .await?,
+        })
+    }
+
+    async fn insert_dump(&self, dump: &Dump) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = 0;
+
+        for guild in &dump.guilds {
+            inserted += sqlx::query(
+                "INSERT INTO guild (id, channel_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            )
+            .bind(guild.id)
+            .bind(guild.channel_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        for summoner in &dump.summoners {
+            inserted += sqlx::query(
+                "INSERT INTO summoner (puuid, game_name, tag, create_time, region)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(&summoner.puuid)
+            .bind(&summoner.game_name)
+            .bind(&summoner.tag)
+            .bind(summoner.create_time)
+            .bind(&summoner.region)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        for data in &dump.matches {
+            inserted += sqlx::query(
+                "INSERT INTO match (
+                    id, start_time, duration, queue_id, game_version, game_mode,
+                    winning_team_id, surrender)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(&data.id)
+            .bind(data.start_time)
+            .bind(data.duration)
+            .bind(data.queue_id)
+            .bind(&data.game_version)
+            .bind(&data.game_mode)
+            .bind(data.winning_team_id)
+            .bind(data.surrender)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        for row in &dump.summoner_matches {
+            inserted += sqlx::query(
+                "INSERT INTO summoner_match (
+                    puuid, match_id, kills, deaths, assists, champion_id, position,
+                    longest_time_living, time_dead, team_id)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(&row.puuid)
+            .bind(&row.match_id)
+            .bind(row.kills)
+            .bind(row.deaths)
+            .bind(row.assists)
+            .bind(row.champion_id)
+            .bind(&row.position)
+            .bind(row.longest_time_living)
+            .bind(row.time_dead)
+            .bind(row.team_id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        for row in &dump.guild_followings {
+            inserted += sqlx::query(
+                "INSERT INTO guild_following (guild_id, puuid) VALUES ($1, $2)
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(row.guild_id)
+            .bind(&row.puuid)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn get_summoner_stats(
+        &self,
+        query: &StatsQuery,
+    ) -> Result<Option<model::SummonerAggregateStats>, Error> {
+        query
+            .build(None, START_MONTH)
+            .build_query_as()
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_summoner_grouped_stats(
+        &self,
+        query: &StatsQuery,
+        grouping: StatsGrouping,
+    ) -> Result<Vec<model::SummonerGroupStats>, Error> {
+        query
+            .build(Some(grouping), START_MONTH)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+}
+
+async fn delete_summoner_rows(conn: &mut PgConnection, puuids: &[Puuid]) -> Result<u64, Error> {
+    sqlx::query("DELETE FROM summoner WHERE puuid = ANY($1)")
+        .bind(puuids)
+        .execute(conn)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+}
+
+async fn delete_match_rows(conn: &mut PgConnection, match_ids: &[MatchId]) -> Result<u64, Error> {
+    // Archived payloads are not tied to the match row, so delete them separately
+    sqlx::query("DELETE FROM match_archive WHERE match_id = ANY($1)")
+        .bind(match_ids)
+        .execute(&mut *conn)
+        .await?;
+    Ok(sqlx::query("DELETE FROM match WHERE id = ANY($1)")
+        .bind(match_ids)
+        .execute(conn)
+        .await?
+        .rows_affected())
+}
@@ -0,0 +1,52 @@
+//! Ranked standings of summoners, recorded after each of their ranked matches.
+
+use riven::consts::{Division, QueueType, Tier};
+use std::str::FromStr;
+
+/// Tiers that are split into divisions, lowest first. Apex tiers share a single ladder
+/// above these.
+const DIVIDED_TIERS: [Tier; 7] = [
+    Tier::IRON,
+    Tier::BRONZE,
+    Tier::SILVER,
+    Tier::GOLD,
+    Tier::PLATINUM,
+    Tier::EMERALD,
+    Tier::DIAMOND,
+];
+
+/// LP needed to advance a division.
+const DIVISION_LP: i64 = 100;
+
+/// Number of divisions in each tier that has them.
+const DIVISIONS_PER_TIER: i64 = 4;
+
+/// The ranked queue that matches of a queue ID count towards, if any.
+pub fn queue_type(queue_id: u16) -> Option<QueueType> {
+    match queue_id {
+        420 => Some(QueueType::RANKED_SOLO_5x5),
+        440 => Some(QueueType::RANKED_FLEX_SR),
+        _ => None,
+    }
+}
+
+/// Position of a rank on a single ladder spanning every tier, where each division is
+/// worth [`DIVISION_LP`], so that the LP gained or lost between two ranks is their
+/// difference. Returns [`None`] for an unknown or unranked tier or division.
+pub fn ladder_points(tier: &str, division: &str, league_points: i64) -> Option<i64> {
+    let tier = Tier::from_str(tier).ok()?;
+    let tier_start = DIVISION_LP * DIVISIONS_PER_TIER;
+    if tier.is_apex() {
+        return Some(DIVIDED_TIERS.len() as i64 * tier_start + league_points);
+    }
+    let tier_index = DIVIDED_TIERS.iter().position(|divided| *divided == tier)? as i64;
+    let division_index = match Division::from_str(division).ok()? {
+        Division::IV => 0,
+        Division::III => 1,
+        Division::II => 2,
+        Division::I => 3,
+        #[allow(deprecated)]
+        Division::V => return None,
+    };
+    Some(tier_index * tier_start + division_index * DIVISION_LP + league_points)
+}
@@ -0,0 +1,56 @@
+//! Policies for deleting data that is no longer needed.
+
+use crate::leaderboard::{LeaderboardMetric, TimeWindow};
+use the_collector_types::{MatchId, Puuid};
+
+/// Number of entries of each leaderboard kept by default, the most a guild can view.
+const DEFAULT_LEADERBOARD_SIZE: usize = 20;
+
+/// What [`DbHandler::prune`] deletes. Nothing is deleted by default.
+///
+/// [`DbHandler::prune`]: crate::DbHandler::prune
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetentionPolicy {
+    /// Delete matches that started more than this many days ago, unless a guild has
+    /// been notified of them or they are on a retained leaderboard.
+    pub match_retention_days: Option<u64>,
+    /// Delete summoners that no guild follows, so that they are no longer polled.
+    pub prune_unfollowed_summoners: bool,
+    /// Number of entries kept of each guild's leaderboards, ranked by each of the
+    /// `leaderboard_metrics` over each of the `leaderboard_windows`, in the queues the
+    /// guild counts.
+    pub leaderboard_size: usize,
+    pub leaderboard_windows: Vec<TimeWindow>,
+    /// By default, every metric except [`LeaderboardMetric::WeightedKda`], whose weights
+    /// are configured for the match evaluator.
+    pub leaderboard_metrics: Vec<LeaderboardMetric>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            match_retention_days: None,
+            prune_unfollowed_summoners: false,
+            leaderboard_size: DEFAULT_LEADERBOARD_SIZE,
+            leaderboard_windows: vec![
+                TimeWindow::AllTime,
+                TimeWindow::ThisMonth,
+                TimeWindow::ThisWeek,
+            ],
+            leaderboard_metrics: vec![
+                LeaderboardMetric::Deaths,
+                LeaderboardMetric::TimeDead,
+                LeaderboardMetric::DeathsPerMinute,
+            ],
+        }
+    }
+}
+
+/// What was deleted by [`DbHandler::prune`], or what would have been on a dry run.
+///
+/// [`DbHandler::prune`]: crate::DbHandler::prune
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PruneReport {
+    pub match_ids: Vec<MatchId>,
+    pub puuids: Vec<Puuid>,
+}
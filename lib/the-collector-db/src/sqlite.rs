@@ -0,0 +1,1307 @@
+use crate::dump::Dump;
+use crate::error::Error;
+use crate::leaderboard::LeaderboardQuery;
+use crate::stats::{StatsGrouping, StatsQuery};
+use crate::{
+    archive, get_participant, get_surrender, get_winning_team, model, timeline, Storage,
+    DEFAULT_QUEUE_IDS,
+};
+use async_trait::async_trait;
+use riven::consts::PlatformRoute;
+use riven::models::account_v1::Account;
+use riven::models::match_v5::{Match, Timeline};
+use sqlx::migrate::Migrator;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::{Pool, Sqlite, SqliteConnection};
+use the_collector_types::{ChannelId, GuildId, MatchId, Puuid};
+
+/// Schema migrations, embedded at compile time from the crate's `migrations` directory.
+static MIGRATOR: Migrator = sqlx::migrate!("migrations/sqlite");
+
+/// Month of a match's start time, formatted as `YYYY-MM`. Start times are stored as
+/// RFC 3339 text, so this is a prefix of them.
+const START_MONTH: &str = "substr(match.start_time, 1, 7)";
+
+/// Maximum number of rows deleted by a single statement when pruning, to stay within
+/// SQLite's limit on bound parameters.
+const PRUNE_BATCH_SIZE: usize = 500;
+
+/// [`Storage`] implementation for SQLite.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteStorage {
+    pub fn new(pool: Pool<Sqlite>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn migrate(&self) -> Result<(), Error> {
+        MIGRATOR.run(&self.pool).await.map_err(Error::MigrateError)
+    }
+
+    async fn get_summoner(&self, puuid: &Puuid) -> Result<Option<model::Summoner>, Error> {
+        sqlx::query_as!(
+            model::Summoner,
+            "SELECT * FROM summoner WHERE puuid = ?",
+            puuid as _
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_summoners(&self) -> Result<Vec<model::Summoner>, Error> {
+        sqlx::query_as!(model::Summoner, "SELECT * FROM summoner")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_summoner_by_name(
+        &self,
+        name: &str,
+        tag: &str,
+    ) -> Result<Option<model::Summoner>, Error> {
+        sqlx::query_as!(
+            model::Summoner,
+            "SELECT * FROM summoner WHERE puuid = COALESCE(
+                (SELECT puuid FROM summoner WHERE game_name = ? AND tag = ?),
+                (SELECT puuid FROM summoner_name_history WHERE game_name = ? AND tag = ?
+                ORDER BY change_time DESC LIMIT 1)
+            )",
+            name,
+            tag,
+            name,
+            tag
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_summoner(
+        &self,
+        account: &Account,
+        region: PlatformRoute,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let region = region.to_string();
+        let game_name = account
+            .game_name
+            .as_ref()
+            .ok_or(Error::MissingData("Game Name".into()))?;
+        let tag = &account
+            .tag_line
+            .as_ref()
+            .ok_or(Error::MissingData("Tag".into()))?;
+        sqlx::query!(
+            "INSERT OR IGNORE
+            INTO summoner (puuid, game_name, tag, create_time, region)
+            VALUES (?, ?, ?, ?, ?)",
+            account.puuid,
+            game_name,
+            tag,
+            now,
+            region
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_summoner_region(
+        &self,
+        puuid: &Puuid,
+        region: PlatformRoute,
+    ) -> Result<u64, Error> {
+        let region = region.to_string();
+        sqlx::query!(
+            "UPDATE summoner SET region = ? WHERE puuid = ? AND region != ?",
+            region,
+            puuid as _,
+            region
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_summoner_name(&self, account: &Account) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let game_name = account
+            .game_name
+            .as_ref()
+            .ok_or(Error::MissingData("Game Name".into()))?;
+        let tag = account
+            .tag_line
+            .as_ref()
+            .ok_or(Error::MissingData("Tag".into()))?;
+
+        let mut tx = self.pool.begin().await?;
+        let Some(current) = sqlx::query!(
+            "SELECT game_name, tag FROM summoner WHERE puuid = ?",
+            account.puuid
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(0);
+        };
+        if current.game_name == *game_name && current.tag == *tag {
+            return Ok(0);
+        }
+
+        sqlx::query!(
+            "INSERT INTO summoner_name_history (puuid, game_name, tag, change_time)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (puuid, game_name, tag) DO UPDATE SET change_time = excluded.change_time",
+            account.puuid,
+            current.game_name,
+            current.tag,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        let count = sqlx::query!(
+            "UPDATE summoner SET game_name = ?, tag = ? WHERE puuid = ?",
+            game_name,
+            tag,
+            account.puuid
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+        Ok(count)
+    }
+
+    async fn get_summoner_name_history(
+        &self,
+        puuid: &Puuid,
+    ) -> Result<Vec<model::SummonerName>, Error> {
+        sqlx::query_as!(
+            model::SummonerName,
+            "SELECT * FROM summoner_name_history WHERE puuid = ? ORDER BY change_time DESC",
+            puuid as _
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_unfollowed_summoners(&self) -> Result<Vec<model::Summoner>, Error> {
+        sqlx::query_as!(
+            model::Summoner,
+            "SELECT * FROM summoner WHERE NOT EXISTS (
+                SELECT 1 FROM guild_following WHERE guild_following.puuid = summoner.puuid
+            )"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn delete_summoners(&self, puuids: &[Puuid]) -> Result<u64, Error> {
+        let mut conn = self.pool.acquire().await?;
+        delete_summoner_rows(&mut conn, puuids).await
+    }
+
+    async fn get_guilds(&self) -> Result<Vec<model::Guild>, Error> {
+        sqlx::query_as!(
+            model::Guild,
+            r#"SELECT id AS "id: _", channel_id AS "channel_id: _" FROM guild"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_guild(&self, guild_id: GuildId) -> Result<u64, Error> {
+        sqlx::query!("INSERT OR IGNORE INTO guild (id) VALUES (?)", guild_id as _)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn delete_guild(&self, guild_id: GuildId) -> Result<u64, Error> {
+        sqlx::query!("DELETE FROM guild WHERE id = ?", guild_id as _)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(Error::SqlxError)
+    }
+
+    async fn update_channel(
+        &self,
+        guild_id: GuildId,
+        channel_id: Option<ChannelId>,
+    ) -> Result<u64, Error> {
+        sqlx::query!(
+            "UPDATE guild SET channel_id = ? WHERE id = ?",
+            channel_id as _,
+            guild_id as _
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<u64, Error> {
+        sqlx::query!(
+            "UPDATE guild SET channel_id = NULL WHERE channel_id = ?",
+            channel_id as _
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_guild_follows(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<model::GuildFollowing>, Error> {
+        sqlx::query_as!(
+            model::GuildFollowing,
+            r#"SELECT guild_id AS "guild_id: _", puuid FROM guild_following WHERE guild_id = ?"#,
+            guild_id as _,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_followed_summoners(
+        &self,
+        guild_id: GuildId,
+    ) -> Result<Vec<model::Summoner>, Error> {
+        sqlx::query_as!(
+            model::Summoner,
+            "SELECT summoner.* FROM summoner
+            INNER JOIN guild_following ON guild_following.puuid = summoner.puuid
+            WHERE guild_following.guild_id = ? ORDER BY game_name, tag",
+            guild_id as _
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_following_guilds(&self, puuid: &Puuid) -> Result<Vec<model::Guild>, Error> {
+        sqlx::query_as!(
+            model::Guild,
+            r#"SELECT guild.id AS "id: _", guild.channel_id AS "channel_id: _"
+            FROM guild_following INNER JOIN guild ON guild.id = guild_following.guild_id
+            WHERE guild_following.puuid = ?"#,
+            puuid as _
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_guild_following(&self, guild_id: GuildId, puuid: &Puuid) -> Result<u64, Error> {
+        sqlx::query!(
+            "INSERT INTO guild_following (guild_id, puuid) VALUES (?, ?)",
+            guild_id as _,
+            puuid as _
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn delete_guild_following(&self, guild_id: GuildId, puuid: &Puuid) -> Result<u64, Error> {
+        sqlx::query!(
+            "DELETE FROM guild_following WHERE guild_id = ? AND puuid = ?",
+            guild_id as _,
+            puuid as _
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_notification(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        guild_id: GuildId,
+        level: &str,
+        message: &str,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let status = model::NotificationStatus::Pending;
+        sqlx::query!(
+            "INSERT INTO notification
+                (puuid, match_id, guild_id, level, message, status, attempts, update_time)
+            VALUES (?, ?, ?, ?, ?, ?, 0, ?)
+            ON CONFLICT (puuid, match_id, guild_id) DO NOTHING",
+            puuid as _,
+            match_id as _,
+            guild_id as _,
+            level,
+            message,
+            status,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_notification(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        guild_id: GuildId,
+        status: model::NotificationStatus,
+        message_id: Option<u64>,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let message_id = message_id.map(|message_id| message_id as i64);
+        sqlx::query!(
+            "UPDATE notification
+            SET status = ?, message_id = ?, attempts = attempts + 1, update_time = ?
+            WHERE puuid = ? AND match_id = ? AND guild_id = ?",
+            status,
+            message_id,
+            now,
+            puuid as _,
+            match_id as _,
+            guild_id as _
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_notifications(
+        &self,
+        status: model::NotificationStatus,
+    ) -> Result<Vec<model::Notification>, Error> {
+        sqlx::query_as!(
+            model::Notification,
+            r#"SELECT
+                puuid, match_id, guild_id AS "guild_id: _", level, message, message_id,
+                status AS "status: model::NotificationStatus", attempts, update_time
+            FROM notification WHERE status = ? ORDER BY update_time"#,
+            status
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_job(&self, kind: model::JobKind, match_id: &MatchId) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let status = model::JobStatus::Pending;
+        sqlx::query!(
+            "INSERT INTO job (kind, match_id, status, attempts, create_time, update_time)
+            VALUES (?, ?, ?, 0, ?, ?)
+            ON CONFLICT (kind, match_id) DO UPDATE
+            SET status = excluded.status, update_time = excluded.update_time
+            WHERE job.status IN ('failed', 'done')",
+            kind,
+            match_id as _,
+            status,
+            now,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_job(
+        &self,
+        kind: model::JobKind,
+        match_id: &MatchId,
+        status: model::JobStatus,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let attempt = i64::from(status == model::JobStatus::InFlight);
+        sqlx::query!(
+            "UPDATE job SET status = ?, attempts = attempts + ?, update_time = ?
+            WHERE kind = ? AND match_id = ?",
+            status,
+            attempt,
+            now,
+            kind,
+            match_id as _
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_jobs(&self, status: model::JobStatus) -> Result<Vec<model::Job>, Error> {
+        sqlx::query_as!(
+            model::Job,
+            r#"SELECT
+                kind AS "kind: model::JobKind", match_id, status AS "status: model::JobStatus",
+                attempts, create_time, update_time
+            FROM job WHERE status = ? ORDER BY create_time"#,
+            status
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn delete_jobs(&self, status: model::JobStatus) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        let jobs = sqlx::query!("DELETE FROM job WHERE status = ?", status)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        let league_jobs = sqlx::query!("DELETE FROM league_job WHERE status = ?", status)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(jobs + league_jobs)
+    }
+
+    async fn insert_league_job(&self, puuid: &Puuid, match_id: &MatchId) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let status = model::JobStatus::Pending;
+        sqlx::query!(
+            "INSERT INTO league_job (puuid, match_id, status, attempts, create_time, update_time)
+            VALUES (?, ?, ?, 0, ?, ?)
+            ON CONFLICT (puuid, match_id) DO UPDATE
+            SET status = excluded.status, update_time = excluded.update_time
+            WHERE league_job.status IN ('failed', 'done')",
+            puuid as _,
+            match_id as _,
+            status,
+            now,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_league_job(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+        status: model::JobStatus,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let attempt = i64::from(status == model::JobStatus::InFlight);
+        sqlx::query!(
+            "UPDATE league_job SET status = ?, attempts = attempts + ?, update_time = ?
+            WHERE puuid = ? AND match_id = ?",
+            status,
+            attempt,
+            now,
+            puuid as _,
+            match_id as _
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_league_jobs(
+        &self,
+        status: model::JobStatus,
+    ) -> Result<Vec<model::LeagueJob>, Error> {
+        sqlx::query_as!(
+            model::LeagueJob,
+            r#"SELECT
+                puuid, match_id, status AS "status: model::JobStatus", attempts, create_time,
+                update_time
+            FROM league_job WHERE status = ? ORDER BY create_time"#,
+            status
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_backfill(&self, puuid: &Puuid, guild_id: GuildId) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let status = model::BackfillStatus::Pending;
+        sqlx::query!(
+            "INSERT INTO backfill (puuid, guild_id, status, matches, create_time, update_time)
+            VALUES (?, ?, ?, 0, ?, ?)
+            ON CONFLICT (puuid) DO NOTHING",
+            puuid as _,
+            guild_id as _,
+            status,
+            now,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn update_backfill(
+        &self,
+        puuid: &Puuid,
+        status: model::BackfillStatus,
+        matches: i64,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "UPDATE backfill SET status = ?, matches = ?, update_time = ? WHERE puuid = ?",
+            status,
+            matches,
+            now,
+            puuid as _
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_backfills(
+        &self,
+        status: model::BackfillStatus,
+    ) -> Result<Vec<model::Backfill>, Error> {
+        sqlx::query_as!(
+            model::Backfill,
+            r#"SELECT
+                puuid, guild_id AS "guild_id: _", status AS "status: model::BackfillStatus",
+                matches, create_time, update_time
+            FROM backfill WHERE status = ? ORDER BY create_time"#,
+            status
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_dead_letter(
+        &self,
+        kind: model::DeadLetterKind,
+        key: &str,
+        error: &str,
+        attempts: i64,
+    ) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "INSERT INTO dead_letter
+                (kind, key, error, attempts, requeued, create_time, update_time)
+            VALUES (?, ?, ?, ?, FALSE, ?, ?)
+            ON CONFLICT (kind, key) DO UPDATE
+            SET error = excluded.error, attempts = excluded.attempts,
+                requeued = FALSE, update_time = excluded.update_time",
+            kind,
+            key,
+            error,
+            attempts,
+            now,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<model::DeadLetter>, Error> {
+        sqlx::query_as!(
+            model::DeadLetter,
+            r#"SELECT
+                kind AS "kind: model::DeadLetterKind", key, error, attempts, requeued,
+                create_time, update_time
+            FROM dead_letter ORDER BY update_time"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn requeue_dead_letters(
+        &self,
+        kind: Option<model::DeadLetterKind>,
+    ) -> Result<u64, Error> {
+        sqlx::query!(
+            "UPDATE dead_letter SET requeued = TRUE WHERE ? IS NULL OR kind = ?",
+            kind,
+            kind
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn take_requeued_dead_letters(&self) -> Result<Vec<model::DeadLetter>, Error> {
+        sqlx::query_as!(
+            model::DeadLetter,
+            r#"DELETE FROM dead_letter WHERE requeued
+            RETURNING
+                kind AS "kind: model::DeadLetterKind", key, error, attempts, requeued,
+                create_time, update_time"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_guild_queues(&self, guild_id: GuildId) -> Result<Vec<u16>, Error> {
+        let queue_ids = sqlx::query_scalar!(
+            "SELECT queue_id FROM guild_queue_filter WHERE guild_id = ? ORDER BY queue_id",
+            guild_id as _
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        if queue_ids.is_empty() {
+            return Ok(DEFAULT_QUEUE_IDS.to_vec());
+        }
+        Ok(queue_ids
+            .into_iter()
+            .map(|queue_id| queue_id as u16)
+            .collect())
+    }
+
+    async fn set_guild_queues(&self, guild_id: GuildId, queue_ids: &[u16]) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "DELETE FROM guild_queue_filter WHERE guild_id = ?",
+            guild_id as _
+        )
+        .execute(&mut *tx)
+        .await?;
+        let mut count = 0;
+        for queue_id in queue_ids {
+            count += sqlx::query!(
+                "INSERT INTO guild_queue_filter (guild_id, queue_id) VALUES (?, ?)
+                ON CONFLICT DO NOTHING",
+                guild_id as _,
+                queue_id
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(count)
+    }
+
+    async fn get_matches(&self, match_ids: &[MatchId]) -> Result<Vec<model::Match>, Error> {
+        let queue_parameters = match_ids
+            .iter()
+            .map(|_| "?")
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let raw_query = format!("SELECT * FROM match WHERE id IN ({queue_parameters})");
+        let mut query = sqlx::query_as(&raw_query);
+        for match_id in match_ids {
+            query = query.bind(match_id);
+        }
+        query.fetch_all(&self.pool).await.map_err(Error::SqlxError)
+    }
+
+    async fn get_match(&self, match_id: &MatchId) -> Result<Option<model::Match>, Error> {
+        sqlx::query_as!(model::Match, "SELECT * FROM match WHERE id = ?", match_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_summoner_latest_match(
+        &self,
+        puuid: &Puuid,
+    ) -> Result<Option<model::Match>, Error> {
+        sqlx::query_as!(model::Match,
+            "SELECT match.* FROM match INNER JOIN summoner_match ON match.id = summoner_match.match_id
+            WHERE summoner_match.puuid = ? ORDER BY start_time DESC LIMIT 1", puuid
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_prunable_match_ids(
+        &self,
+        started_before: DateTime<Utc>,
+    ) -> Result<Vec<MatchId>, Error> {
+        sqlx::query_scalar!(
+            r#"SELECT id AS "id: MatchId" FROM match WHERE start_time < ? AND NOT EXISTS (
+                SELECT 1 FROM notification WHERE notification.match_id = match.id
+            ) ORDER BY start_time"#,
+            started_before
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn delete_matches(&self, match_ids: &[MatchId]) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = delete_match_rows(&mut tx, match_ids).await?;
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn delete_pruned(&self, puuids: &[Puuid], match_ids: &[MatchId]) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        for puuids in puuids.chunks(PRUNE_BATCH_SIZE) {
+            delete_summoner_rows(&mut tx, puuids).await?;
+        }
+        for match_ids in match_ids.chunks(PRUNE_BATCH_SIZE) {
+            delete_match_rows(&mut tx, match_ids).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn ingest_match(&self, data: &Match, tracked_puuids: &[Puuid]) -> Result<u64, Error> {
+        let winning_team_id = get_winning_team(data)?;
+        let surrender = get_surrender(data)?;
+        let start_time = DateTime::from_timestamp_millis(data.info.game_start_timestamp)
+            .ok_or(Error::DateTimeOutOfRange)?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO match (id, start_time, duration, queue_id, game_version, game_mode, winning_team_id, surrender)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (id) DO UPDATE SET
+                start_time = excluded.start_time, duration = excluded.duration,
+                queue_id = excluded.queue_id, game_version = excluded.game_version,
+                game_mode = excluded.game_mode, winning_team_id = excluded.winning_team_id,
+                surrender = excluded.surrender",
+        )
+        .bind(&data.metadata.match_id)
+        .bind(start_time)
+        .bind(data.info.game_duration)
+        .bind(u16::from(data.info.queue_id))
+        .bind(&data.info.game_version)
+        .bind(data.info.game_mode.to_string())
+        .bind(winning_team_id)
+        .bind(surrender)
+        .execute(&mut *tx)
+        .await?;
+
+        // Participants are replaced as a whole so that re-ingesting stays consistent
+        sqlx::query("DELETE FROM match_participant WHERE match_id = ?")
+            .bind(&data.metadata.match_id)
+            .execute(&mut *tx)
+            .await?;
+        for participant in &data.info.participants {
+            sqlx::query(
+                "INSERT INTO match_participant (
+                    match_id, puuid, participant_id, team_id, game_name, tag, champion_id,
+                    champion_level, position, win, kills, deaths, assists, gold_earned, gold_spent,
+                    total_damage_dealt, total_damage_dealt_to_champions, total_damage_taken,
+                    damage_self_mitigated, vision_score, wards_placed, wards_killed,
+                    total_minions_killed, neutral_minions_killed, time_dead,
+                    item0, item1, item2, item3, item4, item5, item6, summoner1_id, summoner2_id)
+                VALUES (
+                    ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
+                    ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&data.metadata.match_id)
+            .bind(&participant.puuid)
+            .bind(participant.participant_id)
+            .bind(u16::from(participant.team_id))
+            .bind(&participant.riot_id_game_name)
+            .bind(&participant.riot_id_tagline)
+            .bind(i16::from(participant.champion()?))
+            .bind(participant.champ_level)
+            .bind(&participant.team_position)
+            .bind(participant.win)
+            .bind(participant.kills)
+            .bind(participant.deaths)
+            .bind(participant.assists)
+            .bind(participant.gold_earned)
+            .bind(participant.gold_spent)
+            .bind(participant.total_damage_dealt)
+            .bind(participant.total_damage_dealt_to_champions)
+            .bind(participant.total_damage_taken)
+            .bind(participant.damage_self_mitigated)
+            .bind(participant.vision_score)
+            .bind(participant.wards_placed)
+            .bind(participant.wards_killed)
+            .bind(participant.total_minions_killed)
+            .bind(participant.neutral_minions_killed)
+            .bind(participant.total_time_spent_dead)
+            .bind(participant.item0)
+            .bind(participant.item1)
+            .bind(participant.item2)
+            .bind(participant.item3)
+            .bind(participant.item4)
+            .bind(participant.item5)
+            .bind(participant.item6)
+            .bind(participant.summoner1_id)
+            .bind(participant.summoner2_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let mut count = 0;
+        for puuid in tracked_puuids {
+            let summoner_stats = get_participant(data, puuid)?;
+            count += sqlx::query(
+                "INSERT INTO summoner_match (puuid, match_id, kills, deaths, assists, champion_id, position, longest_time_living, time_dead, team_id)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (puuid, match_id) DO UPDATE SET
+                    kills = excluded.kills, deaths = excluded.deaths, assists = excluded.assists,
+                    champion_id = excluded.champion_id, position = excluded.position,
+                    longest_time_living = excluded.longest_time_living,
+                    time_dead = excluded.time_dead, team_id = excluded.team_id",
+            )
+            .bind(puuid)
+            .bind(&data.metadata.match_id)
+            .bind(summoner_stats.kills)
+            .bind(summoner_stats.deaths)
+            .bind(summoner_stats.assists)
+            .bind(i16::from(summoner_stats.champion()?))
+            .bind(&summoner_stats.team_position)
+            .bind(summoner_stats.longest_time_spent_living)
+            .bind(summoner_stats.total_time_spent_dead)
+            .bind(u16::from(summoner_stats.team_id))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(count)
+    }
+
+    async fn archive_match(&self, match_id: &MatchId, payload: &[u8]) -> Result<u64, Error> {
+        let now = Utc::now().naive_utc();
+        let bytes = archive::encode(payload)?;
+        sqlx::query!(
+            "INSERT INTO match_archive (match_id, data, archive_time) VALUES (?, ?, ?)
+            ON CONFLICT (match_id) DO UPDATE SET data = excluded.data, archive_time = excluded.archive_time",
+            match_id as _,
+            bytes,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_archived_payload(&self, match_id: &MatchId) -> Result<Option<Vec<u8>>, Error> {
+        let row = sqlx::query!(
+            "SELECT data FROM match_archive WHERE match_id = ?",
+            match_id as _
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        row.map(|row| archive::decode(&row.data)).transpose()
+    }
+
+    async fn get_archived_match_ids(&self) -> Result<Vec<MatchId>, Error> {
+        sqlx::query_scalar!(
+            r#"SELECT match_id AS "match_id: MatchId" FROM match_archive ORDER BY archive_time"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_match_participants(
+        &self,
+        match_id: &MatchId,
+    ) -> Result<Vec<model::MatchParticipant>, Error> {
+        sqlx::query_as!(
+            model::MatchParticipant,
+            "SELECT * FROM match_participant WHERE match_id = ? ORDER BY participant_id",
+            match_id as _
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_lane_opponent(
+        &self,
+        match_id: &MatchId,
+        puuid: &Puuid,
+    ) -> Result<Option<model::MatchParticipant>, Error> {
+        sqlx::query_as(
+            "SELECT opponent.* FROM match_participant AS opponent
+            INNER JOIN match_participant AS participant ON participant.match_id = opponent.match_id
+            WHERE participant.match_id = ? AND participant.puuid = ?
+                AND opponent.position = participant.position AND opponent.team_id != participant.team_id",
+        )
+        .bind(match_id)
+        .bind(puuid)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn ingest_timeline(
+        &self,
+        data: &Timeline,
+        tracked_puuids: &[Puuid],
+    ) -> Result<u64, Error> {
+        let deaths = timeline::get_deaths(data, tracked_puuids)?;
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM death_event_assist WHERE match_id = ?")
+            .bind(&data.metadata.match_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM death_event WHERE match_id = ?")
+            .bind(&data.metadata.match_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let mut count = 0;
+        for death in deaths {
+            count += sqlx::query(
+                "INSERT INTO death_event (match_id, puuid, timestamp, killer_puuid, position_x, position_y)
+                VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&data.metadata.match_id)
+            .bind(death.puuid)
+            .bind(death.timestamp)
+            .bind(death.killer_puuid)
+            .bind(death.position.map(|(x, _)| i64::from(x)))
+            .bind(death.position.map(|(_, y)| i64::from(y)))
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            for assister_puuid in death.assister_puuids {
+                sqlx::query(
+                    "INSERT INTO death_event_assist (match_id, puuid, timestamp, assister_puuid)
+                    VALUES (?, ?, ?, ?)",
+                )
+                .bind(&data.metadata.match_id)
+                .bind(death.puuid)
+                .bind(death.timestamp)
+                .bind(assister_puuid)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(count)
+    }
+
+    async fn get_death_events(
+        &self,
+        match_id: &MatchId,
+        puuid: &Puuid,
+    ) -> Result<Vec<model::DeathEvent>, Error> {
+        let mut death_events: Vec<model::DeathEvent> = sqlx::query_as(
+            "SELECT * FROM death_event WHERE match_id = ? AND puuid = ? ORDER BY timestamp",
+        )
+        .bind(match_id)
+        .bind(puuid)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let assists: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT timestamp, assister_puuid FROM death_event_assist
+            WHERE match_id = ? AND puuid = ? ORDER BY assister_puuid",
+        )
+        .bind(match_id)
+        .bind(puuid)
+        .fetch_all(&self.pool)
+        .await?;
+        for (timestamp, assister_puuid) in assists {
+            if let Some(death_event) = death_events
+                .iter_mut()
+                .find(|death_event| death_event.timestamp == timestamp)
+            {
+                death_event.assister_puuids.push(assister_puuid.into());
+            }
+        }
+        Ok(death_events)
+    }
+
+    async fn get_summoner_match(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+    ) -> Result<Option<model::SummonerMatch>, Error> {
+        sqlx::query_as!(
+            model::SummonerMatch,
+            "SELECT * FROM summoner_match WHERE puuid = ? AND match_id = ?",
+            puuid as _,
+            match_id as _
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn insert_rank_snapshot(&self, snapshot: &model::RankSnapshot) -> Result<u64, Error> {
+        sqlx::query!(
+            "INSERT INTO rank_snapshot (
+                puuid, match_id, queue_type, tier, division, league_points, lp_delta,
+                snapshot_time)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (puuid, match_id) DO NOTHING",
+            snapshot.puuid,
+            snapshot.match_id,
+            snapshot.queue_type,
+            snapshot.tier,
+            snapshot.division,
+            snapshot.league_points,
+            snapshot.lp_delta,
+            snapshot.snapshot_time
+        )
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_rank_snapshot(
+        &self,
+        puuid: &Puuid,
+        match_id: &MatchId,
+    ) -> Result<Option<model::RankSnapshot>, Error> {
+        sqlx::query_as!(
+            model::RankSnapshot,
+            "SELECT * FROM rank_snapshot WHERE puuid = ? AND match_id = ?",
+            puuid as _,
+            match_id as _
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_latest_rank_snapshot(
+        &self,
+        puuid: &Puuid,
+        queue_type: &str,
+    ) -> Result<Option<model::RankSnapshot>, Error> {
+        sqlx::query_as!(
+            model::RankSnapshot,
+            "SELECT * FROM rank_snapshot WHERE puuid = ? AND queue_type = ?
+            ORDER BY snapshot_time DESC LIMIT 1",
+            puuid as _,
+            queue_type
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(Error::SqlxError)
+    }
+
+    async fn get_leaderboard(
+        &self,
+        query: &LeaderboardQuery,
+    ) -> Result<Vec<model::LeaderboardEntry>, Error> {
+        // TODO: Only get ints?
+        let guild_queue_ids = match query.queue_ids {
+            Some(_) => Vec::new(),
+            None => self.get_guild_queues(query.guild_id).await?,
+        };
+        query
+            .build(Utc::now(), &guild_queue_ids)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn export_dump(&self) -> Result<Dump, Error> {
+        Ok(Dump {
+            guilds: sqlx::query_as!(
+                model::Guild,
+                r#"SELECT id AS "id: _", channel_id AS "channel_id: _" FROM guild ORDER BY id"#
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            summoners: sqlx::query_as!(model::Summoner, "SELECT * FROM summoner ORDER BY puuid")
+                .fetch_all(&self.pool)
+                .await?,
+            matches: sqlx::query_as!(model::Match, "SELECT * FROM match ORDER BY id")
+                .fetch_all(&self.pool)
+                .await?,
+            summoner_matches: sqlx::query_as!(
+                model::SummonerMatch,
+                "SELECT * FROM summoner_match ORDER BY puuid, match_id"
+            )
+            .fetch_all(&self.pool)
+            .await?,
+            guild_followings: sqlx::query_as!(
+                model::GuildFollowing,
+                r#"SELECT guild_id AS "guild_id: _", puuid FROM guild_following
+                ORDER BY guild_id, puuid"#
+            )
+            .fetch_all(&self.pool)
+            .await?,
+        })
+    }
+
+    async fn insert_dump(&self, dump: &Dump) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut inserted = 0;
+
+        for guild in &dump.guilds {
+            inserted += sqlx::query!(
+                "INSERT OR IGNORE INTO guild (id, channel_id) VALUES (?, ?)",
+                guild.id,
+                guild.channel_id
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        for summoner in &dump.summoners {
+            inserted += sqlx::query!(
+                "INSERT OR IGNORE INTO summoner (puuid, game_name, tag, create_time, region)
+                VALUES (?, ?, ?, ?, ?)",
+                summoner.puuid,
+                summoner.game_name,
+                summoner.tag,
+                summoner.create_time,
+                summoner.region
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        for data in &dump.matches {
+            // Start times are written by ingestion as RFC 3339, which comparisons rely on
+            let start_time = data.start_time.and_utc();
+            inserted += sqlx::query!(
+                "INSERT OR IGNORE INTO match (
+                    id, start_time, duration, queue_id, game_version, game_mode,
+                    winning_team_id, surrender)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                data.id,
+                start_time,
+                data.duration,
+                data.queue_id,
+                data.game_version,
+                data.game_mode,
+                data.winning_team_id,
+                data.surrender
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        for row in &dump.summoner_matches {
+            inserted += sqlx::query!(
+                "INSERT OR IGNORE INTO summoner_match (
+                    puuid, match_id, kills, deaths, assists, champion_id, position,
+                    longest_time_living, time_dead, team_id)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                row.puuid,
+                row.match_id,
+                row.kills,
+                row.deaths,
+                row.assists,
+                row.champion_id,
+                row.position,
+                row.longest_time_living,
+                row.time_dead,
+                row.team_id
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        for row in &dump.guild_followings {
+            inserted += sqlx::query!(
+                "INSERT OR IGNORE INTO guild_following (guild_id, puuid) VALUES (?, ?)",
+                row.guild_id,
+                row.puuid
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(inserted)
+    }
+
+    async fn get_summoner_stats(
+        &self,
+        query: &StatsQuery,
+    ) -> Result<Option<model::SummonerAggregateStats>, Error> {
+        query
+            .build(None, START_MONTH)
+            .build_query_as()
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+
+    async fn get_summoner_grouped_stats(
+        &self,
+        query: &StatsQuery,
+        grouping: StatsGrouping,
+    ) -> Result<Vec<model::SummonerGroupStats>, Error> {
+        query
+            .build(Some(grouping), START_MONTH)
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(Error::SqlxError)
+    }
+}
+
+async fn delete_summoner_rows(conn: &mut SqliteConnection, puuids: &[Puuid]) -> Result<u64, Error> {
+    let puuid_parameters = puuids.iter().map(|_| "?").collect::<Vec<&str>>().join(", ");
+    let raw_query = format!("DELETE FROM summoner WHERE puuid IN ({puuid_parameters})");
+    let mut query = sqlx::query(&raw_query);
+    for puuid in puuids {
+        query = query.bind(puuid);
+    }
+    query
+        .execute(conn)
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(Error::SqlxError)
+}
+
+async fn delete_match_rows(
+    conn: &mut SqliteConnection,
+    match_ids: &[MatchId],
+) -> Result<u64, Error> {
+    let match_parameters = match_ids
+        .iter()
+        .map(|_| "?")
+        .collect::<Vec<&str>>()
+        .join(", ");
+
+    // Archived payloads are not tied to the match row, so delete them separately
+    let raw_query = format!("DELETE FROM match_archive WHERE match_id IN ({match_parameters})");
+    let mut query = sqlx::query(&raw_query);
+    for match_id in match_ids {
+        query = query.bind(match_id);
+    }
+    query.execute(&mut *conn).await?;
+
+    let raw_query = format!("DELETE FROM match WHERE id IN ({match_parameters})");
+    let mut query = sqlx::query(&raw_query);
+    for match_id in match_ids {
+        query = query.bind(match_id);
+    }
+    Ok(query.execute(conn).await?.rows_affected())
+}
@@ -0,0 +1,154 @@
+//! Filters and groupings of summoner statistics.
+
+use crate::push_in;
+use chrono::{DateTime, Utc};
+use sqlx::{Database, Encode, QueryBuilder, Type};
+
+/// What summoner matches are grouped by when aggregating statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsGrouping {
+    /// By champion ID.
+    Champion,
+    /// By position as given by the Riot API (e.g. `"MIDDLE"`).
+    Position,
+    /// By queue ID.
+    Queue,
+    /// By the month that the match started in, formatted as `YYYY-MM`.
+    Month,
+}
+
+/// Query for the aggregate statistics of a summoner, looked up by name and tag:
+///
+/// ```
+/// use the_collector_db::stats::StatsQuery;
+/// use the_collector_db::DEFAULT_QUEUE_IDS;
+///
+/// let query = StatsQuery::new("Player", "NA1", DEFAULT_QUEUE_IDS).position("MIDDLE");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsQuery {
+    pub name: String,
+    pub tag: String,
+    pub queue_ids: Vec<u16>,
+    /// Position as given by the Riot API (e.g. `"MIDDLE"`).
+    pub position: Option<String>,
+    pub champion_id: Option<i16>,
+    /// Only include matches that started at or after this time.
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl StatsQuery {
+    /// Create a query for all of the summoner's matches in the given queues.
+    pub fn new(
+        name: impl Into<String>,
+        tag: impl Into<String>,
+        queue_ids: impl Into<Vec<u16>>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            tag: tag.into(),
+            queue_ids: queue_ids.into(),
+            position: None,
+            champion_id: None,
+            since: None,
+        }
+    }
+
+    pub fn position(mut self, position: impl Into<String>) -> Self {
+        self.position = Some(position.into());
+        self
+    }
+
+    pub fn champion(mut self, champion_id: i16) -> Self {
+        self.champion_id = Some(champion_id);
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Build the SQL aggregating the summoner's matches, with one row per group if a
+    /// grouping is given. Groups are ordered by most deaths per game first.
+    ///
+    /// Formatting dates is not standard SQL, so `month` is the database's expression
+    /// for the month of `match.start_time` as `YYYY-MM`.
+    pub(crate) fn build<'a, DB: Database>(
+        &'a self,
+        grouping: Option<StatsGrouping>,
+        month: &str,
+    ) -> QueryBuilder<'a, DB>
+    where
+        i64: Encode<'a, DB> + Type<DB>,
+        DateTime<Utc>: Encode<'a, DB> + Type<DB>,
+        &'a str: Encode<'a, DB> + Type<DB>,
+    {
+        let group_key = match grouping {
+            None => "NULL",
+            Some(StatsGrouping::Champion) => "CAST(summoner_match.champion_id AS TEXT)",
+            Some(StatsGrouping::Position) => "summoner_match.position",
+            Some(StatsGrouping::Queue) => "CAST(match.queue_id AS TEXT)",
+            Some(StatsGrouping::Month) => month,
+        };
+
+        // Postgres sums BIGINT columns as NUMERIC, so cast back
+        let mut builder = QueryBuilder::new(format!(
+            "SELECT
+                summoner.game_name, summoner.tag, {group_key} AS group_key,
+                COUNT(*) AS num_matches,
+                CAST(SUM(CASE WHEN summoner_match.team_id = match.winning_team_id
+                    THEN 1 ELSE 0 END) AS BIGINT) AS wins,
+                CAST(SUM(summoner_match.kills) AS BIGINT) AS kills,
+                CAST(SUM(summoner_match.deaths) AS BIGINT) AS deaths,
+                CAST(SUM(summoner_match.assists) AS BIGINT) AS assists,
+                CAST(SUM(match.duration) AS BIGINT) AS total_duration,
+                CAST(SUM(summoner_match.time_dead) AS BIGINT) AS total_time_dead,
+                CAST(SUM(rank_snapshot.lp_delta) AS BIGINT) AS lp_delta
+            FROM summoner_match
+            INNER JOIN summoner ON summoner_match.puuid = summoner.puuid
+            INNER JOIN match ON summoner_match.match_id = match.id
+            LEFT JOIN rank_snapshot ON summoner_match.puuid = rank_snapshot.puuid
+                AND summoner_match.match_id = rank_snapshot.match_id
+            WHERE summoner.puuid = COALESCE(
+                (SELECT puuid FROM summoner WHERE game_name = "
+        ));
+        builder.push_bind(self.name.as_str());
+        builder.push(" AND tag = ");
+        builder.push_bind(self.tag.as_str());
+        builder.push("), (SELECT puuid FROM summoner_name_history WHERE game_name = ");
+        builder.push_bind(self.name.as_str());
+        builder.push(" AND tag = ");
+        builder.push_bind(self.tag.as_str());
+        builder.push(" ORDER BY change_time DESC LIMIT 1))");
+
+        builder.push(" AND ");
+        push_in(
+            &mut builder,
+            "match.queue_id",
+            self.queue_ids.iter().map(|queue_id| i64::from(*queue_id)),
+        );
+
+        if let Some(position) = &self.position {
+            builder.push(" AND summoner_match.position = ");
+            builder.push_bind(position.as_str());
+        }
+        if let Some(champion_id) = self.champion_id {
+            builder.push(" AND summoner_match.champion_id = ");
+            builder.push_bind(i64::from(champion_id));
+        }
+        if let Some(since) = self.since {
+            builder.push(" AND match.start_time >= ");
+            builder.push_bind(since);
+        }
+
+        builder.push(" GROUP BY summoner.game_name, summoner.tag");
+        if grouping.is_some() {
+            builder.push(format!(
+                ", {group_key} ORDER BY SUM(summoner_match.deaths) * 1.0 / COUNT(*) DESC, \
+                COUNT(*) DESC, group_key"
+            ));
+        }
+        builder
+    }
+}
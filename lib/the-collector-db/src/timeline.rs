@@ -0,0 +1,68 @@
+//! Extraction of events from match-v5 timelines.
+
+use crate::error::Error;
+use riven::models::match_v5::Timeline;
+use the_collector_types::Puuid;
+
+/// Event type of a champion being killed.
+const CHAMPION_KILL: &str = "CHAMPION_KILL";
+
+/// A death of a participant, with participant IDs resolved to PUUIDs.
+#[derive(Debug)]
+pub(crate) struct Death<'a> {
+    pub puuid: &'a str,
+    /// Milliseconds since the start of the match.
+    pub timestamp: i64,
+    /// [`None`] if the participant was not killed by a champion (e.g. executed by a turret).
+    pub killer_puuid: Option<&'a str>,
+    pub position: Option<(i32, i32)>,
+    pub assister_puuids: Vec<&'a str>,
+}
+
+/// Get the deaths of each tracked PUUID in a timeline, in the order they happened.
+pub(crate) fn get_deaths<'a>(
+    data: &'a Timeline,
+    tracked_puuids: &[Puuid],
+) -> Result<Vec<Death<'a>>, Error> {
+    let mut deaths = Vec::new();
+    let events = data.info.frames.iter().flat_map(|frame| &frame.events);
+    for event in events.filter(|event| event.r#type == CHAMPION_KILL) {
+        let victim_id = event.victim_id.ok_or(Error::MissingData("Victim".into()))?;
+        let puuid = get_puuid(data, victim_id).ok_or(Error::MissingData("Victim PUUID".into()))?;
+        if !tracked_puuids.iter().any(|tracked| tracked == puuid) {
+            continue;
+        }
+
+        deaths.push(Death {
+            puuid,
+            timestamp: event.timestamp,
+            killer_puuid: event.killer_id.and_then(|id| get_puuid(data, id)),
+            position: event
+                .position
+                .as_ref()
+                .map(|position| (position.x, position.y)),
+            assister_puuids: event
+                .assisting_participant_ids
+                .iter()
+                .flatten()
+                .filter_map(|id| get_puuid(data, *id))
+                .collect(),
+        });
+    }
+    Ok(deaths)
+}
+
+/// Resolve a participant ID to a PUUID. Participant IDs start at 1, while an ID of 0
+/// refers to something other than a champion (e.g. a minion or turret).
+fn get_puuid(data: &Timeline, participant_id: i32) -> Option<&str> {
+    match &data.info.participants {
+        Some(participants) => participants
+            .iter()
+            .find(|participant| participant.participant_id == participant_id)
+            .map(|participant| participant.puuid.as_str()),
+        None => usize::try_from(participant_id - 1)
+            .ok()
+            .and_then(|index| data.metadata.participants.get(index))
+            .map(String::as_str),
+    }
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use riven::consts::PlatformRoute;
+use the_collector_db::model::BackfillStatus;
+use the_collector_types::{GuildId, Puuid};
+
+#[tokio::test]
+async fn test_backfills() {
+    for db_handler in common::db_handlers().await {
+        let account = common::account(0);
+        let puuid = Puuid::from(&account);
+        db_handler
+            .insert_summoner(&account, PlatformRoute::NA1)
+            .await
+            .unwrap();
+        for guild_id in [1234, 1235].map(GuildId::new) {
+            db_handler.insert_guild(guild_id).await.unwrap();
+        }
+
+        // Only the first guild to follow a summoner requests a backfill
+        let count = db_handler
+            .insert_backfill(&puuid, GuildId::new(1234))
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        let count = db_handler
+            .insert_backfill(&puuid, GuildId::new(1235))
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+        let pending = db_handler
+            .get_backfills(BackfillStatus::Pending)
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].puuid, puuid);
+        assert_eq!(pending[0].guild_id, Some(GuildId::new(1234)));
+        assert_eq!(pending[0].matches, 0);
+
+        let count = db_handler
+            .update_backfill(&puuid, BackfillStatus::InProgress, 100)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(db_handler
+            .get_backfills(BackfillStatus::Pending)
+            .await
+            .unwrap()
+            .is_empty());
+        let in_progress = db_handler
+            .get_backfills(BackfillStatus::InProgress)
+            .await
+            .unwrap();
+        assert_eq!(in_progress[0].matches, 100);
+
+        // Backfills outlive the guild that requested them
+        db_handler.delete_guild(GuildId::new(1234)).await.unwrap();
+        let in_progress = db_handler
+            .get_backfills(BackfillStatus::InProgress)
+            .await
+            .unwrap();
+        assert_eq!(in_progress[0].guild_id, None);
+        let count = db_handler
+            .update_backfill(&Puuid::from("puuid-1"), BackfillStatus::Done, 0)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
@@ -0,0 +1,111 @@
+use riven::models::account_v1::Account;
+use riven::models::match_v5::{Match, Timeline};
+use the_collector_db::sqlite::SqliteStorage;
+use the_collector_db::{DbHandler, SqlitePoolOptions};
+
+/// Match ID of the match in `fixtures/match.json`.
+#[allow(dead_code)]
+pub const MATCH_ID: &str = "NA1_5000000001";
+
+/// Create a migrated, empty [`DbHandler`] for every backend that tests should run against.
+///
+/// SQLite is always included, using an in-memory database. When built with the `postgres`
+/// feature and `TEST_POSTGRES_URL` is set, a Postgres backend is included as well, using a
+/// schema unique to the caller so that tests can run concurrently.
+#[allow(dead_code)]
+pub async fn db_handlers() -> Vec<DbHandler> {
+    let db_handlers: Vec<DbHandler> =
+        [Some(sqlite_db_handler().await), postgres_db_handler().await]
+            .into_iter()
+            .flatten()
+            .collect();
+    for db_handler in &db_handlers {
+        db_handler.migrate().await.unwrap();
+    }
+    db_handlers
+}
+
+async fn sqlite_db_handler() -> DbHandler {
+    // In-memory databases are per-connection, so limit the pool to one
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    DbHandler::new(SqliteStorage::new(pool))
+}
+
+#[cfg(not(feature = "postgres"))]
+async fn postgres_db_handler() -> Option<DbHandler> {
+    None
+}
+
+#[cfg(feature = "postgres")]
+async fn postgres_db_handler() -> Option<DbHandler> {
+    use sqlx::postgres::PgConnectOptions;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use the_collector_db::postgres::PostgresStorage;
+    use the_collector_db::PgPoolOptions;
+
+    static SCHEMA_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let url = std::env::var("TEST_POSTGRES_URL").ok()?;
+    let schema = format!(
+        "test_{}_{}",
+        std::process::id(),
+        SCHEMA_COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+
+    let setup_pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&url)
+        .await
+        .unwrap();
+    sqlx::query(&format!("DROP SCHEMA IF EXISTS {schema} CASCADE"))
+        .execute(&setup_pool)
+        .await
+        .unwrap();
+    sqlx::query(&format!("CREATE SCHEMA {schema}"))
+        .execute(&setup_pool)
+        .await
+        .unwrap();
+
+    let options = url
+        .parse::<PgConnectOptions>()
+        .unwrap()
+        .options([("search_path", &schema)]);
+    let pool = PgPoolOptions::new()
+        .max_connections(2)
+        .connect_with(options)
+        .await
+        .unwrap();
+    Some(DbHandler::new(PostgresStorage::new(pool)))
+}
+
+/// Load the match in `fixtures/match.json`.
+#[allow(dead_code)]
+pub fn load_match() -> Match {
+    serde_json::from_str(include_str!("../fixtures/match.json")).unwrap()
+}
+
+/// Load the raw payload in `fixtures/match.json`, as returned by the API.
+#[allow(dead_code)]
+pub fn load_match_payload() -> &'static [u8] {
+    include_bytes!("../fixtures/match.json")
+}
+
+/// Load the timeline of the match in `fixtures/match.json`.
+#[allow(dead_code)]
+pub fn load_timeline() -> Timeline {
+    serde_json::from_str(include_str!("../fixtures/timeline.json")).unwrap()
+}
+
+/// Create an [`Account`] for one of the participants of the fixture match.
+#[allow(dead_code)]
+pub fn account(index: usize) -> Account {
+    Account {
+        puuid: format!("puuid-{index}"),
+        game_name: Some(format!("Player{index}")),
+        tag_line: Some("NA1".into()),
+    }
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::MATCH_ID;
+use the_collector_db::model::DeadLetterKind;
+
+#[tokio::test]
+async fn test_dead_letters() {
+    for db_handler in common::db_handlers().await {
+        db_handler
+            .insert_dead_letter(DeadLetterKind::MatchData, MATCH_ID, "Server error", 5)
+            .await
+            .unwrap();
+        db_handler
+            .insert_dead_letter(DeadLetterKind::Account, "NA1:puuid-0", "Not found", 1)
+            .await
+            .unwrap();
+
+        // Failing again replaces the dead letter
+        db_handler
+            .insert_dead_letter(DeadLetterKind::MatchData, MATCH_ID, "Timed out", 3)
+            .await
+            .unwrap();
+        let dead_letters = db_handler.get_dead_letters().await.unwrap();
+        assert_eq!(dead_letters.len(), 2);
+        let match_data = dead_letters
+            .iter()
+            .find(|dead_letter| dead_letter.kind == DeadLetterKind::MatchData)
+            .unwrap();
+        assert_eq!(match_data.key, MATCH_ID);
+        assert_eq!(match_data.error, "Timed out");
+        assert_eq!(match_data.attempts, 3);
+        assert!(!match_data.requeued);
+
+        // Only requeued dead letters are taken
+        assert!(db_handler
+            .take_requeued_dead_letters()
+            .await
+            .unwrap()
+            .is_empty());
+        let count = db_handler
+            .requeue_dead_letters(Some(DeadLetterKind::Account))
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        let requeued = db_handler.take_requeued_dead_letters().await.unwrap();
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].key, "NA1:puuid-0");
+        assert!(requeued[0].requeued);
+
+        let count = db_handler.requeue_dead_letters(None).await.unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            db_handler.take_requeued_dead_letters().await.unwrap().len(),
+            1
+        );
+        assert!(db_handler.get_dead_letters().await.unwrap().is_empty());
+    }
+}
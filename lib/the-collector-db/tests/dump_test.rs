@@ -0,0 +1,105 @@
+mod common;
+
+use common::{account, load_match, MATCH_ID};
+use riven::consts::PlatformRoute;
+use the_collector_db::dump::{Dump, DumpFormat};
+use the_collector_db::leaderboard::LeaderboardQuery;
+use the_collector_db::DbHandler;
+use the_collector_types::{ChannelId, GuildId, Puuid};
+
+const GUILD_ID: GuildId = GuildId::new(1234);
+
+async fn populate(db_handler: &DbHandler) {
+    db_handler.insert_guild(GUILD_ID).await.unwrap();
+    db_handler
+        .update_channel(GUILD_ID, Some(ChannelId::new(42)))
+        .await
+        .unwrap();
+    db_handler.insert_guild(GuildId::new(1235)).await.unwrap();
+    let mut tracked_puuids = Vec::new();
+    for index in [0, 3] {
+        let account = account(index);
+        db_handler
+            .insert_summoner(&account, PlatformRoute::NA1)
+            .await
+            .unwrap();
+        db_handler
+            .insert_guild_following(GUILD_ID, &Puuid::from(&account))
+            .await
+            .unwrap();
+        tracked_puuids.push(account.puuid.into());
+    }
+    db_handler
+        .ingest_match(&load_match(), &tracked_puuids)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_export_and_import() {
+    for format in [DumpFormat::JsonLines, DumpFormat::Csv] {
+        let dir = std::env::temp_dir().join(format!(
+            "the-collector-dump-{}-{format:?}",
+            std::process::id()
+        ));
+        for (source, target) in common::db_handlers()
+            .await
+            .into_iter()
+            .zip(common::db_handlers().await)
+        {
+            populate(&source).await;
+            let exported = source.export_dump().await.unwrap();
+            assert_eq!(exported.guilds.len(), 2);
+            assert_eq!(exported.summoner_matches.len(), 2);
+            exported.write(&dir, format).unwrap();
+
+            let dump = Dump::read(&dir, format).unwrap();
+            assert_eq!(target.import_dump(&dump).await.unwrap(), 2 + 2 + 1 + 2 + 2);
+            // Importing again changes nothing
+            assert_eq!(target.import_dump(&dump).await.unwrap(), 0);
+
+            let imported = target.export_dump().await.unwrap();
+            assert_eq!(
+                serde_json::to_value(&imported).unwrap(),
+                serde_json::to_value(&exported).unwrap(),
+                "{format:?}"
+            );
+
+            // Imported matches behave as if they were ingested
+            assert_eq!(
+                target
+                    .get_leaderboard(&LeaderboardQuery::new(GUILD_ID))
+                    .await
+                    .unwrap()
+                    .len(),
+                2
+            );
+            assert!(target.get_match(&MATCH_ID.into()).await.unwrap().is_some());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_import_invalid_dump() {
+    for (source, target) in common::db_handlers()
+        .await
+        .into_iter()
+        .zip(common::db_handlers().await)
+    {
+        populate(&source).await;
+
+        let mut dump = source.export_dump().await.unwrap();
+        dump.matches.clear();
+        assert!(target.import_dump(&dump).await.is_err());
+
+        let mut dump = source.export_dump().await.unwrap();
+        let duplicate = source.export_dump().await.unwrap().guilds.remove(0);
+        dump.guilds.push(duplicate);
+        assert!(target.import_dump(&dump).await.is_err());
+
+        // Nothing is imported from an invalid dump
+        let imported = target.export_dump().await.unwrap();
+        assert!(imported.guilds.is_empty() && imported.summoners.is_empty());
+    }
+}
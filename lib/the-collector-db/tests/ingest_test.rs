@@ -0,0 +1,207 @@
+mod common;
+
+use common::{account, load_match, load_match_payload, MATCH_ID};
+use riven::consts::PlatformRoute;
+use the_collector_db::stats::StatsQuery;
+use the_collector_db::DEFAULT_QUEUE_IDS;
+use the_collector_types::MatchId;
+
+#[tokio::test]
+async fn test_ingest_match_is_idempotent() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        let mut tracked_puuids = Vec::new();
+        for index in [0, 3] {
+            let account = account(index);
+            db_handler
+                .insert_summoner(&account, PlatformRoute::NA1)
+                .await
+                .unwrap();
+            tracked_puuids.push(account.puuid.into());
+        }
+
+        for _ in 0..2 {
+            let count = db_handler
+                .ingest_match(&match_data, &tracked_puuids)
+                .await
+                .unwrap();
+            assert_eq!(count, 2);
+        }
+
+        let stats = db_handler
+            .get_summoner_stats(&StatsQuery::new("Player3", "NA1", DEFAULT_QUEUE_IDS))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stats.num_matches, 1);
+        assert_eq!(stats.deaths, 14);
+    }
+}
+
+#[tokio::test]
+async fn test_ingest_match_failure_writes_nothing() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        db_handler
+            .insert_summoner(&account(0), PlatformRoute::NA1)
+            .await
+            .unwrap();
+
+        // The second PUUID did not participate, so ingestion fails after the match
+        // row and the first summoner match have been written
+        let result = db_handler
+            .ingest_match(&match_data, &["puuid-0".into(), "puuid-missing".into()])
+            .await;
+        assert!(result.is_err());
+        assert!(db_handler
+            .get_match(&MATCH_ID.into())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db_handler
+            .get_summoner_match(&"puuid-0".into(), &MATCH_ID.into())
+            .await
+            .unwrap()
+            .is_none());
+
+        // Retrying with the correct PUUIDs then succeeds
+        let count = db_handler
+            .ingest_match(&match_data, &["puuid-0".into()])
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert!(db_handler
+            .get_match(&MATCH_ID.into())
+            .await
+            .unwrap()
+            .is_some());
+        assert!(db_handler
+            .get_summoner_match(&"puuid-0".into(), &MATCH_ID.into())
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
+
+#[tokio::test]
+async fn test_ingest_match_fills_in_missing_summoner_matches() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        db_handler
+            .insert_summoner(&account(0), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        db_handler
+            .insert_summoner(&account(5), PlatformRoute::NA1)
+            .await
+            .unwrap();
+
+        // A match previously stored without all of its tracked summoner matches
+        db_handler
+            .ingest_match(&match_data, &["puuid-0".into()])
+            .await
+            .unwrap();
+        assert!(db_handler
+            .get_summoner_match(&"puuid-5".into(), &MATCH_ID.into())
+            .await
+            .unwrap()
+            .is_none());
+
+        db_handler
+            .ingest_match(&match_data, &["puuid-0".into(), "puuid-5".into()])
+            .await
+            .unwrap();
+        let summoner_match = db_handler
+            .get_summoner_match(&"puuid-5".into(), &MATCH_ID.into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summoner_match.kills, 7);
+    }
+}
+
+#[tokio::test]
+async fn test_reprocess_archived_match() {
+    for db_handler in common::db_handlers().await {
+        assert_eq!(
+            db_handler
+                .archive_match(&MATCH_ID.into(), load_match_payload())
+                .await
+                .unwrap(),
+            1
+        );
+        let archived = db_handler
+            .get_archived_match(&MATCH_ID.into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(archived.metadata.match_id, MATCH_ID);
+        assert_eq!(archived.info.participants.len(), 10);
+        assert_eq!(
+            db_handler.get_archived_match_ids().await.unwrap(),
+            vec![MatchId::from(MATCH_ID)]
+        );
+
+        // Summoners followed after the match was archived get their stats rebuilt
+        db_handler
+            .insert_summoner(&account(2), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        assert_eq!(
+            db_handler.reprocess_match(&MATCH_ID.into()).await.unwrap(),
+            1
+        );
+        assert!(db_handler
+            .get_match(&MATCH_ID.into())
+            .await
+            .unwrap()
+            .is_some());
+        let summoner_match = db_handler
+            .get_summoner_match(&"puuid-2".into(), &MATCH_ID.into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summoner_match.kills, 8);
+
+        db_handler
+            .insert_summoner(&account(7), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        assert_eq!(db_handler.reprocess_archive().await.unwrap(), 1);
+        assert!(db_handler
+            .get_summoner_match(&"puuid-7".into(), &MATCH_ID.into())
+            .await
+            .unwrap()
+            .is_some());
+
+        assert!(db_handler.reprocess_match(&"NA1_0".into()).await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn test_archive_keeps_unknown_fields() {
+    // Add a field that riven does not model, as the API may at any time
+    let mut payload: serde_json::Value = serde_json::from_slice(load_match_payload()).unwrap();
+    payload["info"]["newField"] = serde_json::json!({"value": 1});
+    let payload = serde_json::to_vec(&payload).unwrap();
+    for db_handler in common::db_handlers().await {
+        db_handler
+            .archive_match(&MATCH_ID.into(), &payload)
+            .await
+            .unwrap();
+        let archived = db_handler
+            .get_archived_payload(&MATCH_ID.into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(archived, payload);
+
+        // The payload can still be parsed for reprocessing
+        let archived = db_handler
+            .get_archived_match(&MATCH_ID.into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(archived.metadata.match_id, MATCH_ID);
+    }
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use common::MATCH_ID;
+use the_collector_db::model::{JobKind, JobStatus};
+use the_collector_types::{MatchId, Puuid};
+
+#[tokio::test]
+async fn test_jobs() {
+    let match_id = MatchId::from(MATCH_ID);
+    for db_handler in common::db_handlers().await {
+        // A job is only queued once while it is pending or in flight
+        for kind in [JobKind::MatchData, JobKind::Timeline] {
+            let count = db_handler.insert_job(kind, &match_id).await.unwrap();
+            assert_eq!(count, 1);
+        }
+        let count = db_handler
+            .insert_job(JobKind::MatchData, &match_id)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+        let pending = db_handler.get_jobs(JobStatus::Pending).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].match_id, match_id);
+        assert_eq!(pending[0].attempts, 0);
+
+        db_handler
+            .update_job(JobKind::MatchData, &match_id, JobStatus::InFlight)
+            .await
+            .unwrap();
+        let count = db_handler
+            .insert_job(JobKind::MatchData, &match_id)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+        db_handler
+            .update_job(JobKind::MatchData, &match_id, JobStatus::Failed)
+            .await
+            .unwrap();
+
+        let failed = db_handler.get_jobs(JobStatus::Failed).await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].kind, JobKind::MatchData);
+        assert_eq!(failed[0].status, JobStatus::Failed);
+        assert_eq!(failed[0].attempts, 1);
+
+        // Failed jobs are queued again, keeping their attempts
+        let count = db_handler
+            .insert_job(JobKind::MatchData, &match_id)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        let pending = db_handler.get_jobs(JobStatus::Pending).await.unwrap();
+        assert_eq!(pending.len(), 2);
+        assert!(pending.iter().all(|job| job.status == JobStatus::Pending));
+
+        // Done jobs are cleaned up, leaving the rest
+        db_handler
+            .update_job(JobKind::Timeline, &match_id, JobStatus::Done)
+            .await
+            .unwrap();
+        let count = db_handler.delete_jobs(JobStatus::Done).await.unwrap();
+        assert_eq!(count, 1);
+        let pending = db_handler.get_jobs(JobStatus::Pending).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].kind, JobKind::MatchData);
+        assert_eq!(pending[0].attempts, 1);
+
+        let count = db_handler
+            .update_job(JobKind::Timeline, &match_id, JobStatus::InFlight)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
+
+#[tokio::test]
+async fn test_league_jobs() {
+    let match_id = MatchId::from(MATCH_ID);
+    for db_handler in common::db_handlers().await {
+        // League jobs are queued per summoner of a match
+        for puuid in ["puuid-0", "puuid-3"].map(Puuid::from) {
+            let count = db_handler
+                .insert_league_job(&puuid, &match_id)
+                .await
+                .unwrap();
+            assert_eq!(count, 1);
+        }
+        let puuid = Puuid::from("puuid-0");
+        let count = db_handler
+            .insert_league_job(&puuid, &match_id)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        db_handler
+            .update_league_job(&puuid, &match_id, JobStatus::InFlight)
+            .await
+            .unwrap();
+        let in_flight = db_handler
+            .get_league_jobs(JobStatus::InFlight)
+            .await
+            .unwrap();
+        assert_eq!(in_flight.len(), 1);
+        assert_eq!(in_flight[0].puuid, puuid);
+        assert_eq!(in_flight[0].match_id, match_id);
+        assert_eq!(in_flight[0].attempts, 1);
+
+        // Done league jobs are cleaned up along with other jobs
+        db_handler
+            .update_league_job(&puuid, &match_id, JobStatus::Done)
+            .await
+            .unwrap();
+        let count = db_handler.delete_jobs(JobStatus::Done).await.unwrap();
+        assert_eq!(count, 1);
+        let pending = db_handler
+            .get_league_jobs(JobStatus::Pending)
+            .await
+            .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].puuid.as_str(), "puuid-3");
+    }
+}
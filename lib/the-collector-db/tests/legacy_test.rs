@@ -0,0 +1,138 @@
+mod common;
+
+use common::{load_match, MATCH_ID};
+use the_collector_db::legacy::{
+    self, LegacyFollowing, LegacyMatch, LegacyQueries, LegacySummoner, LegacySummonerMatch,
+};
+use the_collector_db::SqlitePoolOptions;
+use the_collector_types::GuildId;
+
+#[tokio::test]
+async fn test_read_legacy_database() {
+    let path = std::env::temp_dir().join(format!("the-collector-legacy-{}.db", std::process::id()));
+    let url = format!("sqlite://{}?mode=rwc", path.display());
+
+    // A schema storing summoner names, and games by bare game ID
+    let pool = SqlitePoolOptions::new().connect(&url).await.unwrap();
+    for statement in [
+        "CREATE TABLE summoners (puuid TEXT, name TEXT NOT NULL)",
+        "CREATE TABLE follows (guild INTEGER NOT NULL, name TEXT NOT NULL)",
+        "CREATE TABLE games (game_id INTEGER NOT NULL, started INTEGER NOT NULL)",
+        "CREATE TABLE stats (game_id INTEGER NOT NULL, name TEXT NOT NULL, deaths INTEGER)",
+        "INSERT INTO summoners VALUES ('puuid-0', 'Player0'), (NULL, 'Player1')",
+        "INSERT INTO follows VALUES (1234, 'Player1')",
+        "INSERT INTO games VALUES (5000000001, 1735689600000)",
+        "INSERT INTO stats VALUES (5000000001, 'Player0', 12), (5000000001, 'Player1', NULL)",
+    ] {
+        sqlx::query(statement).execute(&pool).await.unwrap();
+    }
+    pool.close().await;
+
+    let queries = LegacyQueries {
+        summoners: "SELECT puuid, name AS game_name FROM summoners".into(),
+        followings: "SELECT guild AS guild_id, name AS game_name FROM follows".into(),
+        matches: "SELECT CAST(game_id AS TEXT) AS match_id, started AS start_time FROM games"
+            .into(),
+        summoner_matches: "SELECT CAST(game_id AS TEXT) AS match_id, name AS game_name, deaths
+            FROM stats"
+            .into(),
+    };
+    let data = legacy::read(&url, &queries).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let player1 = LegacySummoner {
+        puuid: None,
+        game_name: Some("Player1".into()),
+        tag: None,
+    };
+    assert_eq!(data.summoners.len(), 2);
+    assert_eq!(
+        data.summoners[0].puuid.as_ref().map(|puuid| puuid.as_str()),
+        Some("puuid-0")
+    );
+    assert_eq!(data.summoners[1], player1);
+    assert_eq!(
+        data.followings,
+        [LegacyFollowing {
+            guild_id: GuildId::new(1234),
+            summoner: player1
+        }]
+    );
+    assert_eq!(
+        data.matches,
+        [LegacyMatch {
+            match_id: "5000000001".into(),
+            start_time: Some(1735689600000),
+            ..Default::default()
+        }]
+    );
+    assert_eq!(data.summoner_matches.len(), 2);
+    assert_eq!(data.summoner_matches[0].deaths, Some(12));
+    assert_eq!(data.summoner_matches[1].deaths, None);
+}
+
+#[test]
+fn test_map_legacy_records() {
+    let match_data = load_match();
+    let mut legacy_match = LegacyMatch {
+        match_id: "5000000001".into(),
+        start_time: Some(1735689600000),
+        duration: Some(1800),
+        queue_id: Some(420),
+        game_version: Some("14.1".into()),
+        game_mode: Some("CLASSIC".into()),
+        winning_team_id: Some(100),
+        surrender: None,
+    };
+    let mut summoner_match = LegacySummonerMatch {
+        match_id: "5000000001".into(),
+        summoner: LegacySummoner {
+            puuid: Some("puuid-0".into()),
+            game_name: None,
+            tag: None,
+        },
+        kills: None,
+        deaths: Some(20),
+        assists: None,
+        champion_id: None,
+        position: None,
+        longest_time_living: None,
+        time_dead: None,
+        team_id: None,
+    };
+
+    // Records with missing columns are only mapped once they can be filled in
+    assert!(!legacy_match.is_complete());
+    assert!(legacy_match.to_row(&MATCH_ID.into(), None).is_none());
+    assert!(!summoner_match.is_complete());
+    assert!(summoner_match
+        .to_row(&"puuid-0".into(), &MATCH_ID.into(), None)
+        .is_none());
+
+    // Selected columns take precedence over fetched ones
+    let row = legacy_match
+        .to_row(&MATCH_ID.into(), Some(&match_data))
+        .unwrap();
+    assert_eq!(row.id.as_str(), MATCH_ID);
+    assert_eq!(row.game_version, "14.1");
+    let row = summoner_match
+        .to_row(&"puuid-0".into(), &MATCH_ID.into(), Some(&match_data))
+        .unwrap();
+    assert_eq!((row.kills, row.deaths, row.assists), (3, 20, 4));
+    assert_eq!(row.time_dead, 360);
+
+    legacy_match.surrender = Some(false);
+    assert!(legacy_match.is_complete());
+    assert!(legacy_match.to_row(&MATCH_ID.into(), None).is_some());
+    summoner_match.kills = Some(3);
+    summoner_match.assists = Some(4);
+    summoner_match.champion_id = Some(1);
+    summoner_match.longest_time_living = Some(600);
+    summoner_match.time_dead = Some(360);
+    summoner_match.team_id = Some(100);
+    assert!(summoner_match.is_complete());
+    let row = summoner_match
+        .to_row(&"puuid-0".into(), &MATCH_ID.into(), None)
+        .unwrap();
+    assert_eq!(row.position, None);
+}
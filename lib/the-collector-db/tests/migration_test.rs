@@ -0,0 +1,30 @@
+mod common;
+
+use the_collector_types::{ChannelId, GuildId};
+
+#[tokio::test]
+async fn test_migrate_empty_database() {
+    for db_handler in common::db_handlers().await {
+        db_handler.insert_guild(GuildId::new(1234)).await.unwrap();
+        db_handler
+            .update_channel(GuildId::new(1234), Some(ChannelId::new(5678)))
+            .await
+            .unwrap();
+        let guilds = db_handler.get_guilds().await.unwrap();
+        assert_eq!(guilds.len(), 1);
+        assert_eq!(guilds[0].id, GuildId::new(1234));
+        assert_eq!(guilds[0].channel_id, Some(ChannelId::new(5678)));
+        assert!(db_handler.get_summoners().await.unwrap().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_migrate_is_repeatable() {
+    for db_handler in common::db_handlers().await {
+        db_handler.insert_guild(GuildId::new(1234)).await.unwrap();
+
+        // Running again should be a no-op that keeps existing data
+        db_handler.migrate().await.unwrap();
+        assert_eq!(db_handler.get_guilds().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use common::{account, load_match, MATCH_ID};
+use riven::consts::PlatformRoute;
+use the_collector_db::model::NotificationStatus;
+use the_collector_types::GuildId;
+
+const GUILD_ID: GuildId = GuildId::new(1234);
+
+#[tokio::test]
+async fn test_notifications() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        db_handler.insert_guild(GUILD_ID).await.unwrap();
+        db_handler.insert_guild(GuildId::new(1235)).await.unwrap();
+        db_handler
+            .insert_summoner(&account(3), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        db_handler
+            .ingest_match(&match_data, &["puuid-3".into()])
+            .await
+            .unwrap();
+
+        // Each guild is notified at most once per summoner match
+        for guild_id in [GUILD_ID, GuildId::new(1235)] {
+            let count = db_handler
+                .insert_notification(
+                    &"puuid-3".into(),
+                    &MATCH_ID.into(),
+                    guild_id,
+                    "Big",
+                    "Player3 died",
+                )
+                .await
+                .unwrap();
+            assert_eq!(count, 1);
+        }
+        let count = db_handler
+            .insert_notification(
+                &"puuid-3".into(),
+                &MATCH_ID.into(),
+                GUILD_ID,
+                "Big",
+                "Player3 died",
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+        assert_eq!(
+            db_handler
+                .get_notifications(NotificationStatus::Pending)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        db_handler
+            .update_notification(
+                &"puuid-3".into(),
+                &MATCH_ID.into(),
+                GUILD_ID,
+                NotificationStatus::Sent,
+                Some(42),
+            )
+            .await
+            .unwrap();
+        db_handler
+            .update_notification(
+                &"puuid-3".into(),
+                &MATCH_ID.into(),
+                GuildId::new(1235),
+                NotificationStatus::Failed,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let sent = db_handler
+            .get_notifications(NotificationStatus::Sent)
+            .await
+            .unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].message_id, Some(42));
+        assert_eq!(sent[0].attempts, 1);
+
+        let failed = db_handler
+            .get_notifications(NotificationStatus::Failed)
+            .await
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].guild_id, GuildId::new(1235));
+        assert_eq!(failed[0].message, "Player3 died");
+        assert_eq!(failed[0].level, "Big");
+        assert_eq!(failed[0].status, NotificationStatus::Failed);
+        assert!(db_handler
+            .get_notifications(NotificationStatus::Pending)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use common::{account, load_match, MATCH_ID};
+use riven::consts::{Division, PlatformRoute, QueueType, Tier};
+use riven::models::league_v4::LeagueEntry;
+use the_collector_db::rank::{ladder_points, queue_type};
+use the_collector_db::stats::StatsQuery;
+use the_collector_db::DEFAULT_QUEUE_IDS;
+
+fn league_entry(tier: Option<Tier>, division: Option<Division>, league_points: i32) -> LeagueEntry {
+    LeagueEntry {
+        league_id: None,
+        summoner_id: "summoner-3".into(),
+        queue_type: QueueType::RANKED_SOLO_5x5,
+        tier,
+        rank: division,
+        league_points,
+        wins: 0,
+        losses: 0,
+        hot_streak: false,
+        veteran: false,
+        fresh_blood: false,
+        inactive: false,
+        mini_series: None,
+    }
+}
+
+#[test]
+fn test_ladder_points() {
+    assert_eq!(ladder_points("IRON", "IV", 0), Some(0));
+    assert_eq!(ladder_points("GOLD", "II", 45), Some(1445));
+    assert_eq!(ladder_points("DIAMOND", "I", 99), Some(2799));
+    assert_eq!(ladder_points("MASTER", "I", 0), Some(2800));
+    assert_eq!(ladder_points("CHALLENGER", "I", 1200), Some(4000));
+    assert_eq!(ladder_points("UNRANKED", "I", 0), None);
+    assert_eq!(ladder_points("GOLD", "V", 0), None);
+}
+
+#[test]
+fn test_queue_type() {
+    assert_eq!(queue_type(420), Some(QueueType::RANKED_SOLO_5x5));
+    assert_eq!(queue_type(440), Some(QueueType::RANKED_FLEX_SR));
+    assert_eq!(queue_type(400), None);
+}
+
+#[tokio::test]
+async fn test_record_rank() {
+    let match_data = load_match();
+    let mut next_match = load_match();
+    next_match.metadata.match_id = "NA1_5000000002".into();
+    next_match.info.game_start_timestamp += 24 * 60 * 60 * 1000;
+    for db_handler in common::db_handlers().await {
+        let tracked_puuids = vec!["puuid-3".into()];
+        db_handler
+            .insert_summoner(&account(3), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        for data in [&match_data, &next_match] {
+            db_handler
+                .ingest_match(data, &tracked_puuids)
+                .await
+                .unwrap();
+        }
+
+        // Nothing is recorded while unranked
+        let unranked = league_entry(None, None, 0);
+        let count = db_handler
+            .record_rank(&"puuid-3".into(), &MATCH_ID.into(), &unranked)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        // The first snapshot in a queue has no delta
+        let entry = league_entry(Some(Tier::GOLD), Some(Division::II), 80);
+        let count = db_handler
+            .record_rank(&"puuid-3".into(), &MATCH_ID.into(), &entry)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        let snapshot = db_handler
+            .get_rank_snapshot(&"puuid-3".into(), &MATCH_ID.into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.queue_type, "RANKED_SOLO_5x5");
+        assert_eq!(
+            (snapshot.tier.as_str(), snapshot.division.as_str()),
+            ("GOLD", "II")
+        );
+        assert_eq!((snapshot.league_points, snapshot.lp_delta), (80, None));
+
+        // Only the first snapshot after a match is kept
+        let count = db_handler
+            .record_rank(&"puuid-3".into(), &MATCH_ID.into(), &entry)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+
+        // Deltas carry across divisions
+        let entry = league_entry(Some(Tier::GOLD), Some(Division::I), 2);
+        db_handler
+            .record_rank(&"puuid-3".into(), &"NA1_5000000002".into(), &entry)
+            .await
+            .unwrap();
+        let snapshot = db_handler
+            .get_rank_snapshot(&"puuid-3".into(), &"NA1_5000000002".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.lp_delta, Some(22));
+        let latest = db_handler
+            .get_latest_rank_snapshot(&"puuid-3".into(), "RANKED_SOLO_5x5")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest.match_id, "NA1_5000000002");
+
+        let stats = db_handler
+            .get_summoner_stats(&StatsQuery::new("Player3", "NA1", DEFAULT_QUEUE_IDS))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stats.lp_delta, Some(22));
+
+        // Snapshots are deleted along with their match
+        db_handler
+            .delete_matches(&["NA1_5000000002".into()])
+            .await
+            .unwrap();
+        let snapshot = db_handler
+            .get_latest_rank_snapshot(&"puuid-3".into(), "RANKED_SOLO_5x5")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.match_id, MATCH_ID);
+    }
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use common::{account, load_match, MATCH_ID};
+use riven::consts::{PlatformRoute, Queue};
+use riven::models::match_v5::Match;
+use the_collector_db::retention::RetentionPolicy;
+use the_collector_types::{GuildId, Puuid};
+
+const GUILD_ID: GuildId = GuildId::new(1234);
+
+/// The fixture match under another ID, starting `months` later in the given queue.
+fn later_match(match_id: &str, months: i64, queue_id: u16) -> Match {
+    let mut match_data = load_match();
+    match_data.metadata.match_id = match_id.into();
+    match_data.info.game_start_timestamp += months * 31 * 24 * 60 * 60 * 1000;
+    match_data.info.queue_id = Queue::from(queue_id);
+    match_data
+}
+
+#[tokio::test]
+async fn test_prune() {
+    // Matches of followed summoners are pruned in queues the guild does not count, as
+    // they are not on its leaderboards
+    let matches = [
+        (load_match(), vec!["puuid-0", "puuid-3", "puuid-4"]),
+        (later_match("NA1_2", 1, 450), vec!["puuid-0", "puuid-4"]),
+        (later_match("NA1_3", 2, 420), vec!["puuid-0"]),
+        (later_match("NA1_4", 3, 450), vec!["puuid-4"]),
+        (later_match("NA1_5", 4, 420), vec!["puuid-4"]),
+    ];
+    let policy = RetentionPolicy {
+        match_retention_days: Some(30),
+        prune_unfollowed_summoners: true,
+        ..Default::default()
+    };
+    for db_handler in common::db_handlers().await {
+        db_handler.insert_guild(GUILD_ID).await.unwrap();
+        for index in [0, 3, 4] {
+            db_handler
+                .insert_summoner(&account(index), PlatformRoute::NA1)
+                .await
+                .unwrap();
+        }
+        for puuid in ["puuid-0", "puuid-3"] {
+            db_handler
+                .insert_guild_following(GUILD_ID, &puuid.into())
+                .await
+                .unwrap();
+        }
+        for (match_data, tracked_puuids) in &matches {
+            let tracked_puuids: Vec<Puuid> = tracked_puuids
+                .iter()
+                .map(|puuid| Puuid::from(*puuid))
+                .collect();
+            let payload = serde_json::to_vec(match_data).unwrap();
+            db_handler
+                .archive_match(&match_data.into(), &payload)
+                .await
+                .unwrap();
+            db_handler
+                .ingest_match(match_data, &tracked_puuids)
+                .await
+                .unwrap();
+        }
+
+        // Nothing is deleted by default
+        let report = db_handler
+            .prune(&RetentionPolicy::default(), false)
+            .await
+            .unwrap();
+        assert!(report.match_ids.is_empty() && report.puuids.is_empty());
+
+        // A dry run only reports what would be deleted
+        let report = db_handler.prune(&policy, true).await.unwrap();
+        assert_eq!(report.match_ids, ["NA1_2", "NA1_4", "NA1_5"]);
+        assert_eq!(report.puuids, ["puuid-4"]);
+        assert!(db_handler
+            .get_match(&"NA1_4".into())
+            .await
+            .unwrap()
+            .is_some());
+        assert!(db_handler
+            .get_summoner(&"puuid-4".into())
+            .await
+            .unwrap()
+            .is_some());
+
+        // Flagged matches are kept
+        db_handler
+            .insert_notification(
+                &"puuid-4".into(),
+                &"NA1_4".into(),
+                GUILD_ID,
+                "Big",
+                "Player0 died",
+            )
+            .await
+            .unwrap();
+        let report = db_handler.prune(&policy, false).await.unwrap();
+        assert_eq!(report.match_ids, ["NA1_2", "NA1_5"]);
+        assert_eq!(report.puuids, ["puuid-4"]);
+
+        for match_id in [MATCH_ID, "NA1_3", "NA1_4"] {
+            assert!(db_handler
+                .get_match(&match_id.into())
+                .await
+                .unwrap()
+                .is_some());
+        }
+        assert!(db_handler
+            .get_match(&"NA1_5".into())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db_handler
+            .get_archived_match(&"NA1_5".into())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db_handler
+            .get_summoner(&"puuid-4".into())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db_handler
+            .get_summoner_match(&"puuid-4".into(), &MATCH_ID.into())
+            .await
+            .unwrap()
+            .is_none());
+
+        // The notification keeping NA1_4 was deleted along with its summoner
+        let report = db_handler.prune(&policy, false).await.unwrap();
+        assert_eq!(report.match_ids, ["NA1_4"]);
+        assert!(report.puuids.is_empty());
+
+        // Pruning again finds nothing left to delete
+        let report = db_handler.prune(&policy, false).await.unwrap();
+        assert!(report.match_ids.is_empty() && report.puuids.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_prune_keeps_leaderboard_matches() {
+    // The followed summoner's best match is the only one not in the top 2 of any
+    // leaderboard, while the latest match is kept regardless
+    let mut best_match = later_match("NA1_2", 1, 420);
+    let participant = &mut best_match.info.participants[0];
+    participant.deaths = 0;
+    participant.total_time_spent_dead = 0;
+    let matches = [load_match(), best_match, later_match("NA1_3", 2, 420)];
+    let policy = RetentionPolicy {
+        match_retention_days: Some(30),
+        leaderboard_size: 2,
+        ..Default::default()
+    };
+    for db_handler in common::db_handlers().await {
+        db_handler.insert_guild(GUILD_ID).await.unwrap();
+        db_handler
+            .insert_summoner(&account(0), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        db_handler
+            .insert_guild_following(GUILD_ID, &"puuid-0".into())
+            .await
+            .unwrap();
+        for match_data in &matches {
+            db_handler
+                .ingest_match(match_data, &["puuid-0".into()])
+                .await
+                .unwrap();
+        }
+
+        let report = db_handler.prune(&policy, true).await.unwrap();
+        assert_eq!(report.match_ids, ["NA1_2"]);
+
+        let policy = RetentionPolicy {
+            leaderboard_size: 3,
+            ..policy.clone()
+        };
+        let report = db_handler.prune(&policy, true).await.unwrap();
+        assert!(report.match_ids.is_empty());
+    }
+}
@@ -0,0 +1,685 @@
+mod common;
+
+use chrono::{TimeZone, Utc};
+use common::{account, load_match, MATCH_ID};
+use riven::consts::{Champion, PlatformRoute};
+use std::collections::BTreeMap;
+use the_collector_db::leaderboard::{
+    KdaWeights, LeaderboardMetric, LeaderboardQuery, PositionKdaWeights, TimeWindow,
+};
+use the_collector_db::stats::{StatsGrouping, StatsQuery};
+use the_collector_db::DEFAULT_QUEUE_IDS;
+use the_collector_types::{ChannelId, GuildId, Puuid};
+
+const GUILD_ID: GuildId = GuildId::new(1234);
+
+#[tokio::test]
+async fn test_guilds() {
+    for db_handler in common::db_handlers().await {
+        assert_eq!(db_handler.insert_guild(GUILD_ID).await.unwrap(), 1);
+        // Inserting an existing guild is ignored
+        assert_eq!(db_handler.insert_guild(GUILD_ID).await.unwrap(), 0);
+
+        db_handler
+            .update_channel(GUILD_ID, Some(ChannelId::new(1)))
+            .await
+            .unwrap();
+        assert_eq!(
+            db_handler.get_guilds().await.unwrap()[0].channel_id,
+            Some(ChannelId::new(1))
+        );
+        assert_eq!(
+            db_handler.delete_channel(ChannelId::new(1)).await.unwrap(),
+            1
+        );
+        assert_eq!(db_handler.get_guilds().await.unwrap()[0].channel_id, None);
+
+        assert_eq!(db_handler.delete_guild(GUILD_ID).await.unwrap(), 1);
+        assert!(db_handler.get_guilds().await.unwrap().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_summoners() {
+    for db_handler in common::db_handlers().await {
+        assert_eq!(
+            db_handler
+                .insert_summoner(&account(0), PlatformRoute::NA1)
+                .await
+                .unwrap(),
+            1
+        );
+        // Inserting an existing summoner keeps the original
+        let mut renamed = account(0);
+        renamed.game_name = Some("Renamed".into());
+        assert_eq!(
+            db_handler
+                .insert_summoner(&renamed, PlatformRoute::NA1)
+                .await
+                .unwrap(),
+            0
+        );
+
+        let summoner = db_handler
+            .get_summoner(&"puuid-0".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summoner.game_name, "Player0");
+        assert_eq!(summoner.tag, "NA1");
+        let summoner = db_handler
+            .get_summoner_by_name("Player0", "NA1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summoner.puuid, "puuid-0");
+        assert!(db_handler
+            .get_summoner_by_name("Renamed", "NA1")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(db_handler.get_summoners().await.unwrap().len(), 1);
+        assert_eq!(summoner.platform(), Some(PlatformRoute::NA1));
+
+        db_handler
+            .insert_summoner(&account(1), PlatformRoute::EUW1)
+            .await
+            .unwrap();
+        let summoner = db_handler
+            .get_summoner(&"puuid-1".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summoner.region, "EUW1");
+        assert_eq!(summoner.platform(), Some(PlatformRoute::EUW1));
+
+        // Inserting an existing summoner on another platform keeps their platform, as
+        // it applies to every guild following them
+        assert_eq!(
+            db_handler
+                .insert_summoner(&account(1), PlatformRoute::KR)
+                .await
+                .unwrap(),
+            0
+        );
+        let summoner = db_handler
+            .get_summoner(&"puuid-1".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summoner.platform(), Some(PlatformRoute::EUW1));
+
+        // They are only moved explicitly
+        let puuid = Puuid::from("puuid-1");
+        for (region, count) in [(PlatformRoute::KR, 1), (PlatformRoute::KR, 0)] {
+            let moved = db_handler.update_summoner_region(&puuid, region).await;
+            assert_eq!(moved.unwrap(), count);
+        }
+        let summoner = db_handler.get_summoner(&puuid).await.unwrap().unwrap();
+        assert_eq!(summoner.platform(), Some(PlatformRoute::KR));
+        assert_eq!(summoner.game_name, "Player1");
+        let moved = db_handler
+            .update_summoner_region(&"puuid-9".into(), PlatformRoute::KR)
+            .await;
+        assert_eq!(moved.unwrap(), 0);
+    }
+}
+
+#[tokio::test]
+async fn test_summoner_renames() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        db_handler
+            .insert_summoner(&account(3), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        db_handler
+            .ingest_match(&match_data, &["puuid-3".into()])
+            .await
+            .unwrap();
+        // Unchanged names are not recorded
+        assert_eq!(
+            db_handler.update_summoner_name(&account(3)).await.unwrap(),
+            0
+        );
+
+        let mut renamed = account(3);
+        renamed.game_name = Some("Renamed".into());
+        renamed.tag_line = Some("EUW".into());
+        assert_eq!(db_handler.update_summoner_name(&renamed).await.unwrap(), 1);
+        // Unknown summoners are not inserted
+        assert_eq!(
+            db_handler.update_summoner_name(&account(4)).await.unwrap(),
+            0
+        );
+
+        let summoner = db_handler
+            .get_summoner(&"puuid-3".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (summoner.game_name.as_str(), summoner.tag.as_str()),
+            ("Renamed", "EUW")
+        );
+        let history = db_handler
+            .get_summoner_name_history(&"puuid-3".into())
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(
+            (history[0].game_name.as_str(), history[0].tag.as_str()),
+            ("Player3", "NA1")
+        );
+
+        // Both the current and former names resolve to the summoner
+        for (name, tag) in [("Renamed", "EUW"), ("Player3", "NA1")] {
+            let summoner = db_handler
+                .get_summoner_by_name(name, tag)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(summoner.puuid, "puuid-3");
+            let stats = db_handler
+                .get_summoner_stats(&StatsQuery::new(name, tag, DEFAULT_QUEUE_IDS))
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(stats.game_name, "Renamed");
+            assert_eq!(stats.deaths, 14);
+        }
+
+        // A former name now used by another summoner resolves to them instead
+        let mut taken = account(4);
+        taken.game_name = Some("Player3".into());
+        db_handler
+            .insert_summoner(&taken, PlatformRoute::NA1)
+            .await
+            .unwrap();
+        let summoner = db_handler
+            .get_summoner_by_name("Player3", "NA1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summoner.puuid, "puuid-4");
+    }
+}
+
+#[tokio::test]
+async fn test_guild_followings() {
+    for db_handler in common::db_handlers().await {
+        db_handler.insert_guild(GUILD_ID).await.unwrap();
+        db_handler
+            .insert_summoner(&account(0), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        db_handler
+            .insert_guild_following(GUILD_ID, &"puuid-0".into())
+            .await
+            .unwrap();
+
+        let follows = db_handler.get_guild_follows(GUILD_ID).await.unwrap();
+        assert_eq!(follows.len(), 1);
+        assert_eq!(follows[0].puuid, "puuid-0");
+        let summoners = db_handler.get_followed_summoners(GUILD_ID).await.unwrap();
+        assert_eq!(summoners.len(), 1);
+        assert_eq!(summoners[0].game_name, "Player0");
+        let guilds = db_handler
+            .get_following_guilds(&"puuid-0".into())
+            .await
+            .unwrap();
+        assert_eq!(guilds.len(), 1);
+        assert_eq!(guilds[0].id, GUILD_ID);
+
+        db_handler
+            .delete_guild_following(GUILD_ID, &"puuid-0".into())
+            .await
+            .unwrap();
+        assert!(db_handler
+            .get_guild_follows(GUILD_ID)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_matches() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        db_handler
+            .insert_summoner(&account(3), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        assert_eq!(
+            db_handler
+                .ingest_match(&match_data, &["puuid-3".into()])
+                .await
+                .unwrap(),
+            1
+        );
+
+        let db_match = db_handler
+            .get_match(&MATCH_ID.into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(db_match.duration, 1800);
+        assert_eq!(db_match.queue_id, 420);
+        assert_eq!(db_match.winning_team_id, 200);
+        assert_eq!(db_match.game_mode, "CLASSIC");
+        assert!(!db_match.surrender);
+        assert_eq!(
+            db_match.start_time.and_utc().timestamp_millis(),
+            match_data.info.game_start_timestamp
+        );
+
+        let matches = db_handler
+            .get_matches(&[MATCH_ID.into(), "NA1_0".into()])
+            .await
+            .unwrap();
+        assert_eq!(matches.len(), 1);
+
+        let latest = db_handler
+            .get_summoner_latest_match(&"puuid-3".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(latest.id, MATCH_ID);
+
+        let summoner_match = db_handler
+            .get_summoner_match(&"puuid-3".into(), &MATCH_ID.into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            (
+                summoner_match.kills,
+                summoner_match.deaths,
+                summoner_match.assists
+            ),
+            (2, 14, 3)
+        );
+        assert_eq!(summoner_match.champion_id, 222);
+        assert_eq!(summoner_match.position.as_deref(), Some("BOTTOM"));
+        assert_eq!(summoner_match.team_id, 100);
+    }
+}
+
+#[tokio::test]
+async fn test_leaderboard_and_stats() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        db_handler.insert_guild(GUILD_ID).await.unwrap();
+        let mut tracked_puuids = Vec::new();
+        for index in [0, 3, 4] {
+            let account = account(index);
+            db_handler
+                .insert_summoner(&account, PlatformRoute::NA1)
+                .await
+                .unwrap();
+            tracked_puuids.push(account.puuid.into());
+        }
+        db_handler
+            .ingest_match(&match_data, &tracked_puuids)
+            .await
+            .unwrap();
+        // Only followed summoners appear on the leaderboard
+        for puuid in ["puuid-0", "puuid-3"] {
+            db_handler
+                .insert_guild_following(GUILD_ID, &puuid.into())
+                .await
+                .unwrap();
+        }
+
+        let leaderboard = db_handler
+            .get_leaderboard(&LeaderboardQuery::new(GUILD_ID))
+            .await
+            .unwrap();
+        let deaths: Vec<i64> = leaderboard.iter().map(|entry| entry.deaths).collect();
+        assert_eq!(deaths, vec![14, 12]);
+        let entry = &leaderboard[0];
+        assert_eq!(
+            (entry.game_name.as_str(), entry.tag.as_str()),
+            ("Player3", "NA1")
+        );
+        assert_eq!(entry.champion_id, 222);
+        assert_eq!(entry.game_version, "15.1.634.6143");
+        assert_eq!(entry.duration, 1800);
+        assert_eq!(
+            db_handler
+                .get_leaderboard(&LeaderboardQuery::new(GUILD_ID).size(1))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let stats = db_handler
+            .get_summoner_stats(&StatsQuery::new("Player3", "NA1", DEFAULT_QUEUE_IDS))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((stats.num_matches, stats.wins), (1, 0));
+        assert_eq!((stats.kills, stats.deaths, stats.assists), (2, 14, 3));
+        assert_eq!(stats.total_duration, 1800);
+        assert_eq!(stats.total_time_dead, 60 + 25 * 14);
+        assert!(db_handler
+            .get_summoner_stats(&StatsQuery::new("Player3", "NA1", [450]))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db_handler
+            .get_summoner_stats(&StatsQuery::new("Player3", "NA1", Vec::new()))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(db_handler
+            .get_summoner_stats(&StatsQuery::new("Player9", "NA1", DEFAULT_QUEUE_IDS))
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
+
+#[tokio::test]
+async fn test_grouped_stats() {
+    let match_data = load_match();
+    // A month later, Player3 plays the same role on another champion
+    let mut next_match = load_match();
+    next_match.metadata.match_id = "NA1_5000000002".into();
+    next_match.info.game_start_timestamp += 31 * 24 * 60 * 60 * 1000;
+    #[allow(deprecated)]
+    {
+        next_match.info.participants[3].champion_id = Ok(Champion::CAITLYN);
+    }
+    next_match.info.participants[3].deaths = 4;
+    for db_handler in common::db_handlers().await {
+        let tracked_puuids = vec!["puuid-3".into(), "puuid-8".into()];
+        for index in [3, 8] {
+            db_handler
+                .insert_summoner(&account(index), PlatformRoute::NA1)
+                .await
+                .unwrap();
+        }
+        for data in [&match_data, &next_match] {
+            db_handler
+                .ingest_match(data, &tracked_puuids)
+                .await
+                .unwrap();
+        }
+
+        let query = StatsQuery::new("Player3", "NA1", DEFAULT_QUEUE_IDS);
+        let stats = db_handler
+            .get_summoner_stats(&query)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((stats.num_matches, stats.wins, stats.deaths), (2, 0, 18));
+        let stats = db_handler
+            .get_summoner_stats(&StatsQuery::new("Player8", "NA1", DEFAULT_QUEUE_IDS))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((stats.num_matches, stats.wins), (2, 2));
+
+        // Filters
+        let since = Utc.with_ymd_and_hms(2025, 1, 15, 0, 0, 0).unwrap();
+        for (query, deaths) in [
+            (query.clone().champion(222), Some(14)),
+            (query.clone().since(since), Some(4)),
+            (query.clone().position("BOTTOM"), Some(18)),
+            (query.clone().position("TOP"), None),
+        ] {
+            let stats = db_handler.get_summoner_stats(&query).await.unwrap();
+            assert_eq!(stats.map(|stats| stats.deaths), deaths);
+        }
+
+        // Groups, with the most deaths per game first
+        for (grouping, expected) in [
+            (StatsGrouping::Champion, vec![("222", 14), ("51", 4)]),
+            (StatsGrouping::Month, vec![("2025-01", 14), ("2025-02", 4)]),
+            (StatsGrouping::Position, vec![("BOTTOM", 18)]),
+            (StatsGrouping::Queue, vec![("420", 18)]),
+        ] {
+            let groups = db_handler
+                .get_summoner_grouped_stats(&query, grouping)
+                .await
+                .unwrap();
+            let groups: Vec<(&str, i64)> = groups
+                .iter()
+                .map(|group| (group.group_key.as_deref().unwrap(), group.deaths))
+                .collect();
+            assert_eq!(groups, expected, "{grouping:?}");
+        }
+        assert!(db_handler
+            .get_summoner_grouped_stats(&query.position("TOP"), StatsGrouping::Champion)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_guild_queues() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        db_handler.insert_guild(GUILD_ID).await.unwrap();
+        assert_eq!(
+            db_handler.get_guild_queues(GUILD_ID).await.unwrap(),
+            DEFAULT_QUEUE_IDS
+        );
+        db_handler
+            .insert_summoner(&account(0), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        db_handler
+            .insert_guild_following(GUILD_ID, &"puuid-0".into())
+            .await
+            .unwrap();
+        db_handler
+            .ingest_match(&match_data, &["puuid-0".into()])
+            .await
+            .unwrap();
+
+        // The fixture match is ranked solo, so it is no longer counted
+        assert_eq!(
+            db_handler
+                .set_guild_queues(GUILD_ID, &[450, 440])
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            db_handler.get_guild_queues(GUILD_ID).await.unwrap(),
+            vec![440, 450]
+        );
+        let query = LeaderboardQuery::new(GUILD_ID);
+        assert!(db_handler.get_leaderboard(&query).await.unwrap().is_empty());
+        // Queues given by the query take precedence
+        assert_eq!(
+            db_handler
+                .get_leaderboard(&query.clone().queues([420]))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Setting no queues restores the defaults
+        db_handler.set_guild_queues(GUILD_ID, &[]).await.unwrap();
+        assert_eq!(db_handler.get_leaderboard(&query).await.unwrap().len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn test_leaderboard_query() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        db_handler.insert_guild(GUILD_ID).await.unwrap();
+        let mut tracked_puuids = Vec::new();
+        for index in [0, 3, 4] {
+            let account = account(index);
+            db_handler
+                .insert_summoner(&account, PlatformRoute::NA1)
+                .await
+                .unwrap();
+            db_handler
+                .insert_guild_following(GUILD_ID, &Puuid::from(&account))
+                .await
+                .unwrap();
+            tracked_puuids.push(account.puuid.into());
+        }
+        db_handler
+            .ingest_match(&match_data, &tracked_puuids)
+            .await
+            .unwrap();
+
+        let puuids = |query: LeaderboardQuery| {
+            let db_handler = &db_handler;
+            async move {
+                db_handler
+                    .get_leaderboard(&query)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|entry| entry.puuid)
+                    .collect::<Vec<Puuid>>()
+            }
+        };
+        let query = LeaderboardQuery::new(GUILD_ID);
+
+        // Filters
+        assert_eq!(
+            puuids(query.clone().position("UTILITY")).await,
+            vec!["puuid-4"]
+        );
+        assert_eq!(puuids(query.clone().champion(266)).await, vec!["puuid-0"]);
+        assert!(puuids(query.clone().queues([440])).await.is_empty());
+        assert!(puuids(query.clone().queues(Vec::new())).await.is_empty());
+        assert!(puuids(query.clone().window(TimeWindow::ThisWeek))
+            .await
+            .is_empty());
+        // The match started at 2025-01-01 00:00 UTC
+        let range = |start_day, end_day| TimeWindow::Range {
+            start: Utc.with_ymd_and_hms(2025, 1, start_day, 0, 0, 0).unwrap(),
+            end: Utc.with_ymd_and_hms(2025, 1, end_day, 0, 0, 0).unwrap(),
+        };
+        assert_eq!(puuids(query.clone().window(range(1, 2))).await.len(), 3);
+        assert!(puuids(query.clone().window(range(2, 3))).await.is_empty());
+
+        // Metrics
+        assert_eq!(
+            puuids(query.clone().metric(LeaderboardMetric::TimeDead)).await,
+            vec!["puuid-3", "puuid-0", "puuid-4"]
+        );
+        assert_eq!(
+            puuids(query.clone().metric(LeaderboardMetric::DeathsPerMinute)).await,
+            vec!["puuid-3", "puuid-0", "puuid-4"]
+        );
+        let weights = |kills, deaths, assists| KdaWeights {
+            kills,
+            deaths,
+            assists,
+        };
+        let position_weights = PositionKdaWeights {
+            positions: BTreeMap::new(),
+            other: Some(weights(0.0, 0.0, -1.0)),
+        };
+        assert_eq!(
+            puuids(
+                query
+                    .clone()
+                    .metric(LeaderboardMetric::WeightedKda(position_weights.clone()))
+            )
+            .await,
+            vec!["puuid-4", "puuid-0", "puuid-3"]
+        );
+        // Positions with their own weights are weighed by them instead
+        let mut positions = BTreeMap::new();
+        positions.insert("BOTTOM".into(), weights(0.0, 0.0, -10.0));
+        let with_positions = PositionKdaWeights {
+            positions,
+            ..position_weights
+        };
+        assert_eq!(
+            puuids(
+                query
+                    .clone()
+                    .metric(LeaderboardMetric::WeightedKda(with_positions.clone()))
+            )
+            .await,
+            vec!["puuid-3", "puuid-4", "puuid-0"]
+        );
+        // Matches in positions without weights are left off if there are no other weights
+        let only_positions = PositionKdaWeights {
+            other: None,
+            ..with_positions
+        };
+        assert_eq!(
+            puuids(query.metric(LeaderboardMetric::WeightedKda(only_positions))).await,
+            vec!["puuid-3"]
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_match_participants() {
+    let match_data = load_match();
+    for db_handler in common::db_handlers().await {
+        db_handler
+            .insert_summoner(&account(3), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        db_handler
+            .ingest_match(&match_data, &["puuid-3".into()])
+            .await
+            .unwrap();
+
+        // Every participant is stored, including those that are not tracked
+        let participants = db_handler
+            .get_match_participants(&MATCH_ID.into())
+            .await
+            .unwrap();
+        assert_eq!(participants.len(), 10);
+        let puuids: Vec<&str> = participants.iter().map(|p| p.puuid.as_str()).collect();
+        assert_eq!(puuids[0], "puuid-0");
+        assert_eq!(puuids[9], "puuid-9");
+
+        let participant = &participants[3];
+        assert_eq!(participant.game_name.as_deref(), Some("Player3"));
+        assert_eq!(participant.champion_id, 222);
+        assert_eq!(participant.gold_earned, 10100);
+        assert_eq!(participant.total_damage_dealt_to_champions, 16500);
+        assert_eq!(participant.vision_score, 24);
+        assert_eq!(participant.creep_score(), 124);
+        assert_eq!(participant.items(), vec![3071, 3047, 6333, 1036, 3340]);
+        assert_eq!(
+            (participant.summoner1_id, participant.summoner2_id),
+            (4, 14)
+        );
+        assert!(!participant.win);
+
+        let opponent = db_handler
+            .get_lane_opponent(&MATCH_ID.into(), &"puuid-3".into())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(opponent.puuid, "puuid-8");
+        assert!(opponent.win);
+
+        // Re-ingesting does not duplicate participants
+        db_handler
+            .ingest_match(&match_data, &["puuid-3".into()])
+            .await
+            .unwrap();
+        assert_eq!(
+            db_handler
+                .get_match_participants(&MATCH_ID.into())
+                .await
+                .unwrap()
+                .len(),
+            10
+        );
+    }
+}
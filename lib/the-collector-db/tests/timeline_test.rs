@@ -0,0 +1,60 @@
+mod common;
+
+use common::{account, load_match, load_timeline, MATCH_ID};
+use riven::consts::PlatformRoute;
+
+#[tokio::test]
+async fn test_ingest_timeline() {
+    let match_data = load_match();
+    let timeline = load_timeline();
+    for db_handler in common::db_handlers().await {
+        db_handler
+            .insert_summoner(&account(3), PlatformRoute::NA1)
+            .await
+            .unwrap();
+        let tracked_puuids = vec!["puuid-3".into()];
+        db_handler
+            .ingest_match(&match_data, &tracked_puuids)
+            .await
+            .unwrap();
+
+        // Only deaths of tracked summoners are stored, and ingesting again is safe
+        for _ in 0..2 {
+            let count = db_handler
+                .ingest_timeline(&timeline, &tracked_puuids)
+                .await
+                .unwrap();
+            assert_eq!(count, 2);
+        }
+
+        let death_events = db_handler
+            .get_death_events(&MATCH_ID.into(), &"puuid-3".into())
+            .await
+            .unwrap();
+        assert_eq!(death_events.len(), 2);
+
+        let first = &death_events[0];
+        assert_eq!(first.timestamp, 185000);
+        assert_eq!(
+            first.killer_puuid.as_ref().map(|puuid| puuid.as_str()),
+            Some("puuid-8")
+        );
+        assert_eq!(
+            (first.position_x, first.position_y),
+            (Some(12000), Some(2500))
+        );
+        assert_eq!(first.assister_puuids, vec!["puuid-6", "puuid-9"]);
+
+        // Executed by a turret
+        let second = &death_events[1];
+        assert_eq!(second.timestamp, 420500);
+        assert_eq!(second.killer_puuid, None);
+        assert!(second.assister_puuids.is_empty());
+
+        assert!(db_handler
+            .get_death_events(&MATCH_ID.into(), &"puuid-8".into())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
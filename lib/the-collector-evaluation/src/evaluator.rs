@@ -1,9 +1,10 @@
 use crate::label::IntLevel;
 use crate::role::Role;
-use crate::weight::{WeightedKda, Weights};
+use crate::weight::{position_kda_weights, WeightedKda, Weights};
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::ops::Range;
+use the_collector_db::leaderboard::PositionKdaWeights;
 use the_collector_db::model::{Match, SummonerMatch};
 
 #[derive(Debug)]
@@ -20,7 +21,12 @@ pub struct MatchEvaluator {
 }
 
 impl MatchEvaluator {
-    pub fn evaluate(&self, match_stats: &SummonerMatch, _match_data: &Match) -> Evaluation {
+    /// Get the KDA weights of each role as leaderboard weights.
+    pub fn leaderboard_weights(&self) -> PositionKdaWeights {
+        position_kda_weights(&self.kda_weights)
+    }
+
+    pub fn evaluate(&self, match_stats: &SummonerMatch, _match_data: &Match) -> Evaluation<'_> {
         let role = match_stats.position.as_deref().unwrap_or_default().into();
         let kda = self.kda_weights[&role].calculate_weighted_kda(match_stats);
 
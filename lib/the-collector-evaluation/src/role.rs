@@ -25,3 +25,17 @@ impl From<&str> for Role {
         }
     }
 }
+
+impl Role {
+    /// Position as given by the Riot API, or [`None`] for any other position.
+    pub fn position(&self) -> Option<&'static str> {
+        match self {
+            Role::Top => Some("TOP"),
+            Role::Jungle => Some("JUNGLE"),
+            Role::Mid => Some("MIDDLE"),
+            Role::Bot => Some("BOTTOM"),
+            Role::Support => Some("UTILITY"),
+            Role::Other => None,
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use crate::role::Role;
 use serde::Deserialize;
+use std::collections::HashMap;
+use the_collector_db::leaderboard::{KdaWeights, PositionKdaWeights};
 use the_collector_db::model::SummonerMatch;
 
 #[derive(Debug, PartialEq, PartialOrd, Deserialize, Default)]
@@ -19,3 +22,30 @@ impl Weights {
         WeightedKda(inner)
     }
 }
+
+impl From<&Weights> for KdaWeights {
+    fn from(value: &Weights) -> Self {
+        Self {
+            kills: value.kill_weight.into(),
+            deaths: value.death_weight.into(),
+            assists: value.assist_weight.into(),
+        }
+    }
+}
+
+/// Get the weights of each role as leaderboard weights, so that leaderboards rank
+/// matches by the same weighted KDA as the match evaluator.
+pub fn position_kda_weights(weights: &HashMap<Role, Weights>) -> PositionKdaWeights {
+    let mut position_weights = PositionKdaWeights::default();
+    for (role, role_weights) in weights {
+        match role.position() {
+            Some(position) => {
+                position_weights
+                    .positions
+                    .insert(position.into(), role_weights.into());
+            }
+            None => position_weights.other = Some(role_weights.into()),
+        }
+    }
+    position_weights
+}
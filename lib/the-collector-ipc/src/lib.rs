@@ -1,13 +1,27 @@
 use serde::{Deserialize, Serialize};
+use the_collector_types::{GuildId, MatchId, Puuid};
 
 pub mod error;
 pub mod r#pub;
 pub mod sub;
 
 pub const IPC_SUMMONER_MATCH_PATH: &str = "ipc:///tmp/int.ipc";
+pub const IPC_BACKFILL_PATH: &str = "ipc:///tmp/int-backfill.ipc";
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct SummonerMatchQuery {
-    pub puuid: String,
-    pub match_id: String,
+    pub puuid: Puuid,
+    pub match_id: MatchId,
+}
+
+/// Progress of backfilling the match history of a summoner, for the guild that followed
+/// them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BackfillProgress {
+    pub puuid: Puuid,
+    pub guild_id: GuildId,
+    /// Number of match IDs found so far, whose matches are queued to be collected.
+    pub matches: u32,
+    /// Whether the backfill has finished, in which case no more matches will be found.
+    pub done: bool,
 }
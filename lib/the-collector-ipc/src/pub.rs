@@ -1,7 +1,8 @@
 use crate::error::IpcError;
+use nng::options::{Options, SendTimeout};
 use nng::{Error, Socket};
 use serde::Serialize;
-use std::{marker::PhantomData, sync::Arc};
+use std::{marker::PhantomData, sync::Arc, time::Duration};
 
 #[derive(Debug)]
 pub struct IpcPublisher<T: Serialize> {
@@ -19,6 +20,13 @@ impl<T: Serialize + Send + Sync> IpcPublisher<T> {
         })
     }
 
+    /// Give up on publishing after `timeout`, rather than waiting for a subscriber to
+    /// connect, so that optional messages do not hold up their publisher.
+    pub fn with_send_timeout(self, timeout: Duration) -> Result<Self, Error> {
+        self.socket.set_opt::<SendTimeout>(Some(timeout))?;
+        Ok(self)
+    }
+
     pub async fn publish(&self, data: T) -> Result<(), IpcError> {
         let bytes = bincode::serialize(&data)?;
         let socket = self.socket.clone();
@@ -19,6 +19,12 @@ impl<T: DeserializeOwned + Send + Sync> IpcSubscriber<T> {
         })
     }
 
+    /// Close the socket, so that a pending [`Self::recv`] returns an error rather than
+    /// blocking shutdown.
+    pub fn close(&self) {
+        self.socket.close();
+    }
+
     pub async fn recv(&self) -> Result<T, IpcError> {
         let socket = self.socket.clone();
         let bytes = tokio::task::spawn_blocking(move || socket.recv()).await??;
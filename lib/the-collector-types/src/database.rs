@@ -0,0 +1,49 @@
+//! Database values of the Discord IDs, which are stored as signed integers as not every
+//! database has unsigned ones. Riot IDs are stored as text, deriving their impls.
+
+use crate::{ChannelId, GuildId};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::{Database, Decode, Encode, Type};
+
+macro_rules! impl_discord_id {
+    ($name:ident) => {
+        impl<DB: Database> Type<DB> for $name
+        where
+            i64: Type<DB>,
+        {
+            fn type_info() -> DB::TypeInfo {
+                <i64 as Type<DB>>::type_info()
+            }
+
+            fn compatible(ty: &DB::TypeInfo) -> bool {
+                <i64 as Type<DB>>::compatible(ty)
+            }
+        }
+
+        impl<'q, DB: Database> Encode<'q, DB> for $name
+        where
+            i64: Encode<'q, DB>,
+        {
+            fn encode_by_ref(
+                &self,
+                buf: &mut <DB as Database>::ArgumentBuffer<'q>,
+            ) -> Result<IsNull, BoxDynError> {
+                // Snowflakes fit in 63 bits, so this is lossless in practice
+                (self.get() as i64).encode_by_ref(buf)
+            }
+        }
+
+        impl<'r, DB: Database> Decode<'r, DB> for $name
+        where
+            i64: Decode<'r, DB>,
+        {
+            fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+                Ok(Self::new(<i64 as Decode<DB>>::decode(value)? as u64))
+            }
+        }
+    };
+}
+
+impl_discord_id!(GuildId);
+impl_discord_id!(ChannelId);
@@ -0,0 +1,27 @@
+//! Conversions to and from the IDs of serenity.
+
+use crate::{ChannelId, GuildId};
+
+impl From<serenity::model::id::GuildId> for GuildId {
+    fn from(id: serenity::model::id::GuildId) -> Self {
+        Self::new(id.get())
+    }
+}
+
+impl From<GuildId> for serenity::model::id::GuildId {
+    fn from(id: GuildId) -> Self {
+        Self::new(id.get())
+    }
+}
+
+impl From<serenity::model::id::ChannelId> for ChannelId {
+    fn from(id: serenity::model::id::ChannelId) -> Self {
+        Self::new(id.get())
+    }
+}
+
+impl From<ChannelId> for serenity::model::id::ChannelId {
+    fn from(id: ChannelId) -> Self {
+        Self::new(id.get())
+    }
+}
@@ -0,0 +1,147 @@
+//! Identifiers shared between the crates of the workspace, so that different kinds of
+//! IDs cannot be mixed up.
+//!
+//! Riot IDs wrap the strings used by the Riot API, and Discord IDs wrap the snowflakes
+//! used by Discord. Conversions to and from other crates' types, and between database
+//! values, are behind the `riven`, `serenity` and `sqlx` features.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[cfg(feature = "sqlx")]
+mod database;
+#[cfg(feature = "serenity")]
+mod discord;
+#[cfg(feature = "riven")]
+mod riot;
+
+#[cfg(feature = "riven")]
+pub use riot::account_route;
+
+/// Defines a newtype around a `String` identifier used by the Riot API.
+macro_rules! riot_id {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+        #[cfg_attr(feature = "sqlx", derive(sqlx::Type), sqlx(transparent))]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn new(id: impl Into<String>) -> Self {
+                Self(id.into())
+            }
+
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            pub fn into_inner(self) -> String {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                Self(id.into())
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+    };
+}
+
+/// Defines a newtype around a Discord snowflake, which databases store as a signed
+/// 64-bit integer.
+macro_rules! discord_id {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(u64);
+
+        impl $name {
+            pub const fn new(id: u64) -> Self {
+                Self(id)
+            }
+
+            pub const fn get(self) -> u64 {
+                self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl From<u64> for $name {
+            fn from(id: u64) -> Self {
+                Self(id)
+            }
+        }
+
+        impl From<$name> for u64 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+    };
+}
+
+riot_id!(
+    /// Player universally unique identifier of a Riot account.
+    Puuid
+);
+
+riot_id!(
+    /// ID of a match, prefixed with the platform it was played on (e.g. `NA1_5000000001`).
+    MatchId
+);
+
+discord_id!(
+    /// ID of a Discord guild.
+    GuildId
+);
+
+discord_id!(
+    /// ID of a Discord guild channel.
+    ChannelId
+);
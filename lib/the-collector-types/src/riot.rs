@@ -0,0 +1,50 @@
+//! Conversions from the models of riven, which identifies accounts and matches by
+//! string, and routing of requests for them.
+
+use crate::{MatchId, Puuid};
+use riven::consts::{PlatformRoute, RegionalRoute};
+use riven::models::account_v1::Account;
+use riven::models::match_v5::{Match, Participant, Timeline};
+
+impl From<&Account> for Puuid {
+    fn from(account: &Account) -> Self {
+        Self::new(account.puuid.as_str())
+    }
+}
+
+impl From<&Participant> for Puuid {
+    fn from(participant: &Participant) -> Self {
+        Self::new(participant.puuid.as_str())
+    }
+}
+
+impl From<&Match> for MatchId {
+    fn from(data: &Match) -> Self {
+        Self::new(data.metadata.match_id.as_str())
+    }
+}
+
+impl From<&Timeline> for MatchId {
+    fn from(data: &Timeline) -> Self {
+        Self::new(data.metadata.match_id.as_str())
+    }
+}
+
+impl MatchId {
+    /// Platform the match was played on, parsed from the prefix of the ID (e.g. `EUW1`
+    /// for `EUW1_5000000001`), or [`None`] if the prefix is not a known platform.
+    pub fn platform(&self) -> Option<PlatformRoute> {
+        let (platform, _) = self.as_str().split_once('_')?;
+        platform.parse().ok()
+    }
+}
+
+/// Regional route to look up the accounts of summoners on a platform through account-v1.
+/// Accounts are shared between regions, but account-v1 is not served from
+/// [`RegionalRoute::SEA`], so Asia is used for its platforms instead.
+pub fn account_route(platform: PlatformRoute) -> RegionalRoute {
+    match platform.to_regional() {
+        RegionalRoute::SEA => RegionalRoute::ASIA,
+        route => route,
+    }
+}
@@ -0,0 +1,57 @@
+use the_collector_types::{ChannelId, GuildId, MatchId, Puuid};
+
+#[test]
+fn test_riot_ids() {
+    let match_id = MatchId::from("NA1_5000000001");
+    assert_eq!(match_id, "NA1_5000000001");
+    assert_eq!(match_id.to_string(), "NA1_5000000001");
+    assert_eq!(String::from(match_id.clone()), "NA1_5000000001");
+
+    let json = serde_json::to_string(&match_id).unwrap();
+    assert_eq!(json, r#""NA1_5000000001""#);
+    assert_eq!(serde_json::from_str::<MatchId>(&json).unwrap(), match_id);
+
+    let puuid = Puuid::new("puuid-0");
+    assert_eq!(puuid.as_str(), "puuid-0");
+    assert_eq!(puuid.into_inner(), "puuid-0");
+}
+
+#[test]
+fn test_discord_ids() {
+    let guild_id = GuildId::new(1234);
+    assert_eq!(guild_id.get(), 1234);
+    assert_eq!(guild_id.to_string(), "1234");
+    assert_eq!(u64::from(guild_id), 1234);
+
+    let json = serde_json::to_string(&guild_id).unwrap();
+    assert_eq!(json, "1234");
+    assert_eq!(serde_json::from_str::<GuildId>(&json).unwrap(), guild_id);
+
+    let channel_id = ChannelId::from(5678);
+    assert_eq!(serde_json::to_string(&Some(channel_id)).unwrap(), "5678");
+}
+
+#[cfg(feature = "riven")]
+#[test]
+fn test_match_id_platform() {
+    use riven::consts::PlatformRoute;
+
+    assert_eq!(
+        MatchId::from("EUW1_5000000001").platform(),
+        Some(PlatformRoute::EUW1)
+    );
+    assert_eq!(MatchId::from("OC1_1").platform(), Some(PlatformRoute::OC1));
+    assert_eq!(MatchId::from("5000000001").platform(), None);
+    assert_eq!(MatchId::from("XX_1").platform(), None);
+}
+
+#[cfg(feature = "riven")]
+#[test]
+fn test_account_route() {
+    use riven::consts::{PlatformRoute, RegionalRoute};
+    use the_collector_types::account_route;
+
+    assert_eq!(account_route(PlatformRoute::NA1), RegionalRoute::AMERICAS);
+    assert_eq!(account_route(PlatformRoute::EUW1), RegionalRoute::EUROPE);
+    assert_eq!(account_route(PlatformRoute::OC1), RegionalRoute::ASIA);
+}